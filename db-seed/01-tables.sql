@@ -9,10 +9,14 @@ CREATE TABLE users (
     id           VARCHAR(36)    PRIMARY KEY,
     username     VARCHAR(64)    NOT NULL UNIQUE,
     email        VARCHAR(128)   NOT NULL,
+    email_verified_at TIMESTAMPTZ,              -- NULL until the current email is verified
     created_by   VARCHAR(36),
     created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
     modified_by  VARCHAR(36),
-    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    deleted_by   VARCHAR(36),
+    deleted_at   TIMESTAMPTZ,                   -- NULL unless the user is soft-deleted
+    version      BIGINT         NOT NULL DEFAULT 1  -- incremented on every change, sent as ETag
 );
 
 -- Separate index for email lookup
@@ -33,6 +37,7 @@ CREATE TABLE devices (
     created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
     modified_by  VARCHAR(36),
     modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    version      BIGINT         NOT NULL DEFAULT 1,  -- incremented on every change, sent as ETag
     
     -- enforce unique (user_id, name)
     UNIQUE (user_id, name),
@@ -71,6 +76,10 @@ CREATE TABLE uploaded_files (
 -- If you want a composite key (e.g. one file_name per user), uncomment and adjust:
 --   UNIQUE (user_id, file_name);
 
+-- A user has at most one profile picture
+CREATE UNIQUE INDEX uq_uploaded_files_profile_picture
+    ON uploaded_files(user_id) WHERE file_type = 'profile_picture';
+
 
 -- ------------------------------------------------
 -- 4) user_auth table
@@ -85,3 +94,373 @@ CREATE TABLE user_auth (
     FOREIGN KEY (user_id) REFERENCES users(id)
 );
 
+
+
+-- ------------------------------------------------
+-- 5) refresh_tokens table
+-- ------------------------------------------------
+CREATE TABLE refresh_tokens (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    family_id     VARCHAR(36)  NOT NULL,  -- all tokens rotated from one login
+    token_hash    VARCHAR(64)  NOT NULL UNIQUE,  -- SHA-256 of the opaque token
+    expires_at    TIMESTAMPTZ  NOT NULL,
+    revoked_at    TIMESTAMPTZ,
+    replaced_by   VARCHAR(36),
+    client_id     VARCHAR(36),                  -- OAuth2 client the token was issued to
+    scope         TEXT,                         -- space-delimited scopes granted to the client
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Index to revoke a whole token family at once
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+
+
+-- ------------------------------------------------
+-- 6) revoked_tokens table
+-- ------------------------------------------------
+CREATE TABLE revoked_tokens (
+    jti           VARCHAR(36)  PRIMARY KEY,  -- JWT ID of the revoked access token
+    user_id       VARCHAR(36)  NOT NULL,
+    expires_at    TIMESTAMPTZ  NOT NULL,     -- rows are irrelevant after the token expired
+    revoked_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_revoked_tokens_expires_at ON revoked_tokens(expires_at);
+
+
+-- ------------------------------------------------
+-- 7) user_token_revocations table
+-- ------------------------------------------------
+-- Every token of the user issued at or before revoked_at is rejected.
+CREATE TABLE user_token_revocations (
+    user_id       VARCHAR(36)  PRIMARY KEY,
+    revoked_at    TIMESTAMPTZ  NOT NULL,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 8) roles table
+-- ------------------------------------------------
+CREATE TABLE roles (
+    id            VARCHAR(36)  PRIMARY KEY,
+    name          VARCHAR(64)  NOT NULL UNIQUE,
+    description   VARCHAR(255),
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+
+-- ------------------------------------------------
+-- 9) permissions table
+-- ------------------------------------------------
+CREATE TABLE permissions (
+    id            VARCHAR(36)  PRIMARY KEY,
+    name          VARCHAR(64)  NOT NULL UNIQUE,  -- e.g. device:delete
+    description   VARCHAR(255),
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+
+-- ------------------------------------------------
+-- 10) role_permissions table
+-- ------------------------------------------------
+CREATE TABLE role_permissions (
+    role_id       VARCHAR(36)  NOT NULL,
+    permission_id VARCHAR(36)  NOT NULL,
+
+    PRIMARY KEY (role_id, permission_id),
+
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    FOREIGN KEY (permission_id) REFERENCES permissions(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 11) user_roles table
+-- ------------------------------------------------
+CREATE TABLE user_roles (
+    user_id       VARCHAR(36)  NOT NULL,
+    role_id       VARCHAR(36)  NOT NULL,
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (user_id, role_id),
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 12) password_reset_tokens table
+-- ------------------------------------------------
+CREATE TABLE password_reset_tokens (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    token_hash    VARCHAR(64)  NOT NULL UNIQUE,  -- SHA-256 of the opaque token
+    expires_at    TIMESTAMPTZ  NOT NULL,
+    used_at       TIMESTAMPTZ,                   -- tokens are single-use
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 13) email_verification_tokens table
+-- ------------------------------------------------
+CREATE TABLE email_verification_tokens (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    email         VARCHAR(128) NOT NULL,         -- address the token was sent to
+    token_hash    VARCHAR(64)  NOT NULL UNIQUE,  -- SHA-256 of the opaque token
+    expires_at    TIMESTAMPTZ  NOT NULL,
+    used_at       TIMESTAMPTZ,                   -- tokens are single-use
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 14) login_throttles table
+-- ------------------------------------------------
+-- Failed login attempts per username and per client IP.
+CREATE TABLE login_throttles (
+    scope           VARCHAR(16)  NOT NULL,  -- 'username' or 'ip'
+    key             VARCHAR(128) NOT NULL,
+    failed_attempts INTEGER      NOT NULL DEFAULT 0,
+    last_failed_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until    TIMESTAMPTZ,
+
+    PRIMARY KEY (scope, key)
+);
+
+
+-- ------------------------------------------------
+-- 15) login_lockouts table
+-- ------------------------------------------------
+-- Every lockout is recorded for auditing.
+CREATE TABLE login_lockouts (
+    id              VARCHAR(36)  PRIMARY KEY,
+    scope           VARCHAR(16)  NOT NULL,
+    key             VARCHAR(128) NOT NULL,
+    failed_attempts INTEGER      NOT NULL,
+    locked_until    TIMESTAMPTZ  NOT NULL,
+    created_at      TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_login_lockouts_scope_key ON login_lockouts(scope, key);
+
+
+-- ------------------------------------------------
+-- 16) user_mfa table
+-- ------------------------------------------------
+-- TOTP second factor of a user. MFA is enabled once the enrollment has been
+-- confirmed with a valid code.
+CREATE TABLE user_mfa (
+    user_id         VARCHAR(36)  PRIMARY KEY,
+    totp_secret     VARCHAR(64)  NOT NULL,  -- base32, needed in clear to compute codes
+    enabled_at      TIMESTAMPTZ,            -- NULL until the enrollment is confirmed
+    last_used_step  BIGINT,                 -- last accepted time step, codes cannot be replayed
+    created_at      TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 17) mfa_recovery_codes table
+-- ------------------------------------------------
+CREATE TABLE mfa_recovery_codes (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    code_hash     VARCHAR(64)  NOT NULL,  -- SHA-256 of the normalized code
+    used_at       TIMESTAMPTZ,            -- codes are single-use
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_mfa_recovery_codes_user_id ON mfa_recovery_codes(user_id);
+
+
+-- ------------------------------------------------
+-- 18) mfa_challenges table
+-- ------------------------------------------------
+-- Issued by a password login of a user with MFA enabled and exchanged for an
+-- access token together with a valid code.
+CREATE TABLE mfa_challenges (
+    id              VARCHAR(36)  PRIMARY KEY,
+    user_id         VARCHAR(36)  NOT NULL,
+    token_hash      VARCHAR(64)  NOT NULL UNIQUE,  -- SHA-256 of the opaque token
+    failed_attempts INTEGER      NOT NULL DEFAULT 0,
+    expires_at      TIMESTAMPTZ  NOT NULL,
+    used_at         TIMESTAMPTZ,                   -- challenges are single-use
+    created_at      TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 19) api_keys table
+-- ------------------------------------------------
+-- Keys of machine-to-machine clients acting on behalf of a user.
+-- A key is presented as `<prefix>.<secret>`; the prefix identifies the key.
+CREATE TABLE api_keys (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    name          VARCHAR(64)  NOT NULL,
+    prefix        VARCHAR(16)  NOT NULL UNIQUE,
+    secret_hash   VARCHAR(64)  NOT NULL,          -- SHA-256 of the secret
+    scopes        TEXT[]       NOT NULL,          -- permission names the key is limited to
+    expires_at    TIMESTAMPTZ,                    -- NULL if the key does not expire
+    last_used_at  TIMESTAMPTZ,
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_api_keys_user_id ON api_keys(user_id);
+
+
+-- ------------------------------------------------
+-- 20) oauth_clients table
+-- ------------------------------------------------
+-- Clients registered to use the OAuth2 token endpoint.
+-- Tokens of the client_credentials grant act on behalf of the owner (`user_id`).
+CREATE TABLE oauth_clients (
+    client_id           VARCHAR(36)  PRIMARY KEY,
+    client_secret_hash  VARCHAR(64)  NOT NULL,   -- SHA-256 of the secret
+    name                VARCHAR(64)  NOT NULL,
+    user_id             VARCHAR(36)  NOT NULL,
+    scopes              TEXT[]       NOT NULL,   -- permission names the client may request
+    grant_types         TEXT[]       NOT NULL,   -- grant types the client may use
+    created_at          TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Refresh tokens of a client are deleted together with the client
+ALTER TABLE refresh_tokens
+    ADD FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE;
+
+
+-- ------------------------------------------------
+-- 21) user_sessions table
+-- ------------------------------------------------
+-- One row per login. The session ID is also the family_id of the refresh tokens
+-- rotated from the login and is carried by its access tokens as the `sid` claim.
+CREATE TABLE user_sessions (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    device_id     VARCHAR(36),                  -- device of the user the login came from, if known
+    user_agent    VARCHAR(256),
+    ip_address    VARCHAR(45),
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,  -- login or last refresh
+    revoked_at    TIMESTAMPTZ,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    -- FK to devices.id
+    FOREIGN KEY (device_id) REFERENCES devices(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_user_sessions_user_id ON user_sessions(user_id);
+
+
+-- ------------------------------------------------
+-- 22) impersonation_audit table
+-- ------------------------------------------------
+-- One row per request made with an impersonation token, and one for issuing it.
+-- No foreign keys, so that the trail outlives the users.
+CREATE TABLE impersonation_audit (
+    id          VARCHAR(36)    PRIMARY KEY,
+    actor_id    VARCHAR(36)    NOT NULL,        -- admin acting as the user
+    user_id     VARCHAR(36)    NOT NULL,        -- impersonated user
+    token_id    VARCHAR(36)    NOT NULL,        -- jti of the impersonation token
+    method      VARCHAR(10)    NOT NULL,
+    path        VARCHAR(2048)  NOT NULL,
+    status      SMALLINT       NOT NULL,        -- HTTP status of the response
+    created_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_impersonation_audit_actor_id ON impersonation_audit(actor_id);
+CREATE INDEX idx_impersonation_audit_user_id ON impersonation_audit(user_id);
+
+
+-- ------------------------------------------------
+-- 23) auth_events table
+-- ------------------------------------------------
+-- Authentication events: logins, failed logins, lockouts, refreshes and password changes.
+-- No foreign keys, so that the events outlive the users; failed logins of unknown
+-- usernames have no user_id.
+CREATE TABLE auth_events (
+    id          VARCHAR(36)   PRIMARY KEY,
+    event_type  VARCHAR(32)   NOT NULL,         -- login_success, login_failure, ...
+    user_id     VARCHAR(36),
+    username    VARCHAR(64),                    -- as entered for failed logins
+    ip_address  VARCHAR(45),
+    user_agent  VARCHAR(256),
+    detail      VARCHAR(64),                    -- reason of a failed login
+    created_at  TIMESTAMPTZ   NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_auth_events_created_at ON auth_events(created_at);
+CREATE INDEX idx_auth_events_user_id ON auth_events(user_id, created_at);
+CREATE INDEX idx_auth_events_event_type ON auth_events(event_type, created_at);
+
+
+-- ------------------------------------------------
+-- 24) oidc_logins table
+-- ------------------------------------------------
+-- Started by a redirect to the OpenID Connect provider and completed by its callback.
+-- The state sent to the provider identifies the login; the PKCE code verifier and the
+-- nonce are checked when the authorization code is exchanged.
+CREATE TABLE oidc_logins (
+    id             VARCHAR(36)   PRIMARY KEY,
+    state_hash     VARCHAR(64)   NOT NULL UNIQUE,  -- SHA-256 of the state
+    code_verifier  VARCHAR(128)  NOT NULL,
+    nonce          VARCHAR(64)   NOT NULL,
+    expires_at     TIMESTAMPTZ   NOT NULL,
+    used_at        TIMESTAMPTZ,                    -- logins are single-use
+    created_at     TIMESTAMPTZ   NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+
+-- ------------------------------------------------
+-- 25) user_identities table
+-- ------------------------------------------------
+-- Links an external identity, the subject of an OpenID Connect provider, to a user.
+CREATE TABLE user_identities (
+    id          VARCHAR(36)    PRIMARY KEY,
+    user_id     VARCHAR(36)    NOT NULL,
+    issuer      VARCHAR(255)   NOT NULL,        -- iss of the provider's ID tokens
+    subject     VARCHAR(255)   NOT NULL,        -- sub of the provider's ID tokens
+    email       VARCHAR(128),                   -- as reported by the provider when linked
+    created_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (issuer, subject),
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
@@ -22,6 +22,9 @@ INSERT INTO users (id, username, email, created_by, created_at, modified_by, mod
   ('00000000-0000-0000-0000-000000000020', 'user20', 'user20@example.com', NULL, NOW(), NULL, NOW()),
   ('00000000-0000-0000-0000-000000000021', 'apitest01', 'apitest01@example.com', NULL, NOW(), NULL, NOW());
 
+-- Seeded users have verified email addresses
+UPDATE users SET email_verified_at = NOW();
+
 -- Seed data for devices
 INSERT INTO devices (id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at) VALUES
 -- 4 devices per user
@@ -137,3 +140,45 @@ INSERT INTO devices (id, user_id, name, status, device_os, registered_at, create
 INSERT INTO user_auth
 (user_id, password_hash, created_at, modified_at)
 VALUES('00000000-0000-0000-0000-000000000021', '$argon2id$v=19$m=19456,t=2,p=1$XBFwBY52C9SpzkxON1OTLg$djDqZQvzxFKc9HOCWyZfKy+RlFTs0BJFSkcw/Tos14c', NOW(), NOW());
+
+-- Seed data for roles and permissions
+INSERT INTO roles (id, name, description, created_at) VALUES
+  ('00000000-0000-0000-0000-000000000001', 'admin', 'Full access to all resources', NOW()),
+  ('00000000-0000-0000-0000-000000000002', 'user', 'Regular users, limited to their own devices and files', NOW());
+
+INSERT INTO permissions (id, name, description, created_at) VALUES
+  ('00000000-0000-0000-0000-000000000001', 'user:read', 'Read users', NOW()),
+  ('00000000-0000-0000-0000-000000000002', 'user:create', 'Create users', NOW()),
+  ('00000000-0000-0000-0000-000000000003', 'user:update', 'Update users', NOW()),
+  ('00000000-0000-0000-0000-000000000004', 'user:delete', 'Delete users', NOW()),
+  ('00000000-0000-0000-0000-000000000005', 'device:read', 'Read devices', NOW()),
+  ('00000000-0000-0000-0000-000000000006', 'device:create', 'Create devices', NOW()),
+  ('00000000-0000-0000-0000-000000000007', 'device:update', 'Update devices', NOW()),
+  ('00000000-0000-0000-0000-000000000008', 'device:delete', 'Delete devices', NOW()),
+  ('00000000-0000-0000-0000-000000000009', 'file:read', 'Download files', NOW()),
+  ('00000000-0000-0000-0000-000000000010', 'file:delete', 'Delete files', NOW()),
+  ('00000000-0000-0000-0000-000000000011', 'session:revoke', 'Revoke all sessions of a user', NOW()),
+  ('00000000-0000-0000-0000-000000000012', 'client:manage', 'Register and delete OAuth2 clients', NOW()),
+  ('00000000-0000-0000-0000-000000000013', 'user:impersonate', 'Act as another user', NOW()),
+  ('00000000-0000-0000-0000-000000000014', 'auth_event:read', 'Query the authentication event log', NOW());
+
+-- admin: every permission
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT '00000000-0000-0000-0000-000000000001', id FROM permissions;
+
+-- user: read users, manage own devices and files (ownership is checked by the services)
+INSERT INTO role_permissions (role_id, permission_id) VALUES
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000001'),
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000005'),
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000006'),
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000007'),
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000008'),
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000009'),
+  ('00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000010');
+
+-- apitest01 is the admin, every other user has the user role
+INSERT INTO user_roles (user_id, role_id, created_at)
+SELECT id, '00000000-0000-0000-0000-000000000002', NOW() FROM users WHERE username <> 'apitest01';
+
+INSERT INTO user_roles (user_id, role_id, created_at) VALUES
+  ('00000000-0000-0000-0000-000000000021', '00000000-0000-0000-0000-000000000001', NOW());
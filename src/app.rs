@@ -3,8 +3,8 @@ use axum::{
     error_handling::HandleErrorLayer,
     extract::{DefaultBodyLimit, Request},
     http::{
-        header::{AUTHORIZATION, CONTENT_TYPE},
-        Method, StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH},
+        HeaderName, HeaderValue, Method, StatusCode,
     },
     middleware::{self, Next},
     response::{IntoResponse, Response},
@@ -15,7 +15,7 @@ use http_body_util::BodyExt;
 use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
     services::ServeDir,
     trace::TraceLayer,
 };
@@ -25,13 +25,18 @@ use utoipa::OpenApi;
 use crate::{
     common::{
         app_state::AppState,
+        auth_cookie::CSRF_HEADER,
+        config::Config,
         error::{handle_error, AppError},
         jwt,
     },
     domains::{
-        auth::{user_auth_routes, UserAuthApiDoc},
+        auth::{
+            oauth_routes, user_auth_protected_routes, user_auth_routes, well_known_routes,
+            UserAuthApiDoc,
+        },
         device::{device_routes, DeviceApiDoc},
-        file::{file_routes, FileApiDoc},
+        file::{file_routes, private_asset_routes, FileApiDoc},
         user::{user_routes, UserApiDoc},
     },
 };
@@ -59,12 +64,42 @@ fn create_swagger_ui() -> SwaggerUi {
         .url("/api-docs/file/openapi.json", FileApiDoc::openapi())
 }
 
+/// Builds the CORS layer.
+/// Without configured origins every origin is allowed, but without credentials. With cookie
+/// authentication enabled, the configured origins may also send the cookies cross-origin.
+fn create_cors_layer(config: &Config) -> CorsLayer {
+    let cors = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            AUTHORIZATION,
+            CONTENT_TYPE,
+            IF_MATCH,
+            jwt::API_KEY_HEADER.parse::<HeaderName>().unwrap(),
+            CSRF_HEADER.parse::<HeaderName>().unwrap(),
+        ])
+        .expose_headers([ETAG]);
+
+    if config.cors_allowed_origins.is_empty() {
+        return cors.allow_origin(Any);
+    }
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| origin.parse().ok())
+        .collect();
+    cors.allow_origin(AllowOrigin::list(origins))
+        .allow_credentials(config.auth_cookie_enabled)
+}
+
 pub fn create_router(state: AppState) -> Router {
     // Build a CORS layer that applies to everyone
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_origin(Any)
-        .allow_headers([AUTHORIZATION, CONTENT_TYPE]);
+    let cors = create_cors_layer(&state.config);
 
     // Create a common middleware stack for error handling, timeouts, and CORS.
     let middleware_stack = ServiceBuilder::new()
@@ -75,6 +110,14 @@ pub fn create_router(state: AppState) -> Router {
     // /auth routes (login, register, refresh, etc.) — no logging here
     let auth_router = Router::new()
         .nest("/auth", user_auth_routes())
+        .nest("/oauth", oauth_routes())
+        .layer(middleware::from_fn(make_request_response_inspecter(false)));
+
+    // /auth routes that require a valid token (logout, revoke sessions) — no logging here
+    let protected_auth_router = Router::new()
+        .nest("/auth", user_auth_protected_routes())
+        // enforce JWT authentication
+        .route_layer(middleware::from_fn_with_state(state.clone(), jwt::jwt_auth))
         .layer(middleware::from_fn(make_request_response_inspecter(false)));
 
     // Protected API routes
@@ -86,7 +129,7 @@ pub fn create_router(state: AppState) -> Router {
         // See https://docs.rs/axum/latest/axum/extract/struct.Multipart.html
         .layer(DefaultBodyLimit::max(state.config.asset_max_size))
         // enforce JWT authentication
-        .route_layer(middleware::from_fn(jwt::jwt_auth))
+        .route_layer(middleware::from_fn_with_state(state.clone(), jwt::jwt_auth))
         // attach inspecter
         .layer(middleware::from_fn(make_request_response_inspecter(true)));
 
@@ -96,13 +139,10 @@ pub fn create_router(state: AppState) -> Router {
         ServeDir::new(state.config.assets_public_path.clone()),
     );
 
-    let private_assets_routes = Router::new()
-        .nest_service(
-            state.config.assets_private_url.as_str(),
-            ServeDir::new(state.config.assets_private_path.clone()),
-        )
+    // private assets are only served to their owner, like `GET /file/{file_id}`
+    let private_assets_routes = private_asset_routes(&state.config.assets_private_url)
         // enforce JWT authentication
-        .route_layer(middleware::from_fn(jwt::jwt_auth))
+        .route_layer(middleware::from_fn_with_state(state.clone(), jwt::jwt_auth))
         // attach inspecter
         .layer(middleware::from_fn(make_request_response_inspecter(true)));
 
@@ -112,7 +152,9 @@ pub fn create_router(state: AppState) -> Router {
     // and add the state
     Router::new()
         .route("/health", axum::routing::get(health_check))
+        .nest("/.well-known", well_known_routes())
         .merge(auth_router)
+        .merge(protected_auth_router)
         .merge(protected_routes)
         .merge(create_swagger_ui())
         .merge(public_assets_routes)
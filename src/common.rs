@@ -1,11 +1,20 @@
 pub mod app_state;
+pub mod auth_cookie;
 pub mod bootstrap;
+pub mod client_ip;
 pub mod config;
 pub mod dto;
 pub mod error;
+pub mod etag;
 pub mod hash_util;
+pub mod http_client;
 pub mod jwt;
+pub mod jwt_keys;
+pub mod mail;
 pub mod multipart_helper;
 #[cfg(feature = "opentelemetry")]
 pub mod opentelemetry;
+pub mod password_policy;
+pub mod rbac;
+pub mod totp;
 pub mod ts_format;
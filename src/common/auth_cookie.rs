@@ -0,0 +1,233 @@
+//! Cookie-based authentication for browser clients.
+//!
+//! If `AUTH_COOKIE_ENABLED` is set, logins and refreshes also set the access token and
+//! the refresh token as HttpOnly cookies, so that the web frontend does not have to keep
+//! them in storage readable by scripts. `jwt_auth` accepts the access token cookie when
+//! no `Authorization` header is sent.
+//!
+//! Browsers send cookies with cross-site requests as well, so requests authenticated by
+//! cookie that change state are protected with a double-submit CSRF token: a random token
+//! is set in a cookie readable by scripts, and the frontend must echo it in the
+//! `X-CSRF-Token` header. Other sites can neither read the cookie nor set the header.
+
+use std::str::FromStr;
+
+use axum::http::{
+    header::{COOKIE, SET_COOKIE},
+    HeaderMap, HeaderValue,
+};
+
+use super::{config::Config, hash_util, jwt::AuthBody};
+
+/// Header carrying the CSRF token of a request authenticated by cookie.
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Path of the refresh token cookie, so that it is only sent to `/auth/refresh` and `/auth/logout`.
+const REFRESH_COOKIE_PATH: &str = "/auth";
+
+/// Value of the `SameSite` attribute of the cookies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+impl FromStr for SameSite {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "strict" => Ok(SameSite::Strict),
+            "lax" => Ok(SameSite::Lax),
+            "none" => Ok(SameSite::None),
+            _ => Err(format!("Invalid SameSite value: {value}")),
+        }
+    }
+}
+
+/// Returns the value of the cookie sent with the request, if any.
+pub fn get_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value.trim_matches('"'))
+        .filter(|value| !value.is_empty())
+}
+
+/// Checks that the request echoes the token of the CSRF cookie in the `X-CSRF-Token` header.
+pub fn csrf_token_matches(headers: &HeaderMap, cookie_name: &str) -> bool {
+    let header = headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim);
+    match (get_cookie(headers, cookie_name), header) {
+        (Some(cookie), Some(header)) => {
+            hash_util::constant_time_eq(cookie.as_bytes(), header.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+/// Builds the cookies set by a login or refresh: the access token, the refresh token and
+/// a new CSRF token. Returns no cookies if cookie authentication is disabled.
+pub fn login_cookies(config: &Config, auth_body: &AuthBody) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if !config.auth_cookie_enabled {
+        return headers;
+    }
+
+    let access_cookie = set_cookie(
+        config,
+        &config.auth_cookie_name,
+        &auth_body.access_token,
+        "/",
+        auth_body.expires_in,
+        true,
+    );
+    append_cookie(&mut headers, access_cookie);
+    if let Some(refresh_token) = &auth_body.refresh_token {
+        let refresh_cookie = set_cookie(
+            config,
+            &config.refresh_cookie_name,
+            refresh_token,
+            REFRESH_COOKIE_PATH,
+            config.refresh_token_ttl_seconds,
+            true,
+        );
+        append_cookie(&mut headers, refresh_cookie);
+    }
+    let csrf_cookie = set_cookie(
+        config,
+        &config.csrf_cookie_name,
+        &hash_util::generate_token(),
+        "/",
+        config.refresh_token_ttl_seconds,
+        false,
+    );
+    append_cookie(&mut headers, csrf_cookie);
+
+    headers
+}
+
+/// Builds the headers that remove the cookies set by `login_cookies`.
+/// Returns no headers if cookie authentication is disabled.
+pub fn clear_cookies(config: &Config) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if !config.auth_cookie_enabled {
+        return headers;
+    }
+
+    for (name, path, http_only) in [
+        (&config.auth_cookie_name, "/", true),
+        (&config.refresh_cookie_name, REFRESH_COOKIE_PATH, true),
+        (&config.csrf_cookie_name, "/", false),
+    ] {
+        append_cookie(
+            &mut headers,
+            set_cookie(config, name, "", path, 0, http_only),
+        );
+    }
+
+    headers
+}
+
+/// Formats a `Set-Cookie` header with the configured attributes.
+fn set_cookie(
+    config: &Config,
+    name: &str,
+    value: &str,
+    path: &str,
+    max_age: i64,
+    http_only: bool,
+) -> String {
+    let mut cookie = format!(
+        "{name}={value}; Path={path}; Max-Age={max_age}; SameSite={}",
+        config.auth_cookie_same_site.as_str()
+    );
+    if let Some(domain) = &config.auth_cookie_domain {
+        cookie.push_str(&format!("; Domain={domain}"));
+    }
+    if http_only {
+        cookie.push_str("; HttpOnly");
+    }
+    if config.auth_cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+fn append_cookie(headers: &mut HeaderMap, cookie: String) {
+    match HeaderValue::try_from(cookie) {
+        Ok(value) => {
+            headers.append(SET_COOKIE, value);
+        }
+        Err(err) => tracing::error!("Invalid Set-Cookie header: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_get_cookie() {
+        let headers = headers(&[
+            ("cookie", "theme=dark; access_token=abc.def"),
+            ("cookie", "csrf_token=\"xyz\"; empty="),
+        ]);
+
+        assert_eq!(get_cookie(&headers, "access_token"), Some("abc.def"));
+        assert_eq!(get_cookie(&headers, "csrf_token"), Some("xyz"));
+        assert_eq!(get_cookie(&headers, "empty"), None);
+        assert_eq!(get_cookie(&headers, "token"), None);
+    }
+
+    #[test]
+    fn test_csrf_token_matches() {
+        let cookie = ("cookie", "csrf_token=xyz");
+
+        assert!(csrf_token_matches(
+            &headers(&[cookie, ("x-csrf-token", "xyz")]),
+            "csrf_token"
+        ));
+        assert!(!csrf_token_matches(
+            &headers(&[cookie, ("x-csrf-token", "xyy")]),
+            "csrf_token"
+        ));
+        assert!(!csrf_token_matches(&headers(&[cookie]), "csrf_token"));
+        assert!(!csrf_token_matches(
+            &headers(&[("x-csrf-token", "xyz")]),
+            "csrf_token"
+        ));
+    }
+
+    #[test]
+    fn test_parse_same_site() {
+        assert_eq!("lax".parse::<SameSite>(), Ok(SameSite::Lax));
+        assert_eq!("None".parse::<SameSite>(), Ok(SameSite::None));
+        assert!("always".parse::<SameSite>().is_err());
+    }
+}
@@ -3,6 +3,8 @@ use std::sync::Arc;
 use sqlx::PgPool;
 
 use crate::common::config::Config;
+use crate::common::mail::create_mail_sender;
+use crate::common::password_policy::PasswordPolicy;
 use crate::domains::auth::{AuthService, AuthServiceTrait};
 use crate::domains::device::{DeviceService, DeviceServiceTrait};
 use crate::domains::file::{FileService, FileServiceTrait};
@@ -12,12 +14,22 @@ use crate::{common::app_state::AppState, domains::user::UserService};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 /// Constructs and wires all application services and returns a configured AppState.
+/// Panics if the configured mail transport cannot be set up or the breached password
+/// list cannot be read.
 pub fn build_app_state(pool: PgPool, config: Config) -> AppState {
-    let auth_service: Arc<dyn AuthServiceTrait> = AuthService::create_service(pool.clone());
+    let mail_sender = create_mail_sender(&config)
+        .unwrap_or_else(|err| panic!("Failed to set up mail sender: {err}"));
+    let password_policy = PasswordPolicy::from_config(&config)
+        .unwrap_or_else(|err| panic!("Failed to load breached password list: {err}"));
+    let auth_service: Arc<dyn AuthServiceTrait> =
+        AuthService::create_service(config.clone(), pool.clone(), mail_sender, password_policy);
     let file_service: Arc<dyn FileServiceTrait> =
         FileService::create_service(config.clone(), pool.clone());
-    let user_service: Arc<dyn UserServiceTrait> =
-        UserService::create_service(pool.clone(), Arc::clone(&file_service));
+    let user_service: Arc<dyn UserServiceTrait> = UserService::create_service(
+        pool.clone(),
+        Arc::clone(&file_service),
+        Arc::clone(&auth_service),
+    );
     let device_service: Arc<dyn DeviceServiceTrait> = DeviceService::create_service(pool.clone());
 
     AppState::new(
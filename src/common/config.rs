@@ -4,6 +4,8 @@ use std::env;
 use std::time::Duration;
 use tokio::time::sleep;
 
+use super::auth_cookie::SameSite;
+
 /// Config is a struct that holds the configuration for the application.
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -22,6 +24,66 @@ pub struct Config {
 
     pub asset_allowed_extensions_pattern: Regex,
     pub asset_max_size: usize,
+
+    pub access_token_ttl_seconds: i64,
+    pub refresh_token_ttl_seconds: i64,
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub jwt_leeway_seconds: u64,
+    pub revocation_cache_ttl_seconds: u64,
+    pub permission_cache_ttl_seconds: u64,
+    pub password_reset_ttl_seconds: i64,
+    pub email_verification_ttl_seconds: i64,
+    pub require_verified_email: bool,
+
+    pub auth_cookie_enabled: bool,
+    pub auth_cookie_name: String,
+    pub refresh_cookie_name: String,
+    pub csrf_cookie_name: String,
+    pub auth_cookie_secure: bool,
+    pub auth_cookie_same_site: SameSite,
+    pub auth_cookie_domain: Option<String>,
+    pub cors_allowed_origins: Vec<String>,
+
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+
+    pub password_min_length: usize,
+    pub password_min_character_classes: usize,
+    pub password_reject_username: bool,
+    pub password_breached_list_path: Option<String>,
+
+    pub login_attempt_window_seconds: i64,
+    pub login_backoff_base_seconds: i64,
+    pub login_lockout_seconds: i64,
+    pub login_user_backoff_after: i32,
+    pub login_user_lockout_after: i32,
+    pub login_ip_backoff_after: i32,
+    pub login_ip_lockout_after: i32,
+    pub trust_forwarded_for: bool,
+
+    pub totp_issuer: String,
+    pub mfa_challenge_ttl_seconds: i64,
+    pub mfa_max_attempts: i32,
+
+    pub oidc_discovery_url: Option<String>,
+    pub oidc_client_id: String,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_redirect_uri: String,
+    pub oidc_scopes: String,
+    pub oidc_auto_provision: bool,
+    pub oidc_login_ttl_seconds: i64,
+
+    pub mail_transport: String,
+    pub mail_from: String,
+    pub mail_outbox_path: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+
+    pub require_if_match: bool,
 }
 
 /// from_env reads the environment variables and returns a Config struct.
@@ -32,6 +94,7 @@ impl Config {
         dotenvy::dotenv().ok();
 
         let ext_val = env::var("ASSET_ALLOWED_EXTENSIONS")?;
+        let (argon2_memory_kib, argon2_iterations, argon2_parallelism) = argon2_params_from_env();
 
         Ok(Self {
             database_url: env::var("DATABASE_URL")?,
@@ -63,10 +126,173 @@ impl Config {
 
             asset_max_size: env::var("ASSET_MAX_SIZE")
                 .map(|s| s.parse::<usize>().unwrap_or(50 * 1024 * 1024))?, // Default to 50MB
+
+            access_token_ttl_seconds: env::var("ACCESS_TOKEN_TTL_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(15 * 60))
+                .unwrap_or(15 * 60), // Default to 15 minutes
+            refresh_token_ttl_seconds: env::var("REFRESH_TOKEN_TTL_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(30 * 24 * 60 * 60))
+                .unwrap_or(30 * 24 * 60 * 60), // Default to 30 days
+            jwt_issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "clean_axum_demo".into()),
+            jwt_audience: env::var("JWT_AUDIENCE").unwrap_or_else(|_| "clean_axum_demo".into()),
+            jwt_leeway_seconds: env::var("JWT_LEEWAY_SECONDS")
+                .map(|s| s.parse::<u64>().unwrap_or(30))
+                .unwrap_or(30),
+            revocation_cache_ttl_seconds: env::var("REVOCATION_CACHE_TTL_SECONDS")
+                .map(|s| s.parse::<u64>().unwrap_or(30))
+                .unwrap_or(30),
+            permission_cache_ttl_seconds: env::var("PERMISSION_CACHE_TTL_SECONDS")
+                .map(|s| s.parse::<u64>().unwrap_or(60))
+                .unwrap_or(60),
+            password_reset_ttl_seconds: env::var("PASSWORD_RESET_TTL_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(30 * 60))
+                .unwrap_or(30 * 60), // Default to 30 minutes
+            email_verification_ttl_seconds: env::var("EMAIL_VERIFICATION_TTL_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(24 * 60 * 60))
+                .unwrap_or(24 * 60 * 60), // Default to 24 hours
+            require_verified_email: env::var("REQUIRE_VERIFIED_EMAIL")
+                .map(|s| s.parse::<bool>().unwrap_or(false))
+                .unwrap_or(false),
+
+            auth_cookie_enabled: env::var("AUTH_COOKIE_ENABLED")
+                .map(|s| s.parse::<bool>().unwrap_or(false))
+                .unwrap_or(false),
+            auth_cookie_name: env::var("AUTH_COOKIE_NAME")
+                .unwrap_or_else(|_| "access_token".into()),
+            refresh_cookie_name: env::var("REFRESH_COOKIE_NAME")
+                .unwrap_or_else(|_| "refresh_token".into()),
+            csrf_cookie_name: env::var("CSRF_COOKIE_NAME").unwrap_or_else(|_| "csrf_token".into()),
+            auth_cookie_secure: env::var("AUTH_COOKIE_SECURE")
+                .map(|s| s.parse::<bool>().unwrap_or(true))
+                .unwrap_or(true),
+            auth_cookie_same_site: env::var("AUTH_COOKIE_SAME_SITE")
+                .map(|s| s.parse::<SameSite>().unwrap_or(SameSite::Strict))
+                .unwrap_or(SameSite::Strict),
+            auth_cookie_domain: env::var("AUTH_COOKIE_DOMAIN")
+                .ok()
+                .filter(|d| !d.is_empty()),
+            cors_allowed_origins: env::var("CORS_ALLOWED_ORIGINS")
+                .map(|s| {
+                    s.split(',')
+                        .map(|origin| origin.trim().to_string())
+                        .filter(|origin| !origin.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+
+            argon2_memory_kib,
+            argon2_iterations,
+            argon2_parallelism,
+
+            password_min_length: env::var("PASSWORD_MIN_LENGTH")
+                .map(|s| s.parse::<usize>().unwrap_or(8))
+                .unwrap_or(8),
+            password_min_character_classes: env::var("PASSWORD_MIN_CHARACTER_CLASSES")
+                .map(|s| s.parse::<usize>().unwrap_or(2))
+                .unwrap_or(2),
+            password_reject_username: env::var("PASSWORD_REJECT_USERNAME")
+                .map(|s| s.parse::<bool>().unwrap_or(true))
+                .unwrap_or(true),
+            // An empty value disables the breached password check.
+            password_breached_list_path: match env::var("PASSWORD_BREACHED_LIST_PATH") {
+                Ok(path) => Some(path).filter(|p| !p.is_empty()),
+                Err(_) => Some("assets/common_passwords.txt".into()),
+            },
+
+            login_attempt_window_seconds: env::var("LOGIN_ATTEMPT_WINDOW_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(15 * 60))
+                .unwrap_or(15 * 60), // Default to 15 minutes
+            login_backoff_base_seconds: env::var("LOGIN_BACKOFF_BASE_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(1))
+                .unwrap_or(1),
+            login_lockout_seconds: env::var("LOGIN_LOCKOUT_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(15 * 60))
+                .unwrap_or(15 * 60), // Default to 15 minutes
+            login_user_backoff_after: env::var("LOGIN_USER_BACKOFF_AFTER")
+                .map(|s| s.parse::<i32>().unwrap_or(3))
+                .unwrap_or(3),
+            login_user_lockout_after: env::var("LOGIN_USER_LOCKOUT_AFTER")
+                .map(|s| s.parse::<i32>().unwrap_or(10))
+                .unwrap_or(10),
+            login_ip_backoff_after: env::var("LOGIN_IP_BACKOFF_AFTER")
+                .map(|s| s.parse::<i32>().unwrap_or(10))
+                .unwrap_or(10),
+            login_ip_lockout_after: env::var("LOGIN_IP_LOCKOUT_AFTER")
+                .map(|s| s.parse::<i32>().unwrap_or(50))
+                .unwrap_or(50),
+            trust_forwarded_for: env::var("TRUST_FORWARDED_FOR")
+                .map(|s| s.parse::<bool>().unwrap_or(false))
+                .unwrap_or(false),
+
+            totp_issuer: env::var("TOTP_ISSUER").unwrap_or_else(|_| "clean_axum_demo".into()),
+            mfa_challenge_ttl_seconds: env::var("MFA_CHALLENGE_TTL_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(5 * 60))
+                .unwrap_or(5 * 60), // Default to 5 minutes
+            mfa_max_attempts: env::var("MFA_MAX_ATTEMPTS")
+                .map(|s| s.parse::<i32>().unwrap_or(5))
+                .unwrap_or(5),
+
+            // OpenID Connect login is disabled unless a discovery URL is set.
+            oidc_discovery_url: env::var("OIDC_DISCOVERY_URL")
+                .ok()
+                .filter(|u| !u.is_empty()),
+            oidc_client_id: env::var("OIDC_CLIENT_ID").unwrap_or_default(),
+            oidc_client_secret: env::var("OIDC_CLIENT_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            oidc_redirect_uri: env::var("OIDC_REDIRECT_URI")
+                .unwrap_or_else(|_| "http://localhost:8080/auth/oidc/callback".into()),
+            oidc_scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid profile email".into()),
+            oidc_auto_provision: env::var("OIDC_AUTO_PROVISION")
+                .map(|s| s.parse::<bool>().unwrap_or(false))
+                .unwrap_or(false),
+            oidc_login_ttl_seconds: env::var("OIDC_LOGIN_TTL_SECONDS")
+                .map(|s| s.parse::<i64>().unwrap_or(10 * 60))
+                .unwrap_or(10 * 60), // Default to 10 minutes
+
+            mail_transport: env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "file".into()),
+            mail_from: env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".into()),
+            mail_outbox_path: env::var("MAIL_OUTBOX_PATH").unwrap_or_else(|_| "mail_outbox".into()),
+            smtp_host: env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".into()),
+            smtp_port: env::var("SMTP_PORT")
+                .map(|s| s.parse::<u16>().unwrap_or(587))
+                .unwrap_or(587),
+            smtp_username: env::var("SMTP_USERNAME").ok(),
+            smtp_password: env::var("SMTP_PASSWORD").ok(),
+
+            require_if_match: env::var("REQUIRE_IF_MATCH")
+                .map(|s| s.parse::<bool>().unwrap_or(false))
+                .unwrap_or(false),
         })
     }
 }
 
+/// Reads the Argon2id cost parameters for password hashes.
+/// Missing values default to those of the argon2 crate (19 MiB, 2 iterations, 1 lane);
+/// if Argon2 rejects the combination, all three fall back to the defaults.
+fn argon2_params_from_env() -> (u32, u32, u32) {
+    let read = |name: &str, default: u32| {
+        env::var(name)
+            .map(|s| s.parse::<u32>().unwrap_or(default))
+            .unwrap_or(default)
+    };
+    let memory_kib = read("ARGON2_MEMORY_KIB", argon2::Params::DEFAULT_M_COST);
+    let iterations = read("ARGON2_ITERATIONS", argon2::Params::DEFAULT_T_COST);
+    let parallelism = read("ARGON2_PARALLELISM", argon2::Params::DEFAULT_P_COST);
+
+    match argon2::Params::new(memory_kib, iterations, parallelism, None) {
+        Ok(_) => (memory_kib, iterations, parallelism),
+        Err(err) => {
+            eprintln!("Invalid Argon2 parameters ({err}), using the defaults");
+            (
+                argon2::Params::DEFAULT_M_COST,
+                argon2::Params::DEFAULT_T_COST,
+                argon2::Params::DEFAULT_P_COST,
+            )
+        }
+    }
+}
+
 /// setup_database initializes the database connection pool.
 pub async fn setup_database(config: &Config) -> Result<PgPool, sqlx::Error> {
     // Attempt to connect repeatedly, with a small delay, until success (or a max number of tries)
@@ -1,5 +1,6 @@
 use axum::response::{IntoResponse, Response};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
 
 /// A standardized API response format.
 #[derive(Serialize, Deserialize, Debug)]
@@ -50,6 +51,46 @@ where
     }
 }
 
+/// A page of a paginated list.
+/// `page` starts at 1 and `total` is the number of items on all pages.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct PageDto<T> {
+    pub items: Vec<T>,
+    pub page: i64,
+    pub page_size: i64,
+    pub total: i64,
+}
+
+/// Deserializes a member of a JSON merge patch (RFC 7396): a value becomes `Some(Some(value))`
+/// and `null` becomes `Some(None)`. Used with `#[serde(default)]`, so that an absent member
+/// stays `None`.
+pub fn patch_field<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Direction in which a list is sorted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    /// Returns the SQL keyword of the direction.
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
 /// A wrapper struct for the API response.
 /// This struct is used to convert the API response into a format that can be returned by Axum.
 /// It implements the `IntoResponse` trait, which allows it to be used as a response in Axum handlers.
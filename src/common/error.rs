@@ -1,12 +1,17 @@
 use axum::{
-    http::StatusCode,
+    http::{
+        header::{CACHE_CONTROL, RETRY_AFTER, WWW_AUTHENTICATE},
+        StatusCode,
+    },
     response::{IntoResponse, Response},
     BoxError,
 };
 
 use sqlx::Error as SqlxError;
+use std::collections::BTreeMap;
 use thiserror::Error;
 use tracing::error;
+use validator::ValidationErrors;
 
 use crate::common::dto::RestApiResponse;
 
@@ -28,9 +33,21 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    /// Used for validation errors of individual request fields,
+    /// which are listed per field in the response data.
+    #[error("Validation error: {0}")]
+    InvalidFields(ValidationErrors),
+
     #[error("Forbidden Request")]
     Forbidden,
 
+    /// Used for optimistic concurrency control: the `If-Match` header does not match the
+    /// current version of the record, or is missing although it is required.
+    #[error("The resource has been modified, reload it and retry")]
+    PreconditionFailed,
+    #[error("The If-Match header is required")]
+    PreconditionRequired,
+
     /// Used for file-related errors
     #[error("File data is empty")]
     InvalidFileData,
@@ -53,20 +70,69 @@ pub enum AppError {
     InvalidToken,
     #[error("Token creation error")]
     TokenCreation,
+    #[error("Missing or invalid CSRF token")]
+    InvalidCsrfToken,
     #[error("User not found")]
     UserNotFound,
+    #[error("Email address not verified")]
+    EmailNotVerified,
+    /// Used for OpenID Connect logins: the identity provider failed or sent an invalid
+    /// response, or no user is linked to the external identity.
+    #[error("Identity provider error")]
+    IdentityProvider,
+    #[error("No user is linked to the external identity")]
+    IdentityNotLinked,
+    /// Login is throttled; holds the number of seconds until the next attempt is allowed.
+    #[error("Too many failed login attempts, retry in {0} seconds")]
+    TooManyAttempts(i64),
+
+    /// Used for errors of the OAuth2 endpoints, which are answered in the OAuth2 format
+    /// instead of the API response envelope so that OAuth2 client libraries understand them.
+    #[error("{1}")]
+    OAuth(OAuthErrorCode, String),
+}
+
+/// Error codes of the OAuth2 endpoints (RFC 6749, section 5.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OAuthErrorCode {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnauthorizedClient,
+    UnsupportedGrantType,
+    InvalidScope,
+}
+
+impl OAuthErrorCode {
+    /// Returns the code as sent in the `error` field.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthErrorCode::InvalidRequest => "invalid_request",
+            OAuthErrorCode::InvalidClient => "invalid_client",
+            OAuthErrorCode::InvalidGrant => "invalid_grant",
+            OAuthErrorCode::UnauthorizedClient => "unauthorized_client",
+            OAuthErrorCode::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthErrorCode::InvalidScope => "invalid_scope",
+        }
+    }
 }
 
 /// Converts the AppError enum into an HTTP response.
 /// It maps the error to an appropriate HTTP status code and constructs a JSON response body.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let retry_after = match self {
+            AppError::TooManyAttempts(seconds) => Some(seconds),
+            _ => None,
+        };
         let status = match self {
-            AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            AppError::ValidationError(_) | AppError::InvalidFields(_) => StatusCode::BAD_REQUEST,
             AppError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
             AppError::InvalidFileData
             | AppError::FileSizeExceeded
             | AppError::InvalidFileName
@@ -75,15 +141,70 @@ impl IntoResponse for AppError {
             AppError::MissingCredentials => StatusCode::BAD_REQUEST,
             AppError::InvalidToken => StatusCode::UNAUTHORIZED,
             AppError::TokenCreation => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::InvalidCsrfToken => StatusCode::FORBIDDEN,
             AppError::UserNotFound => StatusCode::NOT_FOUND,
+            AppError::EmailNotVerified => StatusCode::FORBIDDEN,
+            AppError::IdentityProvider => StatusCode::BAD_GATEWAY,
+            AppError::IdentityNotLinked => StatusCode::FORBIDDEN,
+            AppError::TooManyAttempts(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::OAuth(OAuthErrorCode::InvalidClient, _) => StatusCode::UNAUTHORIZED,
+            AppError::OAuth(..) => StatusCode::BAD_REQUEST,
+        };
+        if let AppError::OAuth(code, description) = self {
+            return oauth_error_response(status, code, description);
+        }
+        let data = match &self {
+            AppError::InvalidFields(errors) => Some(field_error_messages(errors)),
+            _ => None,
         };
-        let body = axum::Json(ApiResponse::<()> {
+        let body = axum::Json(ApiResponse {
             status: status.as_u16(),
             message: self.to_string(),
-            data: None,
+            data,
         });
 
-        (status, body).into_response()
+        match retry_after {
+            Some(seconds) => (status, [(RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+/// Lists the messages of the validation errors per field, falling back to the error code.
+fn field_error_messages(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|e| e.message.as_ref().unwrap_or(&e.code).to_string())
+                .collect();
+            (field.to_string(), messages)
+        })
+        .collect()
+}
+
+/// Builds an OAuth2 error response (RFC 6749, section 5.2).
+/// Failed client authentication asks for HTTP Basic credentials, as required for `invalid_client`.
+fn oauth_error_response(status: StatusCode, code: OAuthErrorCode, description: String) -> Response {
+    let body = axum::Json(serde_json::json!({
+        "error": code.as_str(),
+        "error_description": description,
+    }));
+
+    if code == OAuthErrorCode::InvalidClient {
+        (
+            status,
+            [
+                (CACHE_CONTROL, "no-store"),
+                (WWW_AUTHENTICATE, "Basic realm=\"oauth\""),
+            ],
+            body,
+        )
+            .into_response()
+    } else {
+        (status, [(CACHE_CONTROL, "no-store")], body).into_response()
     }
 }
 
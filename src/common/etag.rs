@@ -0,0 +1,100 @@
+//! Optimistic concurrency control with entity tags (RFC 9110, section 13.1.1).
+//!
+//! Versioned records carry a `version` that is incremented on every change. It is returned
+//! as a strong `ETag`, and clients send it back in `If-Match` when changing the record: if
+//! the record has been changed in the meantime, the request fails with 412 Precondition
+//! Failed instead of silently overwriting the other change. Requests without `If-Match`
+//! are accepted, unless `REQUIRE_IF_MATCH` is set; then they fail with 428.
+
+use axum::{
+    extract::FromRequestParts,
+    http::{
+        header::{ETAG, IF_MATCH},
+        request::Parts,
+        HeaderName,
+    },
+};
+
+use super::{app_state::AppState, error::AppError};
+
+/// Returns the `ETag` header for the version of a record.
+pub fn etag_header(version: i64) -> [(HeaderName, String); 1] {
+    [(ETAG, format!("\"{version}\""))]
+}
+
+/// The `If-Match` precondition of a request: the versions the change may apply to,
+/// or `None` if it applies to any version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IfMatch(Option<Vec<i64>>);
+
+impl IfMatch {
+    /// Parses the value of an `If-Match` header; `*` matches any version.
+    /// `If-Match` uses the strong comparison, so weak and malformed entity tags never match.
+    pub fn parse(value: &str) -> Self {
+        let mut versions = Vec::new();
+        for tag in value.split(',').map(str::trim) {
+            if tag == "*" {
+                return Self(None);
+            }
+            if let Some(version) = tag
+                .strip_prefix('"')
+                .and_then(|tag| tag.strip_suffix('"'))
+                .and_then(|version| version.parse().ok())
+            {
+                versions.push(version);
+            }
+        }
+        Self(Some(versions))
+    }
+
+    /// Checks the current version of the record against the precondition.
+    /// Returns `PreconditionFailed` if the record has been changed since.
+    pub fn check(&self, version: i64) -> Result<(), AppError> {
+        match &self.0 {
+            Some(versions) if !versions.contains(&version) => Err(AppError::PreconditionFailed),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Extracts the `If-Match` precondition; several header lines are combined.
+/// A missing header matches any version, or is rejected if `If-Match` is required.
+impl FromRequestParts<AppState> for IfMatch {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, AppError> {
+        let values: Vec<&str> = parts
+            .headers
+            .get_all(IF_MATCH)
+            .iter()
+            .map(|value| value.to_str().unwrap_or_default())
+            .collect();
+
+        if values.is_empty() {
+            return if state.config.require_if_match {
+                Err(AppError::PreconditionRequired)
+            } else {
+                Ok(Self::default())
+            };
+        }
+        Ok(Self::parse(&values.join(",")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_match() {
+        assert!(IfMatch::default().check(3).is_ok());
+        assert!(IfMatch::parse("*").check(3).is_ok());
+        assert!(IfMatch::parse("\"3\"").check(3).is_ok());
+        assert!(IfMatch::parse("\"1\", \"3\"").check(3).is_ok());
+        assert!(IfMatch::parse("\"2\"").check(3).is_err());
+        // Weak and malformed tags never match.
+        assert!(IfMatch::parse("W/\"3\"").check(3).is_err());
+        assert!(IfMatch::parse("3").check(3).is_err());
+        assert!(IfMatch::parse("").check(3).is_err());
+    }
+}
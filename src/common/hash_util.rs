@@ -1,16 +1,69 @@
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    Algorithm, Argon2, Params, Version,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use data_encoding::BASE32_NOPAD;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use super::config::Config;
+
+/// Number of random bytes used for opaque tokens (256 bits).
+const OPAQUE_TOKEN_BYTES: usize = 32;
+
+/// Number of random bytes in the prefix that identifies an API key (12 hex characters).
+const API_KEY_PREFIX_BYTES: usize = 6;
 
-/// Hash the provided password using Argon2.
-pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
+/// Number of random bytes used for MFA recovery codes (80 bits, 16 base32 characters).
+const RECOVERY_CODE_BYTES: usize = 10;
+
+/// Argon2id cost parameters used for new password hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PasswordParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordParams {
+    /// Parameters configured by `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM`.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            memory_kib: config.argon2_memory_kib,
+            iterations: config.argon2_iterations,
+            parallelism: config.argon2_parallelism,
+        }
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+/// The defaults of the argon2 crate (19 MiB, 2 iterations, 1 lane), as recommended by OWASP.
+impl Default for PasswordParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Hash the provided password using Argon2id with the given parameters.
+pub fn hash_password(password: &str, params: PasswordParams) -> Result<String, argon2::Error> {
     let salt = SaltString::generate(&mut OsRng);
 
-    // Argon2 with default params (Argon2id v19)
-    let argon2 = Argon2::default();
+    let argon2 = params.argon2()?;
 
-    // Hash password to PHC string ($argon2id$v=19$...)
+    // Hash password to PHC string ($argon2id$v=19$m=...,t=...,p=...$...)
     let hash_password = argon2
         .hash_password(password.as_bytes(), &salt)
         .map_err(|e| {
@@ -21,7 +74,9 @@ pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
     Ok(hash_password.to_string())
 }
 
-/// Verify that a password matches the provided çå.
+/// Verify that a password matches the provided hash.
+/// Algorithm, version and parameters are taken from the hash, so hashes made with
+/// other parameters, or with `argon2i`, are still accepted; see `needs_rehash`.
 pub fn verify_password(password_hash: &str, password: &str) -> bool {
     let parsed_hash = match PasswordHash::new(password_hash) {
         Ok(hash) => hash,
@@ -35,14 +90,119 @@ pub fn verify_password(password_hash: &str, password: &str) -> bool {
         .is_ok()
 }
 
+/// Returns `true` if the hash was not made with Argon2id v19 and the given parameters,
+/// e.g. an `argon2i` hash or one made before the parameters were raised.
+pub fn needs_rehash(password_hash: &str, params: PasswordParams) -> bool {
+    let Ok(parsed) = PasswordHash::new(password_hash) else {
+        return true;
+    };
+    if parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    !Params::try_from(&parsed).is_ok_and(|p| {
+        p.m_cost() == params.memory_kib
+            && p.t_cost() == params.iterations
+            && p.p_cost() == params.parallelism
+    })
+}
+
+/// Hashes of random passwords, used when there is no stored hash to verify against.
+/// One hash is kept per parameter set, so that verifying it costs as much as verifying
+/// a current hash.
+static DUMMY_PASSWORD_HASHES: LazyLock<Mutex<HashMap<PasswordParams, String>>> =
+    LazyLock::new(Default::default);
+
+/// Verify the password against the hash, or against a dummy hash if there is none.
+/// Unknown users thus take as long to reject as wrong passwords, which prevents
+/// telling them apart by response time. Always returns `false` without a hash.
+pub fn verify_password_or_dummy(
+    password_hash: Option<&str>,
+    password: &str,
+    params: PasswordParams,
+) -> bool {
+    match password_hash {
+        Some(password_hash) => verify_password(password_hash, password),
+        None => {
+            let dummy_hash = DUMMY_PASSWORD_HASHES
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(params)
+                .or_insert_with(|| hash_password(&generate_token(), params).unwrap_or_default())
+                .clone();
+            verify_password(&dummy_hash, password);
+            false
+        }
+    }
+}
+
+/// Generate a random, URL-safe opaque token (e.g. refresh token).
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; OPAQUE_TOKEN_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Generate the random prefix that identifies an API key.
+/// The prefix is stored in clear, so it must never be the only secret part of a key.
+pub fn generate_api_key_prefix() -> String {
+    let mut bytes = [0u8; API_KEY_PREFIX_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Generate a random MFA recovery code such as `abcd-efgh-ijkl-mnop`.
+/// Codes are meant to be typed, so they are lowercase base32 in groups of four.
+pub fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; RECOVERY_CODE_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD
+        .encode(&bytes)
+        .to_lowercase()
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Normalize a recovery code as typed by the user before hashing it,
+/// ignoring case, whitespace and dashes.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Hash an opaque token with SHA-256 so that only the digest is persisted.
+/// Opaque tokens are high-entropy, so a fast hash is sufficient here.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Compares two byte strings without returning early on the first difference.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Cheap parameters, so that the tests run fast.
+    const TEST_PARAMS: PasswordParams = PasswordParams {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
     #[test]
     fn test_password_hash_and_verify() {
         let password = "super_secret_password";
-        let hash = hash_password(password).expect("Failed to hash password");
+        let hash = hash_password(password, TEST_PARAMS).expect("Failed to hash password");
 
         // Verifying that the hashed password matches the original one
         assert!(verify_password(&hash, password));
@@ -50,10 +210,64 @@ mod tests {
         assert!(!verify_password(&hash, "wrong_password"));
     }
 
+    #[test]
+    fn test_verify_password_or_dummy() {
+        let hash = hash_password("password", TEST_PARAMS).expect("Failed to hash password");
+
+        assert!(verify_password_or_dummy(
+            Some(&hash),
+            "password",
+            TEST_PARAMS
+        ));
+        assert!(!verify_password_or_dummy(
+            Some(&hash),
+            "wrong_password",
+            TEST_PARAMS
+        ));
+        assert!(!verify_password_or_dummy(None, "password", TEST_PARAMS));
+    }
+
+    #[test]
+    fn test_needs_rehash() {
+        let hash = hash_password("password", TEST_PARAMS).expect("Failed to hash password");
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(!needs_rehash(&hash, TEST_PARAMS));
+
+        let stronger = PasswordParams {
+            iterations: 2,
+            ..TEST_PARAMS
+        };
+        assert!(needs_rehash(&hash, stronger));
+    }
+
+    #[test]
+    fn test_generate_and_hash_token() {
+        let token = generate_token();
+        assert_ne!(token, generate_token());
+
+        let hash = hash_token(&token);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_token(&token));
+    }
+
+    #[test]
+    fn test_generate_and_normalize_recovery_code() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 19);
+        assert_ne!(code, generate_recovery_code());
+
+        assert_eq!(
+            normalize_recovery_code(&code.to_uppercase().replace('-', " ")),
+            code.replace('-', "")
+        );
+    }
+
     #[test]
     fn test_argon2_jvm_verify() {
         let password = "mySecretPassword";
         let hash = "$argon2i$v=19$m=65536,t=2,p=1$vNVL5PZ1hRwgLUlGmCQVTA$fg1d0/f8pdtMnzQTeh2YE6R0E8vfqMOQOs5k6Y22Qi0";
         assert!(verify_password(hash, password));
+        // argon2i hashes are still accepted, but replaced on the next login.
+        assert!(needs_rehash(hash, PasswordParams::default()));
     }
 }
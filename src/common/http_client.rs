@@ -0,0 +1,161 @@
+//! Outgoing HTTP requests to external services, such as an OpenID Connect provider.
+//!
+//! HTTPS servers are verified against the Mozilla root certificates. Responses are limited
+//! in size and every request in duration.
+
+use std::time::Duration;
+
+use axum::{
+    body::Bytes,
+    http::{header::ACCEPT, StatusCode},
+};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+/// Maximum size of a response body.
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// HttpError is returned when a request fails or its response cannot be used.
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Request failed: {0}")]
+    Request(reqwest::Error),
+
+    #[error("Failed to read response: {0}")]
+    Body(String),
+
+    #[error("Request timed out")]
+    Timeout,
+
+    #[error("Unexpected status {0}: {1}")]
+    Status(StatusCode, String),
+
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            HttpError::Timeout
+        } else {
+            HttpError::Request(err)
+        }
+    }
+}
+
+/// Client for JSON APIs over HTTP and HTTPS.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+}
+
+impl HttpClient {
+    /// Creates a client that gives up on requests taking longer than `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        let client = Client::builder()
+            .use_rustls_tls()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Self { client }
+    }
+
+    /// Sends a GET request and parses the JSON response.
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, HttpError> {
+        let url = parse_url(url)?;
+        let request = self.client.get(url).header(ACCEPT, "application/json");
+
+        let body = send(request).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Sends a form as a POST request and parses the JSON response.
+    /// `basic_auth` is sent as HTTP Basic credentials, if given.
+    pub async fn post_form<T: DeserializeOwned, F: Serialize>(
+        &self,
+        url: &str,
+        form: &F,
+        basic_auth: Option<(&str, &str)>,
+    ) -> Result<T, HttpError> {
+        let url = parse_url(url)?;
+        let mut request = self
+            .client
+            .post(url)
+            .header(ACCEPT, "application/json")
+            .form(form);
+        if let Some((username, password)) = basic_auth {
+            request = request.basic_auth(username, Some(password));
+        }
+
+        let body = send(request).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+/// Parses an absolute `http` or `https` URL.
+fn parse_url(url: &str) -> Result<Url, HttpError> {
+    let parsed = Url::parse(url).map_err(|_| HttpError::InvalidUrl(url.to_string()))?;
+    let scheme_ok = matches!(parsed.scheme(), "http" | "https");
+    if !scheme_ok || parsed.host().is_none() {
+        return Err(HttpError::InvalidUrl(url.to_string()));
+    }
+    Ok(parsed)
+}
+
+/// Sends the request and reads the response body.
+/// Responses with a status other than 2xx are returned as `HttpError::Status`.
+async fn send(request: RequestBuilder) -> Result<Bytes, HttpError> {
+    let mut response = request.send().await?;
+    let status = response.status();
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+            return Err(HttpError::Body("length limit exceeded".into()));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    if !status.is_success() {
+        let text: String = String::from_utf8_lossy(&body).chars().take(200).collect();
+        return Err(HttpError::Status(status, text));
+    }
+    Ok(Bytes::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert!(parse_url("https://idp.example.com/.well-known/openid-configuration").is_ok());
+        assert!(parse_url("http://127.0.0.1:8081/token").is_ok());
+        assert!(parse_url("ftp://idp.example.com/").is_err());
+        assert!(parse_url("/token").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_json_ipv6() {
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = axum::Router::new().route(
+            "/keys",
+            axum::routing::get(|| async { axum::Json(serde_json::json!({ "keys": [] })) }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = HttpClient::new(Duration::from_secs(5));
+        let body: serde_json::Value = client
+            .get_json(&format!("http://[::1]:{port}/keys"))
+            .await
+            .unwrap();
+        assert_eq!(body, serde_json::json!({ "keys": [] }));
+    }
+}
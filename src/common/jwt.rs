@@ -1,53 +1,89 @@
 use axum::{
-    extract::Request,
+    extract::{OriginalUri, Request, State},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::Validation;
 use serde::{Deserialize, Serialize};
 use std::sync::LazyLock;
-use std::{env, fmt::Display};
+use std::{env, fmt::Display, path::Path};
 use utoipa::ToSchema;
+use uuid::Uuid;
 
-use super::error::AppError;
+use super::{
+    app_state::AppState,
+    auth_cookie::{csrf_token_matches, get_cookie},
+    config::Config,
+    error::AppError,
+    jwt_keys::KeyRing,
+};
 
-/// JWT_SECRET_KEY is the environment variable that holds the secret key for JWT encoding and decoding.
-/// It is loaded from the environment variables using the dotenv crate.
-/// The secret key is used to sign the JWT tokens and should be kept secret.
-pub static KEYS: LazyLock<Keys> = LazyLock::new(|| {
+/// KEYS is the key ring used to sign and verify JWT tokens, loaded from the environment.
+/// If `JWT_KEYS_DIR` is set, RS256/EdDSA keys are loaded from the PEM files in that directory
+/// and `JWT_SIGNING_KEY_ID` selects the signing key (see `KeyRing::from_dir`).
+/// Otherwise tokens are signed with HS256 using `JWT_SECRET_KEY`, which must be kept secret.
+/// When both are set, `JWT_SECRET_KEY` only verifies HS256 tokens issued before the switch.
+pub static KEYS: LazyLock<KeyRing> = LazyLock::new(|| {
     dotenvy::dotenv().ok();
 
-    let secret = env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
-    Keys::new(secret.as_bytes())
+    let secret = env::var("JWT_SECRET_KEY").ok().filter(|s| !s.is_empty());
+    match env::var("JWT_KEYS_DIR") {
+        Ok(dir) => KeyRing::from_dir(
+            Path::new(&dir),
+            env::var("JWT_SIGNING_KEY_ID").ok().as_deref(),
+            secret.as_deref().map(str::as_bytes),
+        )
+        .unwrap_or_else(|err| panic!("Failed to load JWT keys from {dir}: {err}")),
+        Err(_) => KeyRing::from_secret(secret.expect("JWT_SECRET_KEY must be set").as_bytes()),
+    }
 });
 
-/// Keys is a struct that holds the encoding and decoding keys for JWT.
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
-}
-
-/// The Keys struct is used to create the encoding and decoding keys for JWT.
-impl Keys {
-    fn new(secret: &[u8]) -> Self {
-        Self {
-            encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
-        }
-    }
-}
+/// Header carrying an API key, accepted by `jwt_auth` instead of a bearer token.
+pub const API_KEY_HEADER: &str = "X-API-Key";
 
 /// Claims is a struct that represents the claims in the JWT token.
-/// It contains the subject (user ID), expiration time, and issued at time.
-/// The `sub` field is the user ID, `exp` is the expiration time, and `iat` is the issued at time.
+/// It contains the subject (user ID), issuer, audience, expiration time, not-before time,
+/// issued at time, token ID and roles.
+/// The `sub` field is the user ID, `iss` and `aud` identify the environment that issued
+/// the token and the one it is meant for, `exp` is the expiration time, `nbf` is the time
+/// before which the token must not be accepted, `iat` is the issued at time,
+/// `jti` uniquely identifies the token so that it can be revoked,
+/// and `roles` lists the names of the roles granted to the user when the token was issued.
+/// Tokens issued through the OAuth2 token endpoint also carry the `client_id` of the
+/// client and the space-delimited `scope` that limits the permissions of the token.
+/// Tokens issued by a login carry the ID of its session as `sid`, so that revoking the
+/// session rejects them.
+/// Tokens issued to an admin impersonating a user carry the user as `sub` and the admin
+/// as the `act` (actor) claim.
 /// The `Claims` struct is used to encode and decode the JWT tokens.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
+    pub iss: String,
+    pub aud: String,
     pub exp: usize,
+    pub nbf: usize,
     pub iat: usize,
+    #[serde(default)]
+    pub jti: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+/// Actor claim (RFC 8693): the user acting on behalf of the subject of the token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
 }
 
 /// The Claims struct implements the `Display` trait for easy printing.
@@ -58,38 +94,91 @@ impl Display for Claims {
     }
 }
 
-/// The Default trait is implemented for the Claims struct.
-/// It sets the default values for the claims.
-impl Default for Claims {
-    fn default() -> Self {
+impl Claims {
+    /// Creates the claims of a new access token for the user.
+    /// Issuer, audience and lifetime are taken from the configuration.
+    pub fn new(user_id: &str, roles: Vec<String>, config: &Config) -> Self {
         let now = Utc::now();
-        let expire: Duration = Duration::hours(24);
+        let expire: Duration = Duration::seconds(config.access_token_ttl_seconds);
         let exp: usize = (now + expire).timestamp() as usize;
         let iat: usize = now.timestamp() as usize;
         Claims {
-            sub: String::new(),
+            sub: user_id.to_string(),
+            iss: config.jwt_issuer.clone(),
+            aud: config.jwt_audience.clone(),
             exp,
+            nbf: iat,
             iat,
+            jti: Uuid::new_v4().to_string(),
+            roles,
+            client_id: None,
+            scope: None,
+            sid: None,
+            act: None,
         }
     }
+
+    /// Binds the claims to a login session.
+    pub fn with_session(mut self, session_id: String) -> Self {
+        self.sid = Some(session_id);
+        self
+    }
+
+    /// Marks the claims as issued to the actor impersonating the subject.
+    pub fn with_actor(mut self, actor_id: String) -> Self {
+        self.act = Some(Actor { sub: actor_id });
+        self
+    }
+
+    /// Returns the ID of the user performing the request: the impersonating admin for
+    /// impersonation tokens, otherwise the subject. Used to record who modified a record.
+    pub fn actor_id(&self) -> &str {
+        self.act.as_ref().map_or(&self.sub, |act| &act.sub)
+    }
+
+    /// Binds the claims to an OAuth2 client and limits them to the scope.
+    pub fn with_client(mut self, client_id: String, scope: String) -> Self {
+        self.client_id = Some(client_id);
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Returns the scopes of an OAuth2 token, or `None` if the token is not limited.
+    pub fn scopes(&self) -> Option<Vec<String>> {
+        self.scope
+            .as_ref()
+            .map(|scope| scope.split_whitespace().map(str::to_string).collect())
+    }
 }
 
 /// AuthBody is a struct that represents the authentication body.
+/// `expires_in` is the access token lifetime in seconds.
+/// `refresh_token` is an opaque token that can be exchanged at `/auth/refresh`.
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthBody {
     pub access_token: String,
     pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: Option<String>,
 }
 
 /// The AuthBody struct is used to create a new instance of the authentication body.
-/// It takes an access token as a parameter and sets the token type to "Bearer".
+/// It takes an access token and its lifetime as parameters and sets the token type to "Bearer".
 impl AuthBody {
-    pub fn new(access_token: String) -> Self {
+    pub fn new(access_token: String, expires_in: i64) -> Self {
         Self {
             access_token,
             token_type: "Bearer".to_string(),
+            expires_in,
+            refresh_token: None,
         }
     }
+
+    /// Attaches a refresh token to the authentication body.
+    pub fn with_refresh_token(mut self, refresh_token: String) -> Self {
+        self.refresh_token = Some(refresh_token);
+        self
+    }
 }
 
 /// AuthPayload is a struct that represents the authentication payload.
@@ -100,40 +189,143 @@ pub struct AuthPayload {
     pub client_secret: String,
 }
 
-/// make_jwt_token is a function that creates a JWT token.
-/// It takes a user ID as a parameter and returns a Result with the JWT token or an error.
-pub fn make_jwt_token(user_id: &str) -> Result<String, AppError> {
-    let claims = Claims {
-        sub: user_id.to_string(),
-        ..Default::default()
-    };
-    encode(&Header::default(), &claims, &KEYS.encoding).map_err(|_| AppError::TokenCreation)
+/// Signs the claims with the current signing key.
+pub fn encode_claims(claims: &Claims) -> Result<String, AppError> {
+    KEYS.encode(claims).map_err(|_| AppError::TokenCreation)
+}
+
+/// Builds the validation rules for access tokens from the configuration.
+/// Tokens must carry the configured issuer and audience, so tokens minted for
+/// another environment are rejected even if it shares the signing keys.
+/// `exp` and `nbf` are checked with the configured clock-skew leeway.
+pub fn make_validation(config: &Config) -> Validation {
+    let mut validation = Validation::default();
+    validation.leeway = config.jwt_leeway_seconds;
+    validation.validate_nbf = true;
+    validation.set_issuer(&[&config.jwt_issuer]);
+    validation.set_audience(&[&config.jwt_audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation
 }
 
 /// Middleware to validate JWT tokens.
-/// If the token is valid, the request proceeds; otherwise, a 401 Unauthorized is returned.
-pub async fn jwt_auth<B>(mut req: Request<B>, next: Next) -> Result<Response, Response>
-where
-    B: Send + Into<axum::body::Body>,
-{
+/// If the token is valid and has not been revoked, the request proceeds;
+/// otherwise, a 401 Unauthorized is returned.
+/// The permissions granted by the token's roles are inserted into the request
+/// extensions for `rbac::require_permission`.
+/// Machine-to-machine clients may send an API key in the `X-API-Key` header instead;
+/// handlers then see the same `Claims`, with the permissions limited to the key's scopes.
+/// Likewise, the permissions of an OAuth2 token are limited to its `scope`.
+/// Every request made with an impersonation token is logged and recorded in the
+/// impersonation audit trail together with its response status.
+/// If cookie authentication is enabled, requests without an `Authorization` header may
+/// send the access token in a cookie; those must carry the CSRF token in the
+/// `X-CSRF-Token` header unless the method is safe, otherwise a 403 Forbidden is returned.
+pub async fn jwt_auth(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let api_key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|key| key.trim().to_string());
+    if let Some(api_key) = api_key {
+        let (claims, permissions) = state
+            .auth_service
+            .authenticate_api_key(&api_key)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        req.extensions_mut().insert(claims);
+        req.extensions_mut().insert(permissions);
+        return Ok(next.run(req).await);
+    }
+
     // Try to extract and trim the token in one go.
-    let token = req
+    let bearer = req
         .headers()
         .get("Authorization")
         .and_then(|v| v.to_str().ok())
         .and_then(|header| header.strip_prefix("Bearer "))
         .map(|t| t.trim())
-        .filter(|t| !t.is_empty())
-        .ok_or_else(|| AppError::InvalidToken.into_response())?;
+        .filter(|t| !t.is_empty());
+    let token = match bearer {
+        Some(token) => token,
+        None if state.config.auth_cookie_enabled => {
+            let token = get_cookie(req.headers(), &state.config.auth_cookie_name)
+                .ok_or_else(|| AppError::InvalidToken.into_response())?;
+            if !req.method().is_safe()
+                && !csrf_token_matches(req.headers(), &state.config.csrf_cookie_name)
+            {
+                return Err(AppError::InvalidCsrfToken.into_response());
+            }
+            token
+        }
+        None => return Err(AppError::InvalidToken.into_response()),
+    };
 
     // Validate and decode the token.
-    let token_data =
-        decode::<Claims>(token, &KEYS.decoding, &Validation::default()).map_err(|err| {
-            tracing::error!("Error decoding token: {:?}", err);
-            AppError::InvalidToken.into_response()
-        })?;
+    let validation = make_validation(&state.config);
+    let token_data = KEYS.decode::<Claims>(token, &validation).map_err(|err| {
+        tracing::error!("Error decoding token: {:?}", err);
+        AppError::InvalidToken.into_response()
+    })?;
+
+    // Reject tokens that were revoked before their expiry (logout, revoke all sessions).
+    if state
+        .auth_service
+        .is_token_revoked(&token_data.claims)
+        .await
+        .map_err(IntoResponse::into_response)?
+    {
+        return Err(AppError::InvalidToken.into_response());
+    }
+
+    // Resolve the permissions granted by the roles in the token.
+    let mut permissions = state
+        .auth_service
+        .resolve_permissions(&token_data.claims.roles)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if let Some(scopes) = token_data.claims.scopes() {
+        permissions = permissions.restrict(&scopes);
+    }
+
+    // Remember impersonated requests, which are audited once the response is known.
+    let impersonation = token_data.claims.act.is_some().then(|| {
+        let uri = req
+            .extensions()
+            .get::<OriginalUri>()
+            .map_or(req.uri(), |original| &original.0);
+        (
+            token_data.claims.clone(),
+            req.method().to_string(),
+            uri.path().to_string(),
+        )
+    });
 
-    // Insert the decoded claims into the request extensions.
+    // Insert the decoded claims and permissions into the request extensions.
     req.extensions_mut().insert(token_data.claims);
-    Ok(next.run(req.map(Into::into)).await)
+    req.extensions_mut().insert(permissions);
+    let response = next.run(req).await;
+
+    if let Some((claims, method, path)) = impersonation {
+        let status = response.status().as_u16();
+        tracing::info!(
+            actor = claims.actor_id(),
+            user = %claims.sub,
+            %method,
+            %path,
+            status,
+            "Impersonated request"
+        );
+        state
+            .auth_service
+            .record_impersonated_request(&claims, &method, &path, status)
+            .await;
+    }
+
+    Ok(response)
 }
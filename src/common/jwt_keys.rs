@@ -0,0 +1,296 @@
+//! Key ring used to sign and verify JWT tokens.
+//!
+//! Asymmetric keys are loaded from a directory of PEM files. Every key is identified by a
+//! `kid` taken from its file name:
+//!
+//! - `<kid>.pub.pem`: public key (SPKI), required for every key. Published in the JWKS.
+//! - `<kid>.pem`: private key (PKCS#8), only required for the key that signs new tokens.
+//!
+//! RSA keys sign with RS256 and Ed25519 keys with EdDSA. To rotate without downtime, add the
+//! new key pair, switch the signing key id and keep the old public key until every token it
+//! signed has expired.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::ErrorKind,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use rsa::traits::PublicKeyParts;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+const PUBLIC_KEY_SUFFIX: &str = ".pub.pem";
+const PRIVATE_KEY_SUFFIX: &str = ".pem";
+
+/// KeyRingError is returned when the key ring cannot be loaded.
+#[derive(Error, Debug)]
+pub enum KeyRingError {
+    #[error("Failed to read key file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid key {kid}: {reason}")]
+    InvalidKey { kid: String, reason: String },
+
+    #[error("No public keys found")]
+    NoKeys,
+
+    #[error("Signing key {0} not found")]
+    SigningKeyNotFound(String),
+}
+
+/// The key used to sign new tokens.
+struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+/// A key accepted when verifying tokens.
+struct VerifyingKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// KeyRing holds the signing key, every key accepted for verification and the public JWKS.
+pub struct KeyRing {
+    signing: SigningKey,
+    verifying: HashMap<String, VerifyingKey>,
+    /// HS256 key for tokens without a `kid`.
+    legacy: Option<DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl KeyRing {
+    /// Creates a key ring that signs and verifies with a single HS256 secret.
+    /// The JWKS is empty since the secret must not be published.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            signing: SigningKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(secret),
+            },
+            verifying: HashMap::new(),
+            legacy: Some(DecodingKey::from_secret(secret)),
+            jwks: JwkSet { keys: Vec::new() },
+        }
+    }
+
+    /// Loads the key ring from a directory of PEM files.
+    /// `signing_kid` selects the signing key; if `None`, the greatest kid that has a
+    /// private key is used, so naming keys by date makes the newest key sign.
+    /// `legacy_secret` keeps HS256 tokens without a `kid` valid while migrating.
+    pub fn from_dir(
+        dir: &Path,
+        signing_kid: Option<&str>,
+        legacy_secret: Option<&[u8]>,
+    ) -> Result<Self, KeyRingError> {
+        let mut kids = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let file_name = entry?.file_name();
+            if let Some(kid) = file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(PUBLIC_KEY_SUFFIX))
+            {
+                kids.push(kid.to_string());
+            }
+        }
+        kids.sort();
+
+        let mut verifying = HashMap::new();
+        let mut jwks = JwkSet { keys: Vec::new() };
+        for kid in &kids {
+            let pem = fs::read(dir.join(format!("{kid}{PUBLIC_KEY_SUFFIX}")))?;
+            let (algorithm, key, jwk) = load_public_key(kid, &pem)?;
+            verifying.insert(kid.clone(), VerifyingKey { algorithm, key });
+            jwks.keys.push(jwk);
+        }
+
+        let signing_kid = match signing_kid {
+            Some(kid) => kid.to_string(),
+            None => kids
+                .iter()
+                .rev()
+                .find(|kid| dir.join(format!("{kid}{PRIVATE_KEY_SUFFIX}")).exists())
+                .cloned()
+                .ok_or(KeyRingError::NoKeys)?,
+        };
+        let algorithm = verifying
+            .get(&signing_kid)
+            .map(|key| key.algorithm)
+            .ok_or_else(|| KeyRingError::SigningKeyNotFound(signing_kid.clone()))?;
+        let pem = fs::read(dir.join(format!("{signing_kid}{PRIVATE_KEY_SUFFIX}")))?;
+        let key = match algorithm {
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+            _ => EncodingKey::from_rsa_pem(&pem),
+        }
+        .map_err(|err| KeyRingError::InvalidKey {
+            kid: signing_kid.clone(),
+            reason: err.to_string(),
+        })?;
+
+        Ok(Self {
+            signing: SigningKey {
+                kid: Some(signing_kid),
+                algorithm,
+                key,
+            },
+            verifying,
+            legacy: legacy_secret.map(DecodingKey::from_secret),
+            jwks,
+        })
+    }
+
+    /// Signs the claims with the signing key, setting `alg` and `kid` in the header.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(self.signing.algorithm);
+        header.kid = self.signing.kid.clone();
+        encode(&header, claims, &self.signing.key)
+    }
+
+    /// Verifies the token with the key named by its `kid` and checks the claims against
+    /// `validation`. The algorithm is pinned to the key's algorithm; tokens without a `kid`
+    /// are only accepted as HS256 tokens if a legacy secret is configured.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let (algorithm, key) = match header.kid {
+            Some(kid) => {
+                let key = self.verifying.get(&kid).ok_or(ErrorKind::InvalidToken)?;
+                (key.algorithm, &key.key)
+            }
+            None => (
+                Algorithm::HS256,
+                self.legacy.as_ref().ok_or(ErrorKind::InvalidToken)?,
+            ),
+        };
+
+        let mut validation = validation.clone();
+        validation.algorithms = vec![algorithm];
+        decode(token, key, &validation)
+    }
+
+    /// Returns the public keys as a JSON Web Key Set.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+/// Parses an RSA or Ed25519 public key and returns its algorithm, decoding key and JWK.
+fn load_public_key(kid: &str, pem: &[u8]) -> Result<(Algorithm, DecodingKey, Jwk), KeyRingError> {
+    let invalid = |reason: String| KeyRingError::InvalidKey {
+        kid: kid.to_string(),
+        reason,
+    };
+    let pem_str = std::str::from_utf8(pem).map_err(|err| invalid(err.to_string()))?;
+
+    let (algorithm, key_algorithm, parameters) =
+        if let Ok(key) = rsa::RsaPublicKey::from_public_key_pem(pem_str) {
+            let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+            });
+            (Algorithm::RS256, KeyAlgorithm::RS256, parameters)
+        } else if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(pem_str) {
+            let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+            });
+            (Algorithm::EdDSA, KeyAlgorithm::EdDSA, parameters)
+        } else {
+            return Err(invalid(
+                "unsupported key type, expected RSA or Ed25519".into(),
+            ));
+        };
+
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    };
+    let key = DecodingKey::from_jwk(&jwk).map_err(|err| invalid(err.to_string()))?;
+
+    Ok((algorithm, key, jwk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "user".to_string(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+        }
+    }
+
+    fn key_dir() -> &'static Path {
+        Path::new("tests/asset/jwt")
+    }
+
+    #[test]
+    fn test_rotation() {
+        // the RSA key signed before the rotation, the Ed25519 key signs after it
+        let old = KeyRing::from_dir(key_dir(), Some("2025-01"), None).unwrap();
+        let new = KeyRing::from_dir(key_dir(), None, None).unwrap();
+
+        let old_token = old.encode(&claims()).unwrap();
+        let new_token = new.encode(&claims()).unwrap();
+
+        assert_eq!(decode_header(&new_token).unwrap().alg, Algorithm::EdDSA);
+        assert!(new
+            .decode::<TestClaims>(&old_token, &Validation::default())
+            .is_ok());
+        assert!(new
+            .decode::<TestClaims>(&new_token, &Validation::default())
+            .is_ok());
+        assert_eq!(new.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_legacy_secret() {
+        let hs256 = KeyRing::from_secret(b"secret");
+        let token = hs256.encode(&claims()).unwrap();
+
+        let without_secret = KeyRing::from_dir(key_dir(), None, None).unwrap();
+        let with_secret = KeyRing::from_dir(key_dir(), None, Some(b"secret")).unwrap();
+
+        assert!(without_secret
+            .decode::<TestClaims>(&token, &Validation::default())
+            .is_err());
+        assert!(with_secret
+            .decode::<TestClaims>(&token, &Validation::default())
+            .is_ok());
+        assert!(hs256.jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_signing_key() {
+        let result = KeyRing::from_dir(key_dir(), Some("missing"), None);
+        assert!(matches!(result, Err(KeyRingError::SigningKeyNotFound(_))));
+    }
+}
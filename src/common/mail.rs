@@ -0,0 +1,143 @@
+//! Outgoing mail.
+//!
+//! Services send mail through the `MailSender` trait, so the transport is chosen by
+//! configuration (`MAIL_TRANSPORT`):
+//!
+//! - `smtp`: delivers through an SMTP relay using STARTTLS.
+//! - `file`: writes every message as an `.eml` file to `MAIL_OUTBOX_PATH` instead of
+//!   delivering it. Meant for local development and tests.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use thiserror::Error;
+
+use super::config::Config;
+
+/// MailError is returned when a message cannot be built or delivered.
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("Invalid address: {0}")]
+    InvalidAddress(#[from] lettre::address::AddressError),
+
+    #[error("Failed to build message: {0}")]
+    Build(#[from] lettre::error::Error),
+
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
+    #[error("Failed to write message: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unknown mail transport: {0}")]
+    UnknownTransport(String),
+}
+
+/// A plain text message.
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+/// Trait implemented by every mail transport.
+pub trait MailSender: Send + Sync {
+    /// Sends the message, returning once it has been handed over to the transport.
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Creates the mail sender selected by `MAIL_TRANSPORT`.
+pub fn create_mail_sender(config: &Config) -> Result<Arc<dyn MailSender>, MailError> {
+    match config.mail_transport.as_str() {
+        "smtp" => Ok(Arc::new(SmtpMailSender::new(config)?)),
+        "file" => Ok(Arc::new(FileMailSender::new(
+            config.mail_from.parse()?,
+            &config.mail_outbox_path,
+        ))),
+        other => Err(MailError::UnknownTransport(other.to_string())),
+    }
+}
+
+/// Builds the RFC 5322 message shared by the transports.
+fn build_message(from: &Mailbox, mail: Mail) -> Result<Message, MailError> {
+    Ok(Message::builder()
+        .from(from.clone())
+        .to(mail.to.parse()?)
+        .subject(mail.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(mail.body)?)
+}
+
+/// Delivers mail through an SMTP relay.
+pub struct SmtpMailSender {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailSender {
+    pub fn new(config: &Config) -> Result<Self, MailError> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?
+            .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            from: config.mail_from.parse()?,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl MailSender for SmtpMailSender {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let message = build_message(&self.from, mail)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Writes mail to a directory instead of delivering it.
+pub struct FileMailSender {
+    from: Mailbox,
+    dir: PathBuf,
+}
+
+impl FileMailSender {
+    pub fn new(from: Mailbox, dir: impl AsRef<Path>) -> Self {
+        Self {
+            from,
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl MailSender for FileMailSender {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let message = build_message(&self.from, mail)?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            chrono::Utc::now().timestamp_millis(),
+            uuid::Uuid::new_v4()
+        ));
+        tokio::fs::write(&path, message.formatted()).await?;
+
+        tracing::info!("Mail written to {}", path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+//! Password policy applied when a password is set.
+//!
+//! Passwords must have a minimum length, contain characters of a minimum number of
+//! classes (lowercase letters, uppercase letters, digits and symbols), must not contain
+//! the username and must not appear in the list of breached or common passwords.
+//! The list is a text file with one password per line, loaded once at startup;
+//! it is matched case-insensitively.
+
+use std::{borrow::Cow, collections::HashSet, fs, io};
+
+use validator::ValidationError;
+
+use super::config::Config;
+
+/// Rules a new password must satisfy.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    min_character_classes: usize,
+    reject_username: bool,
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    /// Builds the policy from the configuration and loads the breached password list
+    /// from `PASSWORD_BREACHED_LIST_PATH`, if set.
+    pub fn from_config(config: &Config) -> Result<Self, io::Error> {
+        let breached = match &config.password_breached_list_path {
+            Some(path) => parse_list(&fs::read_to_string(path)?),
+            None => HashSet::new(),
+        };
+
+        Ok(Self {
+            min_length: config.password_min_length,
+            min_character_classes: config.password_min_character_classes,
+            reject_username: config.password_reject_username,
+            breached,
+        })
+    }
+
+    /// Checks the password of the user against the policy.
+    /// Returns every rule the password violates; an empty list means it is accepted.
+    pub fn check(&self, password: &str, username: &str) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            errors.push(violation(
+                "password_too_short",
+                format!("Password must be at least {} characters", self.min_length),
+            ));
+        }
+
+        if character_classes(password) < self.min_character_classes {
+            errors.push(violation(
+                "password_too_simple",
+                format!(
+                    "Password must contain at least {} of: lowercase letters, uppercase letters, digits, symbols",
+                    self.min_character_classes
+                ),
+            ));
+        }
+
+        let lowercase = password.to_lowercase();
+        let username = username.trim().to_lowercase();
+        if self.reject_username && !username.is_empty() && lowercase.contains(&username) {
+            errors.push(violation(
+                "password_contains_username",
+                "Password must not contain the username".to_string(),
+            ));
+        }
+
+        if self.breached.contains(&lowercase) {
+            errors.push(violation(
+                "password_breached",
+                "Password is too common or has appeared in a data breach".to_string(),
+            ));
+        }
+
+        errors
+    }
+}
+
+/// Parses a password list, skipping blank lines and `#` comments.
+fn parse_list(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Counts the character classes used by the password.
+fn character_classes(password: &str) -> usize {
+    let classes: [fn(char) -> bool; 4] = [
+        char::is_lowercase,
+        char::is_uppercase,
+        char::is_numeric,
+        |c| !c.is_alphanumeric(),
+    ];
+    classes
+        .iter()
+        .filter(|class| password.chars().any(class))
+        .count()
+}
+
+fn violation(code: &'static str, message: String) -> ValidationError {
+    ValidationError::new(code).with_message(Cow::Owned(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            min_character_classes: 3,
+            reject_username: true,
+            breached: parse_list("# common passwords\nPassword1!\n\nqwerty123\n"),
+        }
+    }
+
+    fn codes(errors: Vec<ValidationError>) -> Vec<Cow<'static, str>> {
+        errors.into_iter().map(|e| e.code).collect()
+    }
+
+    #[test]
+    fn test_accepts_valid_password() {
+        assert!(policy().check("Correct-Horse-42", "alice").is_empty());
+    }
+
+    #[test]
+    fn test_rejects_short_and_simple_password() {
+        assert_eq!(
+            codes(policy().check("abc", "alice")),
+            ["password_too_short", "password_too_simple"]
+        );
+        assert_eq!(character_classes("abc-DEF 123"), 4);
+    }
+
+    #[test]
+    fn test_rejects_username() {
+        assert_eq!(
+            codes(policy().check("xx-Alice-2024", "alice")),
+            ["password_contains_username"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_breached_password_case_insensitively() {
+        assert_eq!(
+            codes(policy().check("PASSWORD1!", "alice")),
+            ["password_breached"]
+        );
+    }
+}
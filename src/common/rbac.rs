@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::{error::AppError, jwt::Claims};
+
+/// Permission names checked by the routes.
+/// A permission is granted to a user through one of the user's roles (see `role_permissions`).
+pub const USER_READ: &str = "user:read";
+pub const USER_CREATE: &str = "user:create";
+pub const USER_UPDATE: &str = "user:update";
+pub const USER_DELETE: &str = "user:delete";
+pub const USER_IMPERSONATE: &str = "user:impersonate";
+
+pub const DEVICE_READ: &str = "device:read";
+pub const DEVICE_CREATE: &str = "device:create";
+pub const DEVICE_UPDATE: &str = "device:update";
+pub const DEVICE_DELETE: &str = "device:delete";
+
+pub const FILE_READ: &str = "file:read";
+pub const FILE_DELETE: &str = "file:delete";
+
+pub const SESSION_REVOKE: &str = "session:revoke";
+
+pub const CLIENT_MANAGE: &str = "client:manage";
+
+pub const AUTH_EVENT_READ: &str = "auth_event:read";
+
+/// Role assigned to every user created through registration.
+pub const DEFAULT_ROLE: &str = "user";
+
+/// Role that may access resources owned by other users.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Permissions is the set of permissions granted to the authenticated user.
+/// It is resolved from the `roles` claim by `jwt_auth` and inserted into the request extensions.
+/// For API keys, it is further restricted to the scopes of the key.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions(HashSet<String>);
+
+impl Permissions {
+    pub fn new(permissions: HashSet<String>) -> Self {
+        Self(permissions)
+    }
+
+    /// Returns `true` if the permission has been granted.
+    pub fn contains(&self, permission: &str) -> bool {
+        self.0.contains(permission)
+    }
+
+    /// Returns the permissions that are also listed in `scopes`, e.g. the scopes of an API key.
+    pub fn restrict(&self, scopes: &[String]) -> Self {
+        Self(
+            scopes
+                .iter()
+                .filter(|scope| self.contains(scope))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Returns `true` if the token was issued to an admin.
+pub fn is_admin(claims: &Claims) -> bool {
+    claims.roles.iter().any(|role| role == ADMIN_ROLE)
+}
+
+/// Ownership policy shared by the domain services.
+/// Users may only access resources they own; admins may access every resource.
+/// Returns `AppError::Forbidden` otherwise.
+pub fn ensure_owner_or_admin(claims: &Claims, owner_id: &str) -> Result<(), AppError> {
+    if claims.sub == owner_id || is_admin(claims) {
+        return Ok(());
+    }
+
+    tracing::warn!("User {} is not the owner of the resource", claims.sub);
+    Err(AppError::Forbidden)
+}
+
+// Type alias for the boxed future returned by the permission middleware
+type PermissionFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response, Response>> + Send>>;
+
+/// Middleware that rejects the request with 403 Forbidden unless the user holds the permission.
+/// Must run after `jwt_auth`, which resolves the user's permissions.
+///
+/// ```ignore
+/// .route("/{id}", delete(delete_device).route_layer(middleware::from_fn(require_permission(DEVICE_DELETE))))
+/// ```
+pub fn require_permission(
+    permission: &'static str,
+) -> impl Fn(Request<Body>, Next) -> PermissionFuture + Clone + Send + Sync + 'static {
+    move |req, next| Box::pin(check_permission(req, next, permission))
+}
+
+async fn check_permission(
+    req: Request<Body>,
+    next: Next,
+    permission: &'static str,
+) -> Result<Response, Response> {
+    let granted = req
+        .extensions()
+        .get::<Permissions>()
+        .is_some_and(|permissions| permissions.contains(permission));
+
+    if !granted {
+        tracing::warn!("Permission denied: {permission} is required");
+        return Err(AppError::Forbidden.into_response());
+    }
+
+    Ok(next.run(req).await)
+}
@@ -16,9 +16,16 @@ pub mod dto {
 mod infra {
     mod impl_repository;
     pub mod impl_service;
+    mod login_throttle;
+    mod oauth;
+    mod oidc;
+    mod permission_cache;
+    mod revocation_cache;
 }
 
 // Re-export commonly used items for convenience
-pub use api::routes::{user_auth_routes, UserAuthApiDoc};
+pub use api::routes::{
+    oauth_routes, user_auth_protected_routes, user_auth_routes, well_known_routes, UserAuthApiDoc,
+};
 pub use domain::service::AuthServiceTrait;
 pub use infra::impl_service::AuthService;
@@ -1,45 +1,829 @@
 use crate::{
     common::{
         app_state::AppState,
-        dto::RestApiResponse,
-        error::AppError,
-        jwt::{AuthBody, AuthPayload},
+        auth_cookie,
+        client_ip::ClientIp,
+        dto::{PageDto, RestApiResponse},
+        error::{AppError, OAuthErrorCode},
+        jwt::{AuthBody, AuthPayload, Claims, KEYS},
+        rbac::Permissions,
     },
-    domains::auth::dto::auth_dto::AuthUserDto,
+    domains::auth::dto::auth_dto::{
+        ApiKeyDto, AuthEventDto, AuthEventQueryDto, AuthUserDto, ChangePasswordDto,
+        ClientCredentials, CreateApiKeyDto, CreateOAuthClientDto, CreatedApiKeyDto,
+        CreatedOAuthClientDto, ForgotPasswordDto, IntrospectionDto, LoginClient, LoginResponseDto,
+        LogoutDto, MfaLoginDto, OAuthClientDto, OAuthTokenDto, OAuthTokenRefDto,
+        OAuthTokenRequestDto, OidcCallbackDto, RecoveryCodesDto, RefreshTokenDto,
+        RegisteredUserDto, ResetPasswordDto, SessionDto, TotpCodeDto, TotpEnrollmentDto,
+        VerifyEmailDto,
+    },
+};
+use std::net::IpAddr;
+
+use axum::extract::{
+    rejection::{FormRejection, QueryRejection},
+    Path, Query, State,
+};
+use axum::http::{
+    header::{AUTHORIZATION, CACHE_CONTROL, USER_AGENT},
+    HeaderMap,
 };
-use axum::extract::State;
-use axum::{response::IntoResponse, Json};
+use axum::{
+    response::{IntoResponse, Redirect},
+    Extension, Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use validator::Validate;
+
+/// Header identifying the device of the user a login is sent from.
+const DEVICE_ID_HEADER: &str = "X-Device-Id";
+
+/// Maximum number of characters of a user agent stored with a session.
+const MAX_USER_AGENT_CHARS: usize = 256;
 
-/// this function creates a router for creating user authentication registration
-/// it will create a new user in the database
+/// this function creates a router for self-service registration
+/// it will create a new user together with its credentials in the database
 #[utoipa::path(
     post,
     path = "/auth/register",
     request_body = AuthUserDto,
-    responses((status = 200, description = "Create user authentication", body = AuthUserDto)),
+    responses(
+        (status = 200, description = "Register user", body = RegisteredUserDto),
+        (status = 400, description = "Invalid input or username already exists")
+    ),
     tag = "UserAuth"
 )]
 pub async fn create_user_auth(
     State(state): State<AppState>,
     Json(payload): Json<AuthUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    state.auth_service.create_user_auth(payload).await?;
-    Ok(RestApiResponse::success(()))
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::InvalidFields(err)
+    })?;
+
+    let user = state.auth_service.create_user_auth(payload).await?;
+    Ok(RestApiResponse::success(user))
 }
 
 /// this function creates a router for login user
-/// it will return a JWT token if the user is authenticated
+/// it will return a JWT token if the user is authenticated,
+/// or an MFA challenge if the user has MFA enabled;
+/// with cookie authentication enabled, the tokens are set as cookies as well
 #[utoipa::path(
     post,
     path = "/auth/login",
     request_body = AuthPayload,
-    responses((status = 200, description = "Login user", body = AuthBody)),
+    responses(
+        (status = 200, description = "Login user", body = LoginResponseDto),
+        (status = 401, description = "Unknown user or wrong password"),
+        (status = 429, description = "Too many failed login attempts; see the `Retry-After` header")
+    ),
     tag = "UserAuth"
 )]
 pub async fn login_user(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Json(payload): Json<AuthPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let auth_body = state.auth_service.login_user(payload).await?;
+    let client = login_client(client_ip, &headers);
+    let login_response = state.auth_service.login_user(payload, client).await?;
+    let cookies = match &login_response {
+        LoginResponseDto::Authenticated(auth_body) => {
+            auth_cookie::login_cookies(&state.config, auth_body)
+        }
+        LoginResponseDto::MfaRequired(_) => HeaderMap::new(),
+    };
+    Ok((cookies, RestApiResponse::success(login_response)))
+}
+
+/// this function creates a router for the second login step
+/// it will return a JWT token if the MFA challenge and the TOTP or recovery code are valid
+#[utoipa::path(
+    post,
+    path = "/auth/login/mfa",
+    request_body = MfaLoginDto,
+    responses(
+        (status = 200, description = "Complete login with a second factor", body = AuthBody),
+        (status = 401, description = "Invalid, used or expired MFA challenge, or wrong code"),
+        (status = 429, description = "Too many failed login attempts; see the `Retry-After` header")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn login_mfa(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Json(payload): Json<MfaLoginDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = login_client(client_ip, &headers);
+    let auth_body = state.auth_service.login_mfa(payload, client).await?;
+    let cookies = auth_cookie::login_cookies(&state.config, &auth_body);
+    Ok((cookies, RestApiResponse::success(auth_body)))
+}
+
+/// this function creates a router for starting a login with the OpenID Connect provider
+/// it redirects the browser to the authorization endpoint of the provider
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/login",
+    responses(
+        (status = 303, description = "Redirect to the identity provider"),
+        (status = 404, description = "OpenID Connect login is not configured"),
+        (status = 502, description = "The identity provider cannot be reached")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn oidc_login(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let url = state.auth_service.start_oidc_login().await?;
+    Ok(Redirect::to(&url))
+}
+
+/// this function creates a router for the callback of the OpenID Connect provider
+/// it will return a JWT token if the provider authenticated a user linked to a local user
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/callback",
+    params(OidcCallbackDto),
+    responses(
+        (status = 200, description = "Login with the identity provider", body = LoginResponseDto),
+        (status = 401, description = "Login rejected by the provider, or invalid, used or expired state"),
+        (status = 403, description = "No user is linked to the external identity, or the email address is not verified"),
+        (status = 404, description = "OpenID Connect login is not configured"),
+        (status = 502, description = "The identity provider failed or sent an invalid ID token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Query(payload): Query<OidcCallbackDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = login_client(client_ip, &headers);
+    let login_response = state
+        .auth_service
+        .complete_oidc_login(payload, client)
+        .await?;
+    let cookies = match &login_response {
+        LoginResponseDto::Authenticated(auth_body) => {
+            auth_cookie::login_cookies(&state.config, auth_body)
+        }
+        LoginResponseDto::MfaRequired(_) => HeaderMap::new(),
+    };
+    Ok((cookies, RestApiResponse::success(login_response)))
+}
+
+/// this function creates a router for starting a TOTP enrollment
+/// it returns a new secret and its `otpauth://` URI for the authenticator app
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/totp/enroll",
+    responses(
+        (status = 200, description = "Start TOTP enrollment", body = TotpEnrollmentDto),
+        (status = 400, description = "MFA is already enabled")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let enrollment = state.auth_service.enroll_totp(claims).await?;
+    Ok(RestApiResponse::success(enrollment))
+}
+
+/// this function creates a router for confirming a TOTP enrollment
+/// it enables MFA and returns the recovery codes, which are only shown once
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/totp/confirm",
+    request_body = TotpCodeDto,
+    responses(
+        (status = 200, description = "Confirm TOTP enrollment", body = RecoveryCodesDto),
+        (status = 400, description = "No pending enrollment or invalid code")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<TotpCodeDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let recovery_codes = state.auth_service.confirm_totp(claims, payload).await?;
+    Ok(RestApiResponse::success_with_message(
+        "MFA enabled",
+        recovery_codes,
+    ))
+}
+
+/// this function creates a router for refreshing tokens
+/// it rotates the refresh token and returns a new token pair;
+/// with cookie authentication enabled, the refresh token may be sent in its cookie instead,
+/// in which case the new tokens are only set as cookies
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    request_body = RefreshTokenDto,
+    responses(
+        (status = 200, description = "Refresh access token", body = AuthBody),
+        (status = 403, description = "Refresh token cookie sent without the CSRF token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Json(mut payload): Json<RefreshTokenDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let refresh_cookie = auth_cookie::get_cookie(&headers, &state.config.refresh_cookie_name)
+        .filter(|_| state.config.auth_cookie_enabled && payload.refresh_token.is_empty());
+    if let Some(refresh_cookie) = refresh_cookie {
+        // The browser sends the cookie on its own, so the request must prove that it was
+        // made by the frontend.
+        if !auth_cookie::csrf_token_matches(&headers, &state.config.csrf_cookie_name) {
+            return Err(AppError::InvalidCsrfToken);
+        }
+        payload.refresh_token = refresh_cookie.to_string();
+    }
+
+    let client = login_client(client_ip, &headers);
+    let auth_body = state.auth_service.refresh_token(payload, client).await?;
+    let cookies = auth_cookie::login_cookies(&state.config, &auth_body);
+    if refresh_cookie.is_some() {
+        let response = RestApiResponse::success_with_message("Tokens refreshed", ());
+        return Ok((cookies, response).into_response());
+    }
+    Ok((cookies, RestApiResponse::success(auth_body)).into_response())
+}
+
+/// this function creates a router for changing the password
+/// it requires the current password of the authenticated user
+#[utoipa::path(
+    post,
+    path = "/auth/password/change",
+    request_body = ChangePasswordDto,
+    responses(
+        (status = 200, description = "Change password"),
+        (status = 400, description = "Invalid new password"),
+        (status = 401, description = "Wrong current password")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Json(payload): Json<ChangePasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::InvalidFields(err)
+    })?;
+
+    let client = login_client(client_ip, &headers);
+    state
+        .auth_service
+        .change_password(claims, payload, client)
+        .await?;
+    Ok(RestApiResponse::success_with_message(
+        "Password changed",
+        (),
+    ))
+}
+
+/// this function creates a router for requesting a password reset
+/// it mails a reset token to the user and responds the same way whether the user exists or not
+#[utoipa::path(
+    post,
+    path = "/auth/password/forgot",
+    request_body = ForgotPasswordDto,
+    responses((status = 200, description = "Request password reset")),
+    tag = "UserAuth"
+)]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ForgotPasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.request_password_reset(payload).await?;
+    Ok(RestApiResponse::success_with_message(
+        "If the user exists, a password reset token has been sent",
+        (),
+    ))
+}
+
+/// this function creates a router for resetting the password
+/// it consumes the reset token and revokes all sessions of the user
+#[utoipa::path(
+    post,
+    path = "/auth/password/reset",
+    request_body = ResetPasswordDto,
+    responses(
+        (status = 200, description = "Reset password"),
+        (status = 400, description = "Invalid new password"),
+        (status = 401, description = "Invalid, used or expired reset token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Json(payload): Json<ResetPasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::InvalidFields(err)
+    })?;
+
+    let client = login_client(client_ip, &headers);
+    state.auth_service.reset_password(payload, client).await?;
+    Ok(RestApiResponse::success_with_message("Password reset", ()))
+}
+
+/// this function creates a router for verifying an email address
+/// it consumes the verification token that was mailed to the address
+#[utoipa::path(
+    post,
+    path = "/auth/verify-email",
+    request_body = VerifyEmailDto,
+    responses(
+        (status = 200, description = "Verify email address"),
+        (status = 401, description = "Invalid, used or expired verification token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyEmailDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.verify_email(payload).await?;
+    Ok(RestApiResponse::success_with_message("Email verified", ()))
+}
+
+/// this function creates a router for creating an API key
+/// the key is returned once and can be sent in the `X-API-Key` header instead of a bearer token
+#[utoipa::path(
+    post,
+    path = "/auth/api-keys",
+    request_body = CreateApiKeyDto,
+    responses(
+        (status = 200, description = "Create API key", body = CreatedApiKeyDto),
+        (status = 400, description = "Invalid input or scope not granted to the user"),
+        (status = 403, description = "Impersonation tokens cannot create API keys")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(permissions): Extension<Permissions>,
+    Json(payload): Json<CreateApiKeyDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::ValidationError(format!("Invalid input: {}", err))
+    })?;
+
+    let api_key = state
+        .auth_service
+        .create_api_key(claims, permissions, payload)
+        .await?;
+    Ok(RestApiResponse::success(api_key))
+}
+
+/// this function creates a router for listing the API keys of the authenticated user
+#[utoipa::path(
+    get,
+    path = "/auth/api-keys",
+    responses((status = 200, description = "List API keys", body = [ApiKeyDto])),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let api_keys = state.auth_service.list_api_keys(claims).await?;
+    Ok(RestApiResponse::success(api_keys))
+}
+
+/// this function creates a router for deleting an API key
+#[utoipa::path(
+    delete,
+    path = "/auth/api-keys/{id}",
+    responses(
+        (status = 200, description = "Delete API key"),
+        (status = 403, description = "API key of another user"),
+        (status = 404, description = "API key not found")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn delete_api_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.delete_api_key(claims, id).await?;
+    Ok(RestApiResponse::success_with_message("API key deleted", ()))
+}
+
+/// this function creates a router for registering an OAuth2 client
+/// the client secret is only returned once
+#[utoipa::path(
+    post,
+    path = "/auth/oauth/clients",
+    request_body = CreateOAuthClientDto,
+    responses(
+        (status = 200, description = "Register OAuth2 client", body = CreatedOAuthClientDto),
+        (status = 400, description = "Invalid input, unsupported grant type or scope not granted to the user"),
+        (status = 403, description = "Missing `client:manage` permission or impersonation token")
+    ),
+    security(("bearer_auth" = ["client:manage"])),
+    tag = "UserAuth"
+)]
+pub async fn create_oauth_client(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(permissions): Extension<Permissions>,
+    Json(payload): Json<CreateOAuthClientDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::ValidationError(format!("Invalid input: {}", err))
+    })?;
+
+    let client = state
+        .auth_service
+        .create_oauth_client(claims, permissions, payload)
+        .await?;
+    Ok(RestApiResponse::success_with_message(
+        "OAuth client registered",
+        client,
+    ))
+}
+
+/// this function creates a router for listing the registered OAuth2 clients
+#[utoipa::path(
+    get,
+    path = "/auth/oauth/clients",
+    responses(
+        (status = 200, description = "List OAuth2 clients", body = [OAuthClientDto]),
+        (status = 403, description = "Missing `client:manage` permission")
+    ),
+    security(("bearer_auth" = ["client:manage"])),
+    tag = "UserAuth"
+)]
+pub async fn list_oauth_clients(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let clients = state.auth_service.list_oauth_clients().await?;
+    Ok(RestApiResponse::success(clients))
+}
+
+/// this function creates a router for deleting an OAuth2 client
+/// the refresh tokens of the client are deleted with it
+#[utoipa::path(
+    delete,
+    path = "/auth/oauth/clients/{client_id}",
+    responses(
+        (status = 200, description = "Delete OAuth2 client"),
+        (status = 403, description = "Missing `client:manage` permission"),
+        (status = 404, description = "OAuth2 client not found")
+    ),
+    security(("bearer_auth" = ["client:manage"])),
+    tag = "UserAuth"
+)]
+pub async fn delete_oauth_client(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.delete_oauth_client(client_id).await?;
+    Ok(RestApiResponse::success_with_message(
+        "OAuth client deleted",
+        (),
+    ))
+}
+
+/// this function creates a router for the OAuth2 token endpoint (RFC 6749)
+/// it supports the `password`, `client_credentials` and `refresh_token` grants;
+/// responses and errors use the OAuth2 format, not the API response envelope
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    request_body(content = OAuthTokenRequestDto, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Issue tokens", body = OAuthTokenDto),
+        (status = 400, description = "OAuth2 error, e.g. `invalid_grant` or `invalid_scope`"),
+        (status = 401, description = "Client authentication failed (`invalid_client`)"),
+        (status = 429, description = "Too many failed login attempts; see the `Retry-After` header")
+    ),
+    security((), ("client_basic" = [])),
+    tag = "UserAuth"
+)]
+pub async fn oauth_token(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    payload: Result<Form<OAuthTokenRequestDto>, FormRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Form(mut payload) = payload.map_err(invalid_form)?;
+    let client = client_credentials(
+        &headers,
+        payload.client_id.take(),
+        payload.client_secret.take(),
+    )?;
+
+    let token = state
+        .auth_service
+        .oauth_token(client, payload, login_client(client_ip, &headers))
+        .await?;
+    Ok(([(CACHE_CONTROL, "no-store")], Json(token)))
+}
+
+/// this function creates a router for the OAuth2 token introspection endpoint (RFC 7662)
+/// it describes access tokens to any registered client, and refresh tokens to the
+/// client they were issued to
+#[utoipa::path(
+    post,
+    path = "/oauth/introspect",
+    request_body(content = OAuthTokenRefDto, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Describe token", body = IntrospectionDto),
+        (status = 401, description = "Client authentication failed (`invalid_client`)")
+    ),
+    security((), ("client_basic" = [])),
+    tag = "UserAuth"
+)]
+pub async fn introspect_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Form<OAuthTokenRefDto>, FormRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Form(mut payload) = payload.map_err(invalid_form)?;
+    let client = client_credentials(
+        &headers,
+        payload.client_id.take(),
+        payload.client_secret.take(),
+    )?;
+
+    let introspection = state.auth_service.introspect_token(client, payload).await?;
+    Ok(([(CACHE_CONTROL, "no-store")], Json(introspection)))
+}
+
+/// this function creates a router for the OAuth2 token revocation endpoint (RFC 7009)
+/// it answers 200 for unknown tokens as well, so it does not reveal which tokens exist
+#[utoipa::path(
+    post,
+    path = "/oauth/revoke",
+    request_body(content = OAuthTokenRefDto, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Revoke token"),
+        (status = 401, description = "Client authentication failed (`invalid_client`)")
+    ),
+    security((), ("client_basic" = [])),
+    tag = "UserAuth"
+)]
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Form<OAuthTokenRefDto>, FormRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Form(mut payload) = payload.map_err(invalid_form)?;
+    let client = client_credentials(
+        &headers,
+        payload.client_id.take(),
+        payload.client_secret.take(),
+    )?;
+
+    state
+        .auth_service
+        .revoke_oauth_token(client, payload)
+        .await?;
+    Ok(())
+}
+
+/// Reports a form body that cannot be parsed as an OAuth2 `invalid_request`.
+fn invalid_form(err: FormRejection) -> AppError {
+    tracing::error!("Invalid OAuth request: {err}");
+    AppError::OAuth(OAuthErrorCode::InvalidRequest, err.body_text())
+}
+
+/// Takes the client credentials from the HTTP Basic `Authorization` header
+/// (RFC 6749, section 2.3.1) or, without the header, from the form body.
+fn client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Result<ClientCredentials, AppError> {
+    let invalid_client = || {
+        AppError::OAuth(
+            OAuthErrorCode::InvalidClient,
+            "Client authentication failed".into(),
+        )
+    };
+
+    let basic = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|header| header.strip_prefix("Basic "));
+    if let Some(basic) = basic {
+        let decoded = STANDARD
+            .decode(basic.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(invalid_client)?;
+        let (client_id, client_secret) = decoded.split_once(':').ok_or_else(invalid_client)?;
+        return Ok(ClientCredentials {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+        });
+    }
+
+    match (client_id, client_secret) {
+        (Some(client_id), Some(client_secret)) => Ok(ClientCredentials {
+            client_id,
+            client_secret,
+        }),
+        _ => Err(invalid_client()),
+    }
+}
+
+/// Describes the client of a login or other authentication request from its IP,
+/// `User-Agent` and `X-Device-Id`.
+fn login_client(ip: Option<IpAddr>, headers: &HeaderMap) -> LoginClient {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+
+    LoginClient {
+        ip,
+        user_agent: header(USER_AGENT.as_str())
+            .map(|ua| ua.chars().take(MAX_USER_AGENT_CHARS).collect()),
+        device_id: header(DEVICE_ID_HEADER).map(str::to_string),
+    }
+}
+
+/// this function creates a router for logging out
+/// it revokes the current access token and its session and, if given, the refresh token family,
+/// and removes the authentication cookies
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    request_body = LogoutDto,
+    responses((status = 200, description = "Logout user")),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<LogoutDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.logout(claims, payload).await?;
+    Ok((
+        auth_cookie::clear_cookies(&state.config),
+        RestApiResponse::success_with_message("Logged out", ()),
+    ))
+}
+
+/// this function creates a router for listing the active sessions of the authenticated user
+#[utoipa::path(
+    get,
+    path = "/auth/sessions",
+    responses((status = 200, description = "List active sessions", body = [SessionDto])),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions = state.auth_service.list_sessions(claims).await?;
+    Ok(RestApiResponse::success(sessions))
+}
+
+/// this function creates a router for revoking a session
+/// its refresh tokens and access tokens are rejected from the next request on
+#[utoipa::path(
+    delete,
+    path = "/auth/sessions/{id}",
+    responses(
+        (status = 200, description = "Revoke session"),
+        (status = 403, description = "Session of another user"),
+        (status = 404, description = "Session not found or already revoked")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "UserAuth"
+)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.revoke_session(claims, id).await?;
+    Ok(RestApiResponse::success_with_message("Session revoked", ()))
+}
+
+/// this function creates a router for revoking all sessions of a user
+/// every access and refresh token issued to the user so far becomes invalid
+#[utoipa::path(
+    delete,
+    path = "/auth/users/{user_id}/sessions",
+    responses(
+        (status = 200, description = "Revoke all sessions of a user"),
+        (status = 403, description = "Missing `session:revoke` permission")
+    ),
+    security(("bearer_auth" = ["session:revoke"])),
+    tag = "UserAuth"
+)]
+pub async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.revoke_all_sessions(user_id).await?;
+    Ok(RestApiResponse::success_with_message(
+        "Sessions revoked",
+        (),
+    ))
+}
+
+/// this function creates a router for impersonating a user
+/// it returns an access token for acting as the user, which carries the admin as `act` claim;
+/// every request made with it is recorded in the impersonation audit trail
+#[utoipa::path(
+    post,
+    path = "/auth/impersonate/{user_id}",
+    responses(
+        (status = 200, description = "Impersonate user", body = AuthBody),
+        (status = 400, description = "Cannot impersonate yourself"),
+        (status = 403, description = "Missing `user:impersonate` permission, or the user is an admin"),
+        (status = 404, description = "User not found")
+    ),
+    security(("bearer_auth" = ["user:impersonate"])),
+    tag = "UserAuth"
+)]
+pub async fn impersonate_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let auth_body = state.auth_service.impersonate(claims, user_id).await?;
     Ok(RestApiResponse::success(auth_body))
 }
+
+/// this function creates a router for querying the auth event log
+/// it returns logins, failed logins, lockouts, token refreshes and password changes,
+/// most recent first, filtered by user, event type and time range
+#[utoipa::path(
+    get,
+    path = "/auth/events",
+    params(AuthEventQueryDto),
+    responses(
+        (status = 200, description = "Query auth events", body = PageDto<AuthEventDto>),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 403, description = "Missing `auth_event:read` permission")
+    ),
+    security(("bearer_auth" = ["auth_event:read"])),
+    tag = "UserAuth"
+)]
+pub async fn list_auth_events(
+    State(state): State<AppState>,
+    query: Result<Query<AuthEventQueryDto>, QueryRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(query) = query.map_err(|err| {
+        tracing::error!("Invalid query: {err}");
+        AppError::ValidationError(err.body_text())
+    })?;
+    query.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::InvalidFields(err)
+    })?;
+
+    let events = state.auth_service.list_auth_events(query).await?;
+    Ok(RestApiResponse::success(events))
+}
+
+/// this function creates a router for publishing the public signing keys
+/// it returns a standard JSON Web Key Set, so it is not wrapped in the API response envelope
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses((status = 200, description = "JSON Web Key Set", content_type = "application/json")),
+    tag = "UserAuth"
+)]
+pub async fn jwks() -> impl IntoResponse {
+    Json(KEYS.jwks().clone())
+}
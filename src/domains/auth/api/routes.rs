@@ -1,33 +1,193 @@
-use crate::common::app_state::AppState;
-use axum::{routing::post, Router};
+use crate::common::{
+    app_state::AppState,
+    jwt::API_KEY_HEADER,
+    rbac::{require_permission, AUTH_EVENT_READ, CLIENT_MANAGE, SESSION_REVOKE, USER_IMPERSONATE},
+};
+use axum::{
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
 
 use super::handlers;
 
-use utoipa::OpenApi;
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    OpenApi,
+};
 
 /// Import the necessary modules for OpenAPI documentation generation
 #[derive(OpenApi)]
 #[openapi(
     paths(
         super::handlers::login_user,
+        super::handlers::login_mfa,
+        super::handlers::oidc_login,
+        super::handlers::oidc_callback,
         super::handlers::create_user_auth,
+        super::handlers::refresh_token,
+        super::handlers::change_password,
+        super::handlers::forgot_password,
+        super::handlers::reset_password,
+        super::handlers::verify_email,
+        super::handlers::enroll_totp,
+        super::handlers::confirm_totp,
+        super::handlers::create_api_key,
+        super::handlers::list_api_keys,
+        super::handlers::delete_api_key,
+        super::handlers::create_oauth_client,
+        super::handlers::list_oauth_clients,
+        super::handlers::delete_oauth_client,
+        super::handlers::oauth_token,
+        super::handlers::introspect_token,
+        super::handlers::revoke_token,
+        super::handlers::logout,
+        super::handlers::list_sessions,
+        super::handlers::revoke_session,
+        super::handlers::revoke_all_sessions,
+        super::handlers::impersonate_user,
+        super::handlers::list_auth_events,
+        super::handlers::jwks,
     ),
     components(schemas(
         crate::domains::auth::dto::auth_dto::AuthUserDto,
+        crate::domains::auth::dto::auth_dto::RegisteredUserDto,
+        crate::domains::auth::dto::auth_dto::RefreshTokenDto,
+        crate::domains::auth::dto::auth_dto::LogoutDto,
+        crate::domains::auth::dto::auth_dto::ChangePasswordDto,
+        crate::domains::auth::dto::auth_dto::ForgotPasswordDto,
+        crate::domains::auth::dto::auth_dto::ResetPasswordDto,
+        crate::domains::auth::dto::auth_dto::VerifyEmailDto,
+        crate::domains::auth::dto::auth_dto::LoginResponseDto,
+        crate::domains::auth::dto::auth_dto::MfaChallengeDto,
+        crate::domains::auth::dto::auth_dto::MfaLoginDto,
+        crate::domains::auth::dto::auth_dto::TotpEnrollmentDto,
+        crate::domains::auth::dto::auth_dto::TotpCodeDto,
+        crate::domains::auth::dto::auth_dto::RecoveryCodesDto,
+        crate::domains::auth::dto::auth_dto::CreateApiKeyDto,
+        crate::domains::auth::dto::auth_dto::ApiKeyDto,
+        crate::domains::auth::dto::auth_dto::CreatedApiKeyDto,
+        crate::domains::auth::dto::auth_dto::CreateOAuthClientDto,
+        crate::domains::auth::dto::auth_dto::OAuthClientDto,
+        crate::domains::auth::dto::auth_dto::CreatedOAuthClientDto,
+        crate::domains::auth::dto::auth_dto::OAuthTokenRequestDto,
+        crate::domains::auth::dto::auth_dto::OAuthTokenDto,
+        crate::domains::auth::dto::auth_dto::OAuthTokenRefDto,
+        crate::domains::auth::dto::auth_dto::IntrospectionDto,
+        crate::domains::auth::dto::auth_dto::SessionDto,
+        crate::domains::auth::dto::auth_dto::AuthEventDto,
+        crate::domains::auth::domain::model::AuthEventType,
         crate::common::jwt::AuthPayload,
         crate::common::jwt::AuthBody,
     )),
     tags(
         (name = "UserAuth", description = "User authentication endpoints")
-    )
+    ),
+    modifiers(&UserAuthApiDoc)
 )]
 /// This struct is used to generate OpenAPI documentation for the user authentication routes.
 pub struct UserAuthApiDoc;
 
-/// This function creates a router for the user authentication routes.
+impl utoipa::Modify for UserAuthApiDoc {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.as_mut().unwrap();
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Input your `<your‑jwt>`"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                API_KEY_HEADER,
+                "API key created at `/auth/api-keys`",
+            ))),
+        );
+        components.add_security_scheme(
+            "client_basic",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Basic)
+                    .description(Some("OAuth2 client ID and secret"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// This function creates a router for the public user authentication routes.
 /// It defines the routes and their corresponding handlers.
 pub fn user_auth_routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(handlers::login_user))
+        .route("/login/mfa", post(handlers::login_mfa))
+        .route("/oidc/login", get(handlers::oidc_login))
+        .route("/oidc/callback", get(handlers::oidc_callback))
         .route("/register", post(handlers::create_user_auth))
+        .route("/refresh", post(handlers::refresh_token))
+        .route("/password/forgot", post(handlers::forgot_password))
+        .route("/password/reset", post(handlers::reset_password))
+        .route("/verify-email", post(handlers::verify_email))
+}
+
+/// This function creates a router for the user authentication routes
+/// that require a valid access token.
+pub fn user_auth_protected_routes() -> Router<AppState> {
+    Router::new()
+        .route("/logout", post(handlers::logout))
+        .route("/sessions", get(handlers::list_sessions))
+        .route("/sessions/{id}", delete(handlers::revoke_session))
+        .route("/password/change", post(handlers::change_password))
+        .route("/mfa/totp/enroll", post(handlers::enroll_totp))
+        .route("/mfa/totp/confirm", post(handlers::confirm_totp))
+        .route(
+            "/api-keys",
+            get(handlers::list_api_keys).post(handlers::create_api_key),
+        )
+        .route("/api-keys/{id}", delete(handlers::delete_api_key))
+        .route(
+            "/oauth/clients",
+            get(handlers::list_oauth_clients)
+                .post(handlers::create_oauth_client)
+                .route_layer(middleware::from_fn(require_permission(CLIENT_MANAGE))),
+        )
+        .route(
+            "/oauth/clients/{client_id}",
+            delete(handlers::delete_oauth_client)
+                .route_layer(middleware::from_fn(require_permission(CLIENT_MANAGE))),
+        )
+        .route(
+            "/users/{user_id}/sessions",
+            delete(handlers::revoke_all_sessions)
+                .route_layer(middleware::from_fn(require_permission(SESSION_REVOKE))),
+        )
+        .route(
+            "/impersonate/{user_id}",
+            post(handlers::impersonate_user)
+                .route_layer(middleware::from_fn(require_permission(USER_IMPERSONATE))),
+        )
+        .route(
+            "/events",
+            get(handlers::list_auth_events)
+                .route_layer(middleware::from_fn(require_permission(AUTH_EVENT_READ))),
+        )
+}
+
+/// This function creates a router for the OAuth2 endpoints.
+/// They authenticate the client instead of a user, so they are public.
+pub fn oauth_routes() -> Router<AppState> {
+    Router::new()
+        .route("/token", post(handlers::oauth_token))
+        .route("/introspect", post(handlers::introspect_token))
+        .route("/revoke", post(handlers::revoke_token))
+}
+
+/// This function creates a router for the public `/.well-known` discovery routes.
+pub fn well_known_routes() -> Router<AppState> {
+    Router::new().route("/jwks.json", get(handlers::jwks))
 }
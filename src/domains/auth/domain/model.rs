@@ -1,8 +1,14 @@
 //! This module defines the `UserAuth` model used for representing
-//! authentication data tied to a user.
+//! authentication data tied to a user, the `RefreshToken` model
+//! used for rotating refresh tokens, the password reset and email verification
+//! token models, the login lockout model, the MFA models, the API key model, the
+//! OAuth2 client model, the session model, the impersonation audit model, the
+//! authentication event models and the role/permission models.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
 
 /// Represents a user's authentication information, including hashed password.
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -10,3 +16,260 @@ pub struct UserAuth {
     pub user_id: String,
     pub password_hash: String,
 }
+
+/// Represents the account details needed to contact a user.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserAccount {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub email_verified_at: Option<DateTime<Utc>>,
+}
+
+/// Represents a stored refresh token.
+/// Only the SHA-256 hash of the opaque token is persisted.
+/// Tokens rotated from the same login share a `family_id`.
+/// Tokens issued through the OAuth2 token endpoint are bound to the `client_id` and
+/// the space-delimited `scope` granted to it.
+#[derive(Debug, Clone, FromRow)]
+pub struct RefreshToken {
+    pub id: String,
+    pub user_id: String,
+    pub family_id: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a stored password reset token.
+/// Only the SHA-256 hash of the opaque token is persisted; a token can be used once.
+#[derive(Debug, Clone, FromRow)]
+pub struct PasswordResetToken {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a stored email verification token.
+/// The token verifies the `email` it was sent to, so it becomes useless once the
+/// user changes the address again.
+#[derive(Debug, Clone, FromRow)]
+pub struct EmailVerificationToken {
+    pub id: String,
+    pub user_id: String,
+    pub email: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents an access token that was revoked before its expiry (e.g. on logout).
+#[derive(Debug, Clone, FromRow)]
+pub struct RevokedToken {
+    pub jti: String,
+    pub user_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Represents a "revoke all sessions" cut-off for a user.
+/// Every token issued at or before `revoked_at` is rejected.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserTokenRevocation {
+    pub user_id: String,
+    pub revoked_at: DateTime<Utc>,
+}
+
+/// Represents a lockout caused by too many failed login attempts.
+/// `scope` is either `username` or `ip`, and `key` the username or IP address.
+#[derive(Debug, Clone, FromRow)]
+pub struct LoginLockout {
+    pub id: String,
+    pub scope: String,
+    pub key: String,
+    pub failed_attempts: i32,
+    pub locked_until: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents the TOTP second factor of a user.
+/// MFA is only enforced once `enabled_at` is set by confirming the enrollment.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserMfa {
+    pub user_id: String,
+    pub totp_secret: String,
+    pub enabled_at: Option<DateTime<Utc>>,
+}
+
+/// Represents a stored MFA recovery code.
+/// Only the SHA-256 hash of the normalized code is persisted; a code can be used once.
+#[derive(Debug, Clone, FromRow)]
+pub struct MfaRecoveryCode {
+    pub id: String,
+    pub user_id: String,
+    pub code_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a pending second login step.
+/// Only the SHA-256 hash of the opaque token is persisted; a challenge can be used once
+/// and is given up after too many wrong codes.
+#[derive(Debug, Clone, FromRow)]
+pub struct MfaChallenge {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub failed_attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents an API key of a machine-to-machine client acting on behalf of a user.
+/// The key is presented as `<prefix>.<secret>`; only the SHA-256 hash of the secret
+/// is persisted. `scopes` lists the permission names the key is limited to.
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub prefix: String,
+    pub secret_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a client registered to use the OAuth2 token endpoint.
+/// Only the SHA-256 hash of the client secret is persisted. `scopes` lists the
+/// permission names the client may request and `grant_types` the grants it may use.
+#[derive(Debug, Clone, FromRow)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_secret_hash: String,
+    pub name: String,
+    pub user_id: String,
+    pub scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a login session of a user.
+/// The session ID is also the family ID of the refresh tokens rotated from the login,
+/// and is carried by its access tokens as the `sid` claim. `last_seen_at` is updated
+/// on login and on every refresh.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserSession {
+    pub id: String,
+    pub user_id: String,
+    pub device_id: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Represents an entry of the impersonation audit trail: a request that `actor_id` made
+/// as `user_id` with the impersonation token `token_id`, or the issuing of the token.
+#[derive(Debug, Clone, FromRow)]
+pub struct ImpersonationAudit {
+    pub id: String,
+    pub actor_id: String,
+    pub user_id: String,
+    pub token_id: String,
+    pub method: String,
+    pub path: String,
+    pub status: i16,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Type of an authentication event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventType {
+    LoginSuccess,
+    LoginFailure,
+    LoginLockout,
+    TokenRefresh,
+    PasswordChange,
+    PasswordReset,
+}
+
+impl AuthEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventType::LoginSuccess => "login_success",
+            AuthEventType::LoginFailure => "login_failure",
+            AuthEventType::LoginLockout => "login_lockout",
+            AuthEventType::TokenRefresh => "token_refresh",
+            AuthEventType::PasswordChange => "password_change",
+            AuthEventType::PasswordReset => "password_reset",
+        }
+    }
+}
+
+/// Represents an authentication event of the auth event log.
+/// `user_id` is missing for failed logins of unknown usernames, `username` is the one
+/// entered at login, and `detail` gives the reason of a failed login.
+#[derive(Debug, Clone, FromRow)]
+pub struct AuthEvent {
+    pub id: String,
+    pub event_type: String,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Criteria of an auth event log query; unset criteria match every event.
+/// `from` is inclusive and `to` is exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct AuthEventFilter {
+    pub user_id: Option<String>,
+    pub event_type: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Represents a login started by a redirect to the OpenID Connect provider.
+/// Only the SHA-256 hash of the state is persisted; the PKCE code verifier and the nonce
+/// are needed to complete the login, which can be done once.
+#[derive(Debug, Clone, FromRow)]
+pub struct OidcLogin {
+    pub id: String,
+    pub state_hash: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents an external identity linked to a user: the subject `subject` of the
+/// OpenID Connect provider `issuer`. `email` is the one reported when it was linked.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserIdentity {
+    pub id: String,
+    pub user_id: String,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a permission granted by a role, both referenced by name.
+#[derive(Debug, Clone, FromRow)]
+pub struct RolePermission {
+    pub role: String,
+    pub permission: String,
+}
@@ -1,9 +1,20 @@
-//! This module defines the `UserAuthRepository` trait, which provides an abstraction
-//! over database operations related to user authentication records.
+//! This module defines the `UserAuthRepository`, `RefreshTokenRepository`,
+//! `PasswordResetTokenRepository`, `EmailVerificationTokenRepository`,
+//! `LoginThrottleRepository`, `MfaRepository`, `MfaChallengeRepository`,
+//! `ApiKeyRepository`, `OAuthClientRepository`, `SessionRepository`,
+//! `ImpersonationAuditRepository`, `AuthEventRepository`, `OidcLoginRepository`,
+//! `UserIdentityRepository`, `TokenRevocationRepository` and `RoleRepository` traits,
+//! which provide an abstraction over database operations related to user authentication and authorization records.
 
-use super::model::UserAuth;
+use super::model::{
+    ApiKey, AuthEvent, AuthEventFilter, EmailVerificationToken, ImpersonationAudit, LoginLockout,
+    MfaChallenge, MfaRecoveryCode, OAuthClient, OidcLogin, PasswordResetToken, RefreshToken,
+    RevokedToken, RolePermission, UserAccount, UserAuth, UserIdentity, UserMfa, UserSession,
+    UserTokenRevocation,
+};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 #[async_trait]
@@ -18,10 +29,568 @@ pub trait UserAuthRepository: Send + Sync {
         user_name: String,
     ) -> Result<Option<UserAuth>, sqlx::Error>;
 
+    /// Finds a user authentication record by the user's ID.
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserAuth>, sqlx::Error>;
+
+    /// Finds the account of a user by the user's username, whether or not it has credentials.
+    async fn find_account_by_user_name(
+        &self,
+        pool: PgPool,
+        user_name: String,
+    ) -> Result<Option<UserAccount>, sqlx::Error>;
+
+    /// Finds the account of a user by the user's ID, whether or not it has credentials.
+    async fn find_account_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserAccount>, sqlx::Error>;
+
+    /// Checks whether a user exists and is not deleted, whether or not it has credentials.
+    async fn user_exists(&self, pool: PgPool, user_id: String) -> Result<bool, sqlx::Error>;
+
+    /// Inserts a new user record using a transaction, so that it can be created
+    /// together with its credentials. The user is recorded as its own creator.
+    async fn create_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        username: String,
+        email: String,
+    ) -> Result<(), sqlx::Error>;
+
     /// Inserts a new user authentication record into the database using a transaction.
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_auth: UserAuth,
     ) -> Result<(), sqlx::Error>;
+
+    /// Replaces the password hash of a user using a transaction.
+    async fn update_password(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        password_hash: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Replaces the password hash of a user, provided it is still `old_hash`.
+    /// Returns `false` if the password has been changed in the meantime.
+    async fn replace_password_hash(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        old_hash: String,
+        new_hash: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Marks the email of a user as verified, provided it is still `email`.
+    /// Returns `false` if the user has changed the address in the meantime.
+    async fn mark_email_verified(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        email: String,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for password reset tokens.
+pub trait PasswordResetTokenRepository: Send + Sync {
+    /// Inserts a new password reset token record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        reset_token: PasswordResetToken,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a password reset token by its hash and locks the row for the rest of the transaction.
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<PasswordResetToken>, sqlx::Error>;
+
+    /// Marks every unused password reset token of a user as used.
+    async fn use_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for refresh tokens.
+pub trait RefreshTokenRepository: Send + Sync {
+    /// Inserts a new refresh token record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        refresh_token: RefreshToken,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a refresh token by its hash.
+    async fn find_by_hash(
+        &self,
+        pool: PgPool,
+        token_hash: String,
+    ) -> Result<Option<RefreshToken>, sqlx::Error>;
+
+    /// Finds a refresh token by its hash and locks the row for the rest of the transaction.
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<RefreshToken>, sqlx::Error>;
+
+    /// Marks a refresh token as used and records the token that replaced it.
+    async fn mark_rotated(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        replaced_by: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Revokes every still-active token of a token family.
+    /// Returns the number of revoked tokens.
+    async fn revoke_family(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        family_id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Revokes every still-active refresh token of a user.
+    async fn revoke_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for email verification tokens.
+pub trait EmailVerificationTokenRepository: Send + Sync {
+    /// Inserts a new email verification token record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        verification_token: EmailVerificationToken,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds an email verification token by its hash and locks the row for the rest of the transaction.
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<EmailVerificationToken>, sqlx::Error>;
+
+    /// Marks every unused email verification token of a user as used.
+    async fn use_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for failed login attempts and lockouts.
+/// Attempts are counted per `scope` (`username` or `ip`) and `key`.
+pub trait LoginThrottleRepository: Send + Sync {
+    /// Returns the time until which the key is locked, if it has ever been locked.
+    async fn find_locked_until(
+        &self,
+        pool: PgPool,
+        scope: String,
+        key: String,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error>;
+
+    /// Counts a failed attempt and returns the number of consecutive failed attempts.
+    /// The count restarts if the previous failure happened before `window_start`.
+    async fn record_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: String,
+        key: String,
+        window_start: DateTime<Utc>,
+    ) -> Result<i32, sqlx::Error>;
+
+    /// Locks the key until the given time.
+    async fn lock(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: String,
+        key: String,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Records a lockout event.
+    async fn record_lockout(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        lockout: LoginLockout,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Clears the failed attempts and any lock of the key.
+    async fn reset(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: String,
+        key: String,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for the TOTP second factor and recovery codes.
+pub trait MfaRepository: Send + Sync {
+    /// Finds the TOTP second factor of a user, whether confirmed or not.
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserMfa>, sqlx::Error>;
+
+    /// Stores a new, unconfirmed TOTP secret, replacing a previous unconfirmed one.
+    /// Returns `false` if MFA is already enabled for the user.
+    async fn save_secret(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        totp_secret: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Enables MFA and records the time step of the code that confirmed it.
+    /// Returns `false` if MFA was enabled in the meantime.
+    async fn enable(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        step: i64,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Records the time step of an accepted code.
+    /// Returns `false` if a code of the same or a later step has been accepted already.
+    async fn use_step(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        step: i64,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Replaces every recovery code of a user.
+    async fn replace_recovery_codes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        recovery_codes: Vec<MfaRecoveryCode>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Marks an unused recovery code of a user as used.
+    /// Returns `false` if the user has no such unused code.
+    async fn use_recovery_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        code_hash: String,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for pending second login steps.
+pub trait MfaChallengeRepository: Send + Sync {
+    /// Inserts a new MFA challenge record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        challenge: MfaChallenge,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds an MFA challenge by its hash and locks the row for the rest of the transaction.
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<MfaChallenge>, sqlx::Error>;
+
+    /// Counts a wrong code and returns the number of wrong codes so far.
+    async fn record_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<i32, sqlx::Error>;
+
+    /// Marks an MFA challenge as used.
+    async fn mark_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for API keys.
+pub trait ApiKeyRepository: Send + Sync {
+    /// Inserts a new API key record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        api_key: ApiKey,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds an API key by its ID.
+    async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    /// Finds an API key by the prefix that identifies it.
+    async fn find_by_prefix(
+        &self,
+        pool: PgPool,
+        prefix: String,
+    ) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    /// Returns every API key of a user, newest first.
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<ApiKey>, sqlx::Error>;
+
+    /// Records that the key has been used. The timestamp is only updated once per minute,
+    /// so that busy clients do not write on every request.
+    async fn touch(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Deletes an API key. Returns `false` if it did not exist.
+    async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for OAuth2 clients.
+pub trait OAuthClientRepository: Send + Sync {
+    /// Inserts a new client record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client: OAuthClient,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a client by its client ID.
+    async fn find_by_client_id(
+        &self,
+        pool: PgPool,
+        client_id: String,
+    ) -> Result<Option<OAuthClient>, sqlx::Error>;
+
+    /// Returns every registered client, newest first.
+    async fn find_all(&self, pool: PgPool) -> Result<Vec<OAuthClient>, sqlx::Error>;
+
+    /// Deletes a client together with its refresh tokens. Returns `false` if it did not exist.
+    async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client_id: String,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for login sessions.
+pub trait SessionRepository: Send + Sync {
+    /// Inserts a new session record using a transaction.
+    /// The device is only linked if it belongs to the user of the session.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        session: UserSession,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a session by its ID.
+    async fn find_by_id(
+        &self,
+        pool: PgPool,
+        id: String,
+    ) -> Result<Option<UserSession>, sqlx::Error>;
+
+    /// Returns the sessions of a user that are not revoked and were seen after `since`,
+    /// most recently seen first.
+    async fn find_active_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, sqlx::Error>;
+
+    /// Records activity of a session that is not revoked.
+    async fn touch(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Revokes a session. Returns `false` if it did not exist or was already revoked.
+    async fn revoke(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Revokes every session of a user.
+    async fn revoke_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the IDs of the sessions revoked after `since`.
+    /// Sessions revoked earlier cannot have access tokens that are still valid.
+    async fn find_revoked_since(
+        &self,
+        pool: PgPool,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<String>, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for the impersonation audit trail.
+pub trait ImpersonationAuditRepository: Send + Sync {
+    /// Inserts an audit entry using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        entry: ImpersonationAudit,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for the authentication event log.
+pub trait AuthEventRepository: Send + Sync {
+    /// Inserts an authentication event using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: AuthEvent,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns a page of the events matching the filter, most recent first.
+    async fn find(
+        &self,
+        pool: PgPool,
+        filter: AuthEventFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuthEvent>, sqlx::Error>;
+
+    /// Counts the events matching the filter.
+    async fn count(&self, pool: PgPool, filter: AuthEventFilter) -> Result<i64, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for pending OpenID Connect logins.
+pub trait OidcLoginRepository: Send + Sync {
+    /// Inserts a new OpenID Connect login record using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        login: OidcLogin,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a login by the hash of its state and locks the row for the rest of the transaction.
+    async fn find_by_state_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        state_hash: String,
+    ) -> Result<Option<OidcLogin>, sqlx::Error>;
+
+    /// Marks a login as used.
+    async fn mark_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for external identities linked to users.
+pub trait UserIdentityRepository: Send + Sync {
+    /// Finds the account of the user linked to the subject of the issuer.
+    async fn find_account(
+        &self,
+        pool: PgPool,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<UserAccount>, sqlx::Error>;
+
+    /// Finds the accounts of the users whose verified email address is the given one.
+    async fn find_accounts_by_verified_email(
+        &self,
+        pool: PgPool,
+        email: String,
+    ) -> Result<Vec<UserAccount>, sqlx::Error>;
+
+    /// Links an external identity to a user using a transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        identity: UserIdentity,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for the access token revocation list.
+pub trait TokenRevocationRepository: Send + Sync {
+    /// Adds a single access token to the revocation list.
+    async fn revoke_token(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        revoked_token: RevokedToken,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Records a cut-off time before which all tokens of the user are rejected.
+    async fn revoke_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        revocation: UserTokenRevocation,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the JWT IDs of all revoked tokens that have not expired yet.
+    async fn find_active_revoked_tokens(&self, pool: PgPool) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Returns the user cut-offs recorded after `since`.
+    /// Older cut-offs cannot affect tokens that are still valid.
+    async fn find_user_revocations_since(
+        &self,
+        pool: PgPool,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UserTokenRevocation>, sqlx::Error>;
+}
+
+#[async_trait]
+/// Trait representing the repository contract for roles and their permissions.
+pub trait RoleRepository: Send + Sync {
+    /// Returns the names of the roles assigned to the user.
+    async fn find_role_names_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Returns every role/permission pair.
+    async fn find_role_permissions(&self, pool: PgPool)
+        -> Result<Vec<RolePermission>, sqlx::Error>;
+
+    /// Assigns the role with the given name to the user, if not assigned yet.
+    async fn assign_role(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        role_name: String,
+    ) -> Result<(), sqlx::Error>;
 }
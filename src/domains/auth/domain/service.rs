@@ -7,10 +7,23 @@ use sqlx::PgPool;
 
 use crate::{
     common::{
+        config::Config,
+        dto::PageDto,
         error::AppError,
-        jwt::{AuthBody, AuthPayload},
+        jwt::{AuthBody, AuthPayload, Claims},
+        mail::MailSender,
+        password_policy::PasswordPolicy,
+        rbac::Permissions,
+    },
+    domains::auth::dto::auth_dto::{
+        ApiKeyDto, AuthEventDto, AuthEventQueryDto, AuthUserDto, ChangePasswordDto,
+        ClientCredentials, CreateApiKeyDto, CreateOAuthClientDto, CreatedApiKeyDto,
+        CreatedOAuthClientDto, ForgotPasswordDto, IntrospectionDto, LoginClient, LoginResponseDto,
+        LogoutDto, MfaLoginDto, OAuthClientDto, OAuthTokenDto, OAuthTokenRefDto,
+        OAuthTokenRequestDto, OidcCallbackDto, RecoveryCodesDto, RefreshTokenDto,
+        RegisteredUserDto, ResetPasswordDto, SessionDto, TotpCodeDto, TotpEnrollmentDto,
+        VerifyEmailDto,
     },
-    domains::auth::dto::auth_dto::AuthUserDto,
 };
 
 #[async_trait::async_trait]
@@ -18,13 +31,183 @@ use crate::{
 /// Implementors are responsible for handling user creation and login logic.
 pub trait AuthServiceTrait: Send + Sync {
     /// constructor for the service.
-    fn create_service(pool: PgPool) -> Arc<dyn AuthServiceTrait>
+    fn create_service(
+        config: Config,
+        pool: PgPool,
+        mail_sender: Arc<dyn MailSender>,
+        password_policy: PasswordPolicy,
+    ) -> Arc<dyn AuthServiceTrait>
     where
         Self: Sized;
 
-    /// Registers a new user authentication entry.
-    async fn create_user_auth(&self, auth_user: AuthUserDto) -> Result<(), AppError>;
+    /// Registers a new user together with its credentials.
+    async fn create_user_auth(&self, auth_user: AuthUserDto)
+        -> Result<RegisteredUserDto, AppError>;
+
+    /// Authenticates a user and returns a JWT token payload on success,
+    /// or an MFA challenge if the user has MFA enabled.
+    /// Failed attempts are throttled per username and, if known, per client IP.
+    /// A successful login starts a session recording the client.
+    /// Successful and failed logins and lockouts are recorded in the auth event log.
+    async fn login_user(
+        &self,
+        auth_payload: AuthPayload,
+        client: LoginClient,
+    ) -> Result<LoginResponseDto, AppError>;
+
+    /// Exchanges an MFA challenge and a TOTP or recovery code for a JWT token payload.
+    /// Wrong codes are throttled like wrong passwords.
+    async fn login_mfa(
+        &self,
+        payload: MfaLoginDto,
+        client: LoginClient,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Starts a login with the OpenID Connect provider and returns the URL of its
+    /// authorization endpoint, which the browser is redirected to.
+    async fn start_oidc_login(&self) -> Result<String, AppError>;
+
+    /// Completes a login with the OpenID Connect provider: exchanges the authorization code
+    /// for an ID token, maps the external identity to a user and logs the user in.
+    async fn complete_oidc_login(
+        &self,
+        payload: OidcCallbackDto,
+        client: LoginClient,
+    ) -> Result<LoginResponseDto, AppError>;
+
+    /// Starts a TOTP enrollment by generating a new secret for the authenticated user.
+    async fn enroll_totp(&self, claims: Claims) -> Result<TotpEnrollmentDto, AppError>;
+
+    /// Enables MFA if the code matches the enrolled secret and returns new recovery codes.
+    async fn confirm_totp(
+        &self,
+        claims: Claims,
+        payload: TotpCodeDto,
+    ) -> Result<RecoveryCodesDto, AppError>;
+
+    /// Rotates a refresh token and returns a new access/refresh token pair.
+    /// Replaying an already rotated token revokes the whole token family.
+    async fn refresh_token(
+        &self,
+        payload: RefreshTokenDto,
+        client: LoginClient,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Changes the password of the authenticated user after checking the current password.
+    async fn change_password(
+        &self,
+        claims: Claims,
+        payload: ChangePasswordDto,
+        client: LoginClient,
+    ) -> Result<(), AppError>;
+
+    /// Mails a single-use password reset token to the user, if the user exists.
+    async fn request_password_reset(&self, payload: ForgotPasswordDto) -> Result<(), AppError>;
+
+    /// Sets a new password using a password reset token and revokes all sessions of the user.
+    async fn reset_password(
+        &self,
+        payload: ResetPasswordDto,
+        client: LoginClient,
+    ) -> Result<(), AppError>;
+
+    /// Mails a single-use token that verifies the given email address of the user.
+    async fn send_email_verification(&self, user_id: String, email: String)
+        -> Result<(), AppError>;
+
+    /// Marks the email address the token was sent to as verified.
+    async fn verify_email(&self, payload: VerifyEmailDto) -> Result<(), AppError>;
+
+    /// Creates an API key for the authenticated user, limited to scopes the user holds.
+    async fn create_api_key(
+        &self,
+        claims: Claims,
+        permissions: Permissions,
+        payload: CreateApiKeyDto,
+    ) -> Result<CreatedApiKeyDto, AppError>;
+
+    /// Lists the API keys of the authenticated user.
+    async fn list_api_keys(&self, claims: Claims) -> Result<Vec<ApiKeyDto>, AppError>;
+
+    /// Deletes an API key of the authenticated user; admins may delete any key.
+    async fn delete_api_key(&self, claims: Claims, id: String) -> Result<(), AppError>;
+
+    /// Authenticates an `X-API-Key` header value and returns the claims of the key's
+    /// user together with the permissions the key grants.
+    async fn authenticate_api_key(&self, api_key: &str) -> Result<(Claims, Permissions), AppError>;
+
+    /// Registers an OAuth2 client owned by the authenticated user, limited to scopes the user holds.
+    async fn create_oauth_client(
+        &self,
+        claims: Claims,
+        permissions: Permissions,
+        payload: CreateOAuthClientDto,
+    ) -> Result<CreatedOAuthClientDto, AppError>;
+
+    /// Lists every registered OAuth2 client.
+    async fn list_oauth_clients(&self) -> Result<Vec<OAuthClientDto>, AppError>;
+
+    /// Deletes an OAuth2 client together with its refresh tokens.
+    async fn delete_oauth_client(&self, client_id: String) -> Result<(), AppError>;
+
+    /// Issues tokens to an authenticated OAuth2 client (RFC 6749).
+    async fn oauth_token(
+        &self,
+        client: ClientCredentials,
+        payload: OAuthTokenRequestDto,
+        login_client: LoginClient,
+    ) -> Result<OAuthTokenDto, AppError>;
+
+    /// Describes the state of an access or refresh token (RFC 7662).
+    async fn introspect_token(
+        &self,
+        client: ClientCredentials,
+        payload: OAuthTokenRefDto,
+    ) -> Result<IntrospectionDto, AppError>;
+
+    /// Revokes an access or refresh token issued to the client (RFC 7009).
+    async fn revoke_oauth_token(
+        &self,
+        client: ClientCredentials,
+        payload: OAuthTokenRefDto,
+    ) -> Result<(), AppError>;
+
+    /// Revokes the presented access token and its session and, optionally,
+    /// a refresh token family.
+    async fn logout(&self, claims: Claims, payload: LogoutDto) -> Result<(), AppError>;
+
+    /// Lists the active sessions of the authenticated user.
+    async fn list_sessions(&self, claims: Claims) -> Result<Vec<SessionDto>, AppError>;
+
+    /// Revokes a session of the authenticated user; admins may revoke any session.
+    async fn revoke_session(&self, claims: Claims, id: String) -> Result<(), AppError>;
+
+    /// Revokes every access and refresh token issued to the user so far.
+    async fn revoke_all_sessions(&self, user_id: String) -> Result<(), AppError>;
+
+    /// Issues an access token for acting as the user on behalf of the authenticated admin.
+    async fn impersonate(&self, claims: Claims, user_id: String) -> Result<AuthBody, AppError>;
+
+    /// Records a request made with an impersonation token in the audit trail.
+    /// Failures are only logged, since the request has already been handled.
+    async fn record_impersonated_request(
+        &self,
+        claims: &Claims,
+        method: &str,
+        path: &str,
+        status: u16,
+    );
+
+    /// Queries the log of authentication events: logins, failed logins, lockouts,
+    /// token refreshes and password changes.
+    async fn list_auth_events(
+        &self,
+        query: AuthEventQueryDto,
+    ) -> Result<PageDto<AuthEventDto>, AppError>;
+
+    /// Checks whether the token described by the claims has been revoked.
+    async fn is_token_revoked(&self, claims: &Claims) -> Result<bool, AppError>;
 
-    /// Authenticates a user and returns a JWT token payload on success.
-    async fn login_user(&self, auth_payload: AuthPayload) -> Result<AuthBody, AppError>;
+    /// Resolves the permissions granted by the given roles.
+    async fn resolve_permissions(&self, roles: &[String]) -> Result<Permissions, AppError>;
 }
@@ -1,9 +1,403 @@
-use serde::Deserialize;
-use utoipa::ToSchema;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
-#[derive(Debug, Deserialize, ToSchema, Validate)]
+use crate::{
+    common::jwt::AuthBody,
+    domains::auth::domain::model::{
+        ApiKey, AuthEvent, AuthEventFilter, AuthEventType, OAuthClient, UserSession,
+    },
+};
+
+/// Request body for self-service registration.
+/// The user and its credentials are created together.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct AuthUserDto {
-    pub user_id: String,
+    #[validate(length(min = 1, max = 64, message = "Username must be 1 to 64 characters"))]
+    pub username: String,
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+    #[validate(length(max = 128, message = "Password cannot exceed 128 characters"))]
     pub password: String,
 }
+
+/// Response body for a successful registration.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RegisteredUserDto {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+}
+
+/// Request body for exchanging a refresh token for a new token pair.
+/// With cookie authentication enabled, `refresh_token` may be left out to use the cookie.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshTokenDto {
+    #[serde(default)]
+    pub refresh_token: String,
+}
+
+/// Request body for logging out.
+/// If a refresh token is given, every token rotated from the same login is revoked as well.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct LogoutDto {
+    pub refresh_token: Option<String>,
+}
+
+/// Request body for changing the password of the authenticated user.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ChangePasswordDto {
+    pub current_password: String,
+    #[validate(length(max = 128, message = "Password cannot exceed 128 characters"))]
+    pub new_password: String,
+}
+
+/// Request body for requesting a password reset token by mail.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ForgotPasswordDto {
+    pub username: String,
+}
+
+/// Request body for setting a new password with a password reset token.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ResetPasswordDto {
+    pub token: String,
+    #[validate(length(max = 128, message = "Password cannot exceed 128 characters"))]
+    pub new_password: String,
+}
+
+/// Request body for verifying an email address with the token that was mailed to it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VerifyEmailDto {
+    pub token: String,
+}
+
+/// Response body of the first login step for users with MFA enabled.
+/// The `mfa_token` is exchanged together with a code at `/auth/login/mfa`;
+/// `expires_in` is its lifetime in seconds.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaChallengeDto {
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
+
+/// Response body for logging in.
+/// Users with MFA enabled receive an MFA challenge instead of the tokens.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponseDto {
+    Authenticated(AuthBody),
+    MfaRequired(MfaChallengeDto),
+}
+
+/// Request body for the second login step.
+/// `code` is either a TOTP code or one of the recovery codes.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaLoginDto {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+/// Query parameters of the redirect from the OpenID Connect provider back to the callback.
+/// The provider sends either an authorization `code` or an `error`, with the `state`
+/// of the login.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallbackDto {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Response body for starting a TOTP enrollment.
+/// Add the secret to an authenticator app, e.g. by rendering the URI as a QR code.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TotpEnrollmentDto {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Request body for confirming a TOTP enrollment with a code of the authenticator app.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TotpCodeDto {
+    pub code: String,
+}
+
+/// Response body for a confirmed TOTP enrollment.
+/// The recovery codes are only shown once; each of them can replace a TOTP code once.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecoveryCodesDto {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Request body for creating an API key.
+/// `scopes` are permission names, each of which must be granted to the user.
+/// Without `expires_at`, the key is valid until it is deleted.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateApiKeyDto {
+    #[validate(length(min = 1, max = 64, message = "Name must be 1 to 64 characters"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Response body describing an API key, without its secret.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyDto {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ApiKey> for ApiKeyDto {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            prefix: api_key.prefix,
+            scopes: api_key.scopes,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+            created_at: api_key.created_at,
+        }
+    }
+}
+
+/// Client a login request was sent from, recorded with the session the login starts.
+/// `device_id` is taken from the `X-Device-Id` header and only linked if the device
+/// belongs to the user.
+#[derive(Debug, Clone, Default)]
+pub struct LoginClient {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub device_id: Option<String>,
+}
+
+/// Response body describing a login session.
+/// `current` is set for the session of the token the request was made with.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionDto {
+    pub id: String,
+    pub device_id: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::common::ts_format")]
+    pub last_seen_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+impl From<UserSession> for SessionDto {
+    fn from(session: UserSession) -> Self {
+        Self {
+            id: session.id,
+            device_id: session.device_id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            current: false,
+        }
+    }
+}
+
+/// Response body for a created API key.
+/// `key` is sent in the `X-API-Key` header; it is only shown once.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedApiKeyDto {
+    pub key: String,
+    pub api_key: ApiKeyDto,
+}
+
+/// Request body for registering an OAuth2 client.
+/// `scopes` are permission names, each of which must be granted to the user registering
+/// the client. `grant_types` lists the grants of the token endpoint the client may use:
+/// `password`, `client_credentials` and `refresh_token`.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateOAuthClientDto {
+    #[validate(length(min = 1, max = 64, message = "Name must be 1 to 64 characters"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+    #[validate(length(min = 1, message = "At least one grant type is required"))]
+    pub grant_types: Vec<String>,
+}
+
+/// Response body describing an OAuth2 client, without its secret.
+/// Tokens of the `client_credentials` grant act on behalf of `user_id`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthClientDto {
+    pub client_id: String,
+    pub name: String,
+    pub user_id: String,
+    pub scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<OAuthClient> for OAuthClientDto {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            client_id: client.client_id,
+            name: client.name,
+            user_id: client.user_id,
+            scopes: client.scopes,
+            grant_types: client.grant_types,
+            created_at: client.created_at,
+        }
+    }
+}
+
+/// Response body for a registered OAuth2 client.
+/// `client_secret` is only shown once.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedOAuthClientDto {
+    pub client_secret: String,
+    pub client: OAuthClientDto,
+}
+
+/// Credentials of an OAuth2 client, taken from the HTTP Basic `Authorization` header
+/// or from the form body.
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Form body of the OAuth2 token endpoint (RFC 6749).
+/// `username` and `password` are used by the `password` grant and `refresh_token` by the
+/// `refresh_token` grant. `scope` is a space-delimited list of permission names; without
+/// it, every scope of the client (or of the refresh token) is granted.
+/// Clients that cannot use HTTP Basic authentication send `client_id` and `client_secret`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthTokenRequestDto {
+    pub grant_type: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// Response body of the OAuth2 token endpoint.
+/// The `client_credentials` grant does not issue a refresh token.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthTokenDto {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+}
+
+/// Form body of the token introspection (RFC 7662) and revocation (RFC 7009) endpoints.
+/// `token_type_hint` is either `access_token` or `refresh_token`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthTokenRefDto {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// Response body of the token introspection endpoint (RFC 7662).
+/// Only `active` is set for tokens that are unknown, expired or revoked.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionDto {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// Query parameters of the auth event log.
+/// `from` (inclusive) and `to` (exclusive) are RFC 3339 timestamps; pages start at 1.
+#[derive(Debug, Default, Deserialize, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+pub struct AuthEventQueryDto {
+    pub user_id: Option<String>,
+    pub event_type: Option<AuthEventType>,
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub to: Option<DateTime<Utc>>,
+    #[validate(range(min = 1, message = "Page must be at least 1"))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100, message = "Page size must be 1 to 100"))]
+    pub page_size: Option<i64>,
+}
+
+impl AuthEventQueryDto {
+    /// Returns the criteria of the query.
+    pub fn filter(&self) -> AuthEventFilter {
+        AuthEventFilter {
+            user_id: self.user_id.clone(),
+            event_type: self.event_type.map(|t| t.as_str().to_string()),
+            from: self.from,
+            to: self.to,
+        }
+    }
+}
+
+/// Response body describing an authentication event.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuthEventDto {
+    pub id: String,
+    pub event_type: String,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AuthEvent> for AuthEventDto {
+    fn from(event: AuthEvent) -> Self {
+        Self {
+            id: event.id,
+            event_type: event.event_type,
+            user_id: event.user_id,
+            username: event.username,
+            ip_address: event.ip_address,
+            user_agent: event.user_agent,
+            detail: event.detail,
+            created_at: event.created_at,
+        }
+    }
+}
@@ -1,10 +1,52 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
-use crate::domains::auth::domain::model::UserAuth;
-use crate::domains::auth::domain::repository::UserAuthRepository;
+use crate::domains::auth::domain::model::{
+    ApiKey, AuthEvent, AuthEventFilter, EmailVerificationToken, ImpersonationAudit, LoginLockout,
+    MfaChallenge, MfaRecoveryCode, OAuthClient, OidcLogin, PasswordResetToken, RefreshToken,
+    RevokedToken, RolePermission, UserAccount, UserAuth, UserIdentity, UserMfa, UserSession,
+    UserTokenRevocation,
+};
+use crate::domains::auth::domain::repository::{
+    ApiKeyRepository, AuthEventRepository, EmailVerificationTokenRepository,
+    ImpersonationAuditRepository, LoginThrottleRepository, MfaChallengeRepository, MfaRepository,
+    OAuthClientRepository, OidcLoginRepository, PasswordResetTokenRepository,
+    RefreshTokenRepository, RoleRepository, SessionRepository, TokenRevocationRepository,
+    UserAuthRepository, UserIdentityRepository,
+};
 pub struct UserAuthRepo;
 
+pub struct RefreshTokenRepo;
+
+pub struct PasswordResetTokenRepo;
+
+pub struct EmailVerificationTokenRepo;
+
+pub struct LoginThrottleRepo;
+
+pub struct MfaRepo;
+
+pub struct MfaChallengeRepo;
+
+pub struct ApiKeyRepo;
+
+pub struct OAuthClientRepo;
+
+pub struct SessionRepo;
+
+pub struct ImpersonationAuditRepo;
+
+pub struct AuthEventRepo;
+
+pub struct OidcLoginRepo;
+
+pub struct UserIdentityRepo;
+
+pub struct TokenRevocationRepo;
+
+pub struct RoleRepo;
+
 #[async_trait]
 impl UserAuthRepository for UserAuthRepo {
     async fn find_by_user_name(
@@ -19,6 +61,50 @@ impl UserAuthRepository for UserAuthRepo {
               FROM user_auth ua
               JOIN users u ON ua.user_id = u.id
               WHERE u.username = $1
+                AND u.deleted_at IS NULL
+            "#,
+            user_name
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserAuth>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserAuth,
+            r#"
+            SELECT ua.user_id, ua.password_hash
+              FROM user_auth ua
+              JOIN users u ON ua.user_id = u.id
+              WHERE ua.user_id = $1
+                AND u.deleted_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_account_by_user_name(
+        &self,
+        pool: PgPool,
+        user_name: String,
+    ) -> Result<Option<UserAccount>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserAccount,
+            r#"
+            SELECT u.id AS user_id, u.username, u.email, u.email_verified_at
+              FROM users u
+              WHERE u.username = $1
+                AND u.deleted_at IS NULL
             "#,
             user_name
         )
@@ -28,6 +114,61 @@ impl UserAuthRepository for UserAuthRepo {
         Ok(result)
     }
 
+    async fn find_account_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserAccount>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserAccount,
+            r#"
+            SELECT u.id AS user_id, u.username, u.email, u.email_verified_at
+              FROM users u
+              WHERE u.id = $1
+                AND u.deleted_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn user_exists(&self, pool: PgPool, user_id: String) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL) AS "exists!""#,
+            user_id
+        )
+        .fetch_one(&pool)
+        .await?;
+        Ok(exists)
+    }
+
+    async fn create_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        username: String,
+        email: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO users
+            (id, username, email, created_by, modified_by)
+            VALUES
+            ($1, $2, $3, $1, $1)
+            "#,
+            id,
+            username,
+            email
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -48,4 +189,1489 @@ impl UserAuthRepository for UserAuthRepo {
 
         Ok(())
     }
+
+    async fn update_password(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        password_hash: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE user_auth
+               SET password_hash = $1,
+                   modified_at = NOW()
+             WHERE user_id = $2
+            "#,
+            password_hash,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn replace_password_hash(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        old_hash: String,
+        new_hash: String,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_auth
+               SET password_hash = $1,
+                   modified_at = NOW()
+             WHERE user_id = $2
+               AND password_hash = $3
+            "#,
+            new_hash,
+            user_id,
+            old_hash
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn mark_email_verified(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        email: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE users
+               SET email_verified_at = NOW(),
+                   version = version + 1
+             WHERE id = $1
+               AND email = $2
+               AND deleted_at IS NULL
+            "#,
+            user_id,
+            email
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl PasswordResetTokenRepository for PasswordResetTokenRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        reset_token: PasswordResetToken,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens
+            (id, user_id, token_hash, expires_at, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5)
+            "#,
+            reset_token.id,
+            reset_token.user_id,
+            reset_token.token_hash,
+            reset_token.expires_at,
+            reset_token.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<PasswordResetToken>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            PasswordResetToken,
+            r#"
+            SELECT id, user_id, token_hash, expires_at, used_at, created_at
+              FROM password_reset_tokens
+              WHERE token_hash = $1
+              FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn use_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for RefreshTokenRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        refresh_token: RefreshToken,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens
+            (id, user_id, family_id, token_hash, expires_at, client_id, scope, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            refresh_token.id,
+            refresh_token.user_id,
+            refresh_token.family_id,
+            refresh_token.token_hash,
+            refresh_token.expires_at,
+            refresh_token.client_id,
+            refresh_token.scope,
+            refresh_token.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_hash(
+        &self,
+        pool: PgPool,
+        token_hash: String,
+    ) -> Result<Option<RefreshToken>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            SELECT id, user_id, family_id, token_hash, expires_at, revoked_at, client_id, scope,
+                   created_at
+              FROM refresh_tokens
+              WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<RefreshToken>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            SELECT id, user_id, family_id, token_hash, expires_at, revoked_at, client_id, scope,
+                   created_at
+              FROM refresh_tokens
+              WHERE token_hash = $1
+              FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn mark_rotated(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        replaced_by: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+               SET revoked_at = NOW(),
+                   replaced_by = $1
+             WHERE id = $2
+            "#,
+            replaced_by,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_family(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        family_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+               SET revoked_at = NOW()
+             WHERE family_id = $1
+               AND revoked_at IS NULL
+            "#,
+            family_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    async fn revoke_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+               SET revoked_at = NOW()
+             WHERE user_id = $1
+               AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+}
+
+#[async_trait]
+impl EmailVerificationTokenRepository for EmailVerificationTokenRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        verification_token: EmailVerificationToken,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO email_verification_tokens
+            (id, user_id, email, token_hash, expires_at, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6)
+            "#,
+            verification_token.id,
+            verification_token.user_id,
+            verification_token.email,
+            verification_token.token_hash,
+            verification_token.expires_at,
+            verification_token.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<EmailVerificationToken>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            EmailVerificationToken,
+            r#"
+            SELECT id, user_id, email, token_hash, expires_at, used_at, created_at
+              FROM email_verification_tokens
+              WHERE token_hash = $1
+              FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn use_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE email_verification_tokens
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+}
+
+#[async_trait]
+impl LoginThrottleRepository for LoginThrottleRepo {
+    async fn find_locked_until(
+        &self,
+        pool: PgPool,
+        scope: String,
+        key: String,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT locked_until
+              FROM login_throttles
+              WHERE scope = $1
+                AND key = $2
+            "#,
+            scope,
+            key
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result.flatten())
+    }
+
+    async fn record_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: String,
+        key: String,
+        window_start: DateTime<Utc>,
+    ) -> Result<i32, sqlx::Error> {
+        let failed_attempts = sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_throttles
+            (scope, key, failed_attempts, last_failed_at)
+            VALUES
+            ($1, $2, 1, NOW())
+            ON CONFLICT (scope, key) DO UPDATE
+               SET failed_attempts = CASE
+                       WHEN login_throttles.last_failed_at < $3 THEN 1
+                       ELSE login_throttles.failed_attempts + 1
+                   END,
+                   last_failed_at = NOW()
+            RETURNING failed_attempts
+            "#,
+            scope,
+            key,
+            window_start
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(failed_attempts)
+    }
+
+    async fn lock(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: String,
+        key: String,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE login_throttles
+               SET locked_until = $1
+             WHERE scope = $2
+               AND key = $3
+            "#,
+            locked_until,
+            scope,
+            key
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_lockout(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        lockout: LoginLockout,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO login_lockouts
+            (id, scope, key, failed_attempts, locked_until, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6)
+            "#,
+            lockout.id,
+            lockout.scope,
+            lockout.key,
+            lockout.failed_attempts,
+            lockout.locked_until,
+            lockout.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: String,
+        key: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM login_throttles
+             WHERE scope = $1
+               AND key = $2
+            "#,
+            scope,
+            key
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MfaRepository for MfaRepo {
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserMfa>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserMfa,
+            r#"
+            SELECT user_id, totp_secret, enabled_at
+              FROM user_mfa
+              WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn save_secret(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        totp_secret: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            INSERT INTO user_mfa
+            (user_id, totp_secret)
+            VALUES
+            ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+               SET totp_secret = EXCLUDED.totp_secret,
+                   last_used_step = NULL,
+                   created_at = NOW()
+             WHERE user_mfa.enabled_at IS NULL
+            "#,
+            user_id,
+            totp_secret
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn enable(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        step: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_mfa
+               SET enabled_at = NOW(),
+                   last_used_step = $2
+             WHERE user_id = $1
+               AND enabled_at IS NULL
+            "#,
+            user_id,
+            step
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn use_step(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        step: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_mfa
+               SET last_used_step = $2
+             WHERE user_id = $1
+               AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        recovery_codes: Vec<MfaRecoveryCode>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM mfa_recovery_codes
+             WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        for recovery_code in recovery_codes {
+            sqlx::query!(
+                r#"
+                INSERT INTO mfa_recovery_codes
+                (id, user_id, code_hash, created_at)
+                VALUES
+                ($1, $2, $3, $4)
+                "#,
+                recovery_code.id,
+                recovery_code.user_id,
+                recovery_code.code_hash,
+                recovery_code.created_at
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        code_hash: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE mfa_recovery_codes
+               SET used_at = NOW()
+             WHERE id = (
+                   SELECT id
+                     FROM mfa_recovery_codes
+                    WHERE user_id = $1
+                      AND code_hash = $2
+                      AND used_at IS NULL
+                    LIMIT 1
+                   )
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl MfaChallengeRepository for MfaChallengeRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        challenge: MfaChallenge,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO mfa_challenges
+            (id, user_id, token_hash, failed_attempts, expires_at, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6)
+            "#,
+            challenge.id,
+            challenge.user_id,
+            challenge.token_hash,
+            challenge.failed_attempts,
+            challenge.expires_at,
+            challenge.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<MfaChallenge>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            MfaChallenge,
+            r#"
+            SELECT id, user_id, token_hash, failed_attempts, expires_at, used_at, created_at
+              FROM mfa_challenges
+              WHERE token_hash = $1
+              FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn record_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<i32, sqlx::Error> {
+        let failed_attempts = sqlx::query_scalar!(
+            r#"
+            UPDATE mfa_challenges
+               SET failed_attempts = failed_attempts + 1
+             WHERE id = $1
+            RETURNING failed_attempts
+            "#,
+            id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(failed_attempts)
+    }
+
+    async fn mark_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE mfa_challenges
+               SET used_at = NOW()
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for ApiKeyRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        api_key: ApiKey,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO api_keys
+            (id, user_id, name, prefix, secret_hash, scopes, expires_at, last_used_at, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            api_key.id,
+            api_key.user_id,
+            api_key.name,
+            api_key.prefix,
+            api_key.secret_hash,
+            &api_key.scopes,
+            api_key.expires_at,
+            api_key.last_used_at,
+            api_key.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<ApiKey>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, prefix, secret_hash, scopes, expires_at, last_used_at, created_at
+              FROM api_keys
+              WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_by_prefix(
+        &self,
+        pool: PgPool,
+        prefix: String,
+    ) -> Result<Option<ApiKey>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT k.id, k.user_id, k.name, k.prefix, k.secret_hash, k.scopes, k.expires_at,
+                   k.last_used_at, k.created_at
+              FROM api_keys k
+              JOIN users u ON k.user_id = u.id
+              WHERE k.prefix = $1
+                AND u.deleted_at IS NULL
+            "#,
+            prefix
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, prefix, secret_hash, scopes, expires_at, last_used_at, created_at
+              FROM api_keys
+              WHERE user_id = $1
+              ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn touch(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE api_keys
+               SET last_used_at = NOW()
+             WHERE id = $1
+               AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM api_keys
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl OAuthClientRepository for OAuthClientRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client: OAuthClient,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_clients
+            (client_id, client_secret_hash, name, user_id, scopes, grant_types, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            client.client_id,
+            client.client_secret_hash,
+            client.name,
+            client.user_id,
+            &client.scopes,
+            &client.grant_types,
+            client.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_client_id(
+        &self,
+        pool: PgPool,
+        client_id: String,
+    ) -> Result<Option<OAuthClient>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            OAuthClient,
+            r#"
+            SELECT c.client_id, c.client_secret_hash, c.name, c.user_id, c.scopes, c.grant_types,
+                   c.created_at
+              FROM oauth_clients c
+              JOIN users u ON c.user_id = u.id
+              WHERE c.client_id = $1
+                AND u.deleted_at IS NULL
+            "#,
+            client_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_all(&self, pool: PgPool) -> Result<Vec<OAuthClient>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            OAuthClient,
+            r#"
+            SELECT client_id, client_secret_hash, name, user_id, scopes, grant_types, created_at
+              FROM oauth_clients
+              ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM oauth_clients
+             WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl SessionRepository for SessionRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        session: UserSession,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_sessions
+            (id, user_id, device_id, user_agent, ip_address, created_at, last_seen_at, revoked_at)
+            VALUES
+            ($1, $2, (SELECT id FROM devices WHERE id = $3 AND user_id = $2::VARCHAR), $4, $5, $6, $7, $8)
+            "#,
+            session.id,
+            session.user_id,
+            session.device_id,
+            session.user_agent,
+            session.ip_address,
+            session.created_at,
+            session.last_seen_at,
+            session.revoked_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        pool: PgPool,
+        id: String,
+    ) -> Result<Option<UserSession>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserSession,
+            r#"
+            SELECT id, user_id, device_id, user_agent, ip_address, created_at, last_seen_at, revoked_at
+              FROM user_sessions
+              WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_active_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserSession,
+            r#"
+            SELECT id, user_id, device_id, user_agent, ip_address, created_at, last_seen_at, revoked_at
+              FROM user_sessions
+              WHERE user_id = $1
+                AND revoked_at IS NULL
+                AND last_seen_at > $2
+              ORDER BY last_seen_at DESC
+            "#,
+            user_id,
+            since
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn touch(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE user_sessions
+               SET last_seen_at = NOW()
+             WHERE id = $1
+               AND revoked_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_sessions
+               SET revoked_at = NOW()
+             WHERE id = $1
+               AND revoked_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn revoke_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE user_sessions
+               SET revoked_at = NOW()
+             WHERE user_id = $1
+               AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_revoked_since(
+        &self,
+        pool: PgPool,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT id
+              FROM user_sessions
+              WHERE revoked_at > $1
+            "#,
+            since
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl ImpersonationAuditRepository for ImpersonationAuditRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        entry: ImpersonationAudit,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO impersonation_audit
+            (id, actor_id, user_id, token_id, method, path, status, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            entry.id,
+            entry.actor_id,
+            entry.user_id,
+            entry.token_id,
+            entry.method,
+            entry.path,
+            entry.status,
+            entry.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuthEventRepository for AuthEventRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: AuthEvent,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO auth_events
+            (id, event_type, user_id, username, ip_address, user_agent, detail, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            event.id,
+            event.event_type,
+            event.user_id,
+            event.username,
+            event.ip_address,
+            event.user_agent,
+            event.detail,
+            event.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        pool: PgPool,
+        filter: AuthEventFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuthEvent>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            AuthEvent,
+            r#"
+            SELECT id, event_type, user_id, username, ip_address, user_agent, detail, created_at
+              FROM auth_events
+              WHERE ($1::VARCHAR IS NULL OR user_id = $1)
+                AND ($2::VARCHAR IS NULL OR event_type = $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+              ORDER BY created_at DESC, id
+              LIMIT $5 OFFSET $6
+            "#,
+            filter.user_id,
+            filter.event_type,
+            filter.from,
+            filter.to,
+            limit,
+            offset
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn count(&self, pool: PgPool, filter: AuthEventFilter) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+              FROM auth_events
+              WHERE ($1::VARCHAR IS NULL OR user_id = $1)
+                AND ($2::VARCHAR IS NULL OR event_type = $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+            "#,
+            filter.user_id,
+            filter.event_type,
+            filter.from,
+            filter.to
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl OidcLoginRepository for OidcLoginRepo {
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        login: OidcLogin,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oidc_logins
+            (id, state_hash, code_verifier, nonce, expires_at, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6)
+            "#,
+            login.id,
+            login.state_hash,
+            login.code_verifier,
+            login.nonce,
+            login.expires_at,
+            login.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_state_hash_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        state_hash: String,
+    ) -> Result<Option<OidcLogin>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            OidcLogin,
+            r#"
+            SELECT id, state_hash, code_verifier, nonce, expires_at, used_at, created_at
+              FROM oidc_logins
+              WHERE state_hash = $1
+              FOR UPDATE
+            "#,
+            state_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn mark_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE oidc_logins
+               SET used_at = NOW()
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserIdentityRepository for UserIdentityRepo {
+    async fn find_account(
+        &self,
+        pool: PgPool,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<UserAccount>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserAccount,
+            r#"
+            SELECT u.id AS user_id, u.username, u.email, u.email_verified_at
+              FROM users u
+              JOIN user_identities ui ON ui.user_id = u.id
+              WHERE ui.issuer = $1 AND ui.subject = $2
+                AND u.deleted_at IS NULL
+            "#,
+            issuer,
+            subject
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn find_accounts_by_verified_email(
+        &self,
+        pool: PgPool,
+        email: String,
+    ) -> Result<Vec<UserAccount>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserAccount,
+            r#"
+            SELECT id AS user_id, username, email, email_verified_at
+              FROM users
+              WHERE LOWER(email) = LOWER($1)
+                AND email_verified_at IS NOT NULL
+                AND deleted_at IS NULL
+              ORDER BY created_at
+            "#,
+            email
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        identity: UserIdentity,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_identities
+            (id, user_id, issuer, subject, email, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6)
+            "#,
+            identity.id,
+            identity.user_id,
+            identity.issuer,
+            identity.subject,
+            identity.email,
+            identity.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TokenRevocationRepository for TokenRevocationRepo {
+    async fn revoke_token(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        revoked_token: RevokedToken,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO revoked_tokens
+            (jti, user_id, expires_at)
+            VALUES
+            ($1, $2, $3)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+            revoked_token.jti,
+            revoked_token.user_id,
+            revoked_token.expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        revocation: UserTokenRevocation,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_token_revocations
+            (user_id, revoked_at)
+            VALUES
+            ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET revoked_at = EXCLUDED.revoked_at
+            "#,
+            revocation.user_id,
+            revocation.revoked_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_active_revoked_tokens(&self, pool: PgPool) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query_scalar!(
+            r#"
+            SELECT jti
+              FROM revoked_tokens
+             WHERE expires_at > NOW()
+            "#
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn find_user_revocations_since(
+        &self,
+        pool: PgPool,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UserTokenRevocation>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            UserTokenRevocation,
+            r#"
+            SELECT user_id, revoked_at
+              FROM user_token_revocations
+             WHERE revoked_at > $1
+            "#,
+            since
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl RoleRepository for RoleRepo {
+    async fn find_role_names_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query_scalar!(
+            r#"
+            SELECT r.name
+              FROM user_roles ur
+              JOIN roles r ON ur.role_id = r.id
+             WHERE ur.user_id = $1
+             ORDER BY r.name
+            "#,
+            user_id
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn find_role_permissions(
+        &self,
+        pool: PgPool,
+    ) -> Result<Vec<RolePermission>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            RolePermission,
+            r#"
+            SELECT r.name AS role, p.name AS permission
+              FROM role_permissions rp
+              JOIN roles r ON rp.role_id = r.id
+              JOIN permissions p ON rp.permission_id = p.id
+            "#
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn assign_role(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        role_name: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_roles
+            (user_id, role_id)
+            SELECT $1, id FROM roles WHERE name = $2
+            ON CONFLICT (user_id, role_id) DO NOTHING
+            "#,
+            user_id,
+            role_name
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
 }
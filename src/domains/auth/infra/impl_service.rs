@@ -1,59 +1,216 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+use validator::ValidationErrors;
 
 use crate::{
     common::{
-        error::AppError,
-        hash_util,
-        jwt::{make_jwt_token, AuthBody, AuthPayload},
+        config::Config,
+        dto::PageDto,
+        error::{AppError, OAuthErrorCode},
+        hash_util::{self, PasswordParams},
+        jwt::{encode_claims, make_validation, AuthBody, AuthPayload, Claims, KEYS},
+        mail::{Mail, MailSender},
+        password_policy::PasswordPolicy,
+        rbac::{ensure_owner_or_admin, Permissions, ADMIN_ROLE, DEFAULT_ROLE},
+        totp,
     },
     domains::auth::{
-        domain::{model::UserAuth, repository::UserAuthRepository, service::AuthServiceTrait},
-        dto::auth_dto::AuthUserDto,
-        infra::impl_repository::UserAuthRepo,
+        domain::{
+            model::{
+                ApiKey, AuthEvent, AuthEventType, EmailVerificationToken, ImpersonationAudit,
+                LoginLockout, MfaChallenge, MfaRecoveryCode, OAuthClient, OidcLogin,
+                PasswordResetToken, RefreshToken, RevokedToken, UserAccount, UserAuth,
+                UserIdentity, UserMfa, UserSession, UserTokenRevocation,
+            },
+            repository::{
+                ApiKeyRepository, AuthEventRepository, EmailVerificationTokenRepository,
+                ImpersonationAuditRepository, LoginThrottleRepository, MfaChallengeRepository,
+                MfaRepository, OAuthClientRepository, OidcLoginRepository,
+                PasswordResetTokenRepository, RefreshTokenRepository, RoleRepository,
+                SessionRepository, TokenRevocationRepository, UserAuthRepository,
+                UserIdentityRepository,
+            },
+            service::AuthServiceTrait,
+        },
+        dto::auth_dto::{
+            ApiKeyDto, AuthEventDto, AuthEventQueryDto, AuthUserDto, ChangePasswordDto,
+            ClientCredentials, CreateApiKeyDto, CreateOAuthClientDto, CreatedApiKeyDto,
+            CreatedOAuthClientDto, ForgotPasswordDto, IntrospectionDto, LoginClient,
+            LoginResponseDto, LogoutDto, MfaChallengeDto, MfaLoginDto, OAuthClientDto,
+            OAuthTokenDto, OAuthTokenRefDto, OAuthTokenRequestDto, OidcCallbackDto,
+            RecoveryCodesDto, RefreshTokenDto, RegisteredUserDto, ResetPasswordDto, SessionDto,
+            TotpCodeDto, TotpEnrollmentDto, VerifyEmailDto,
+        },
+        infra::{
+            impl_repository::{
+                ApiKeyRepo, AuthEventRepo, EmailVerificationTokenRepo, ImpersonationAuditRepo,
+                LoginThrottleRepo, MfaChallengeRepo, MfaRepo, OAuthClientRepo, OidcLoginRepo,
+                PasswordResetTokenRepo, RefreshTokenRepo, RoleRepo, SessionRepo,
+                TokenRevocationRepo, UserAuthRepo, UserIdentityRepo,
+            },
+            login_throttle::{ThrottleAction, ThrottlePolicy, IP_SCOPE, USERNAME_SCOPE},
+            oauth::{
+                grant_scope, split_scope, CLIENT_CREDENTIALS_GRANT, GRANT_TYPES, PASSWORD_GRANT,
+                REFRESH_TOKEN_GRANT,
+            },
+            oidc::{self, ExternalIdentity, OidcProvider},
+            permission_cache::PermissionCache,
+            revocation_cache::RevocationCache,
+        },
     },
 };
 
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Number of recovery codes issued when MFA is enabled.
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Maximum number of characters of a request path stored in the impersonation audit trail.
+const MAX_AUDIT_PATH_CHARS: usize = 2048;
+
+/// Maximum number of characters of a username stored in the auth event log.
+const MAX_EVENT_USERNAME_CHARS: usize = 64;
+
+/// Number of auth events per page if the query does not set `page_size`.
+const DEFAULT_EVENT_PAGE_SIZE: i64 = 20;
+
+/// Maximum number of characters of a username, as stored in the users table.
+const MAX_USERNAME_CHARS: usize = 64;
+
+/// Number of usernames tried when provisioning a user for an external identity.
+const MAX_PROVISION_ATTEMPTS: usize = 3;
+
+/// OAuth2 client a token is issued to, with the scope granted to it.
+struct ClientGrant {
+    client_id: String,
+    scope: String,
+}
 
 /// Service for handling user authentication
 /// and authorization logic.
 #[derive(Clone)]
 pub struct AuthService {
+    config: Config,
     pool: PgPool,
     repo: Arc<dyn UserAuthRepository + Send + Sync>,
+    refresh_token_repo: Arc<dyn RefreshTokenRepository + Send + Sync>,
+    reset_token_repo: Arc<dyn PasswordResetTokenRepository + Send + Sync>,
+    verification_token_repo: Arc<dyn EmailVerificationTokenRepository + Send + Sync>,
+    throttle_repo: Arc<dyn LoginThrottleRepository + Send + Sync>,
+    mfa_repo: Arc<dyn MfaRepository + Send + Sync>,
+    mfa_challenge_repo: Arc<dyn MfaChallengeRepository + Send + Sync>,
+    api_key_repo: Arc<dyn ApiKeyRepository + Send + Sync>,
+    oauth_client_repo: Arc<dyn OAuthClientRepository + Send + Sync>,
+    session_repo: Arc<dyn SessionRepository + Send + Sync>,
+    impersonation_audit_repo: Arc<dyn ImpersonationAuditRepository + Send + Sync>,
+    auth_event_repo: Arc<dyn AuthEventRepository + Send + Sync>,
+    oidc_login_repo: Arc<dyn OidcLoginRepository + Send + Sync>,
+    identity_repo: Arc<dyn UserIdentityRepository + Send + Sync>,
+    oidc_provider: Option<Arc<OidcProvider>>,
+    revocation_repo: Arc<dyn TokenRevocationRepository + Send + Sync>,
+    revocation_cache: Arc<RevocationCache>,
+    role_repo: Arc<dyn RoleRepository + Send + Sync>,
+    permission_cache: Arc<PermissionCache>,
+    mail_sender: Arc<dyn MailSender>,
+    password_policy: Arc<PasswordPolicy>,
 }
 
 /// Implementation of the AuthService
 #[async_trait::async_trait]
 impl AuthServiceTrait for AuthService {
     /// constructor for the service.
-    fn create_service(pool: PgPool) -> Arc<dyn AuthServiceTrait> {
+    fn create_service(
+        config: Config,
+        pool: PgPool,
+        mail_sender: Arc<dyn MailSender>,
+        password_policy: PasswordPolicy,
+    ) -> Arc<dyn AuthServiceTrait> {
+        let revocation_cache = Arc::new(RevocationCache::new(std::time::Duration::from_secs(
+            config.revocation_cache_ttl_seconds,
+        )));
+        let permission_cache = Arc::new(PermissionCache::new(std::time::Duration::from_secs(
+            config.permission_cache_ttl_seconds,
+        )));
+
+        let oidc_provider = OidcProvider::from_config(&config).map(Arc::new);
+
         Arc::new(Self {
+            config,
             pool,
             repo: Arc::new(UserAuthRepo {}),
+            refresh_token_repo: Arc::new(RefreshTokenRepo {}),
+            reset_token_repo: Arc::new(PasswordResetTokenRepo {}),
+            verification_token_repo: Arc::new(EmailVerificationTokenRepo {}),
+            throttle_repo: Arc::new(LoginThrottleRepo {}),
+            mfa_repo: Arc::new(MfaRepo {}),
+            mfa_challenge_repo: Arc::new(MfaChallengeRepo {}),
+            api_key_repo: Arc::new(ApiKeyRepo {}),
+            oauth_client_repo: Arc::new(OAuthClientRepo {}),
+            session_repo: Arc::new(SessionRepo {}),
+            impersonation_audit_repo: Arc::new(ImpersonationAuditRepo {}),
+            auth_event_repo: Arc::new(AuthEventRepo {}),
+            oidc_login_repo: Arc::new(OidcLoginRepo {}),
+            identity_repo: Arc::new(UserIdentityRepo {}),
+            oidc_provider,
+            revocation_repo: Arc::new(TokenRevocationRepo {}),
+            revocation_cache,
+            role_repo: Arc::new(RoleRepo {}),
+            permission_cache,
+            mail_sender,
+            password_policy: Arc::new(password_policy),
         })
     }
 
-    /// It hashes the password and stores it in the database.
-    async fn create_user_auth(&self, auth_user: AuthUserDto) -> Result<(), AppError> {
-        let mut tx = self.pool.begin().await?;
-
-        let password_hash =
-            hash_util::hash_password(&auth_user.password).map_err(|_| AppError::InternalError)?;
+    /// Registers a new user: creates the user and its hashed credentials in a single
+    /// transaction and grants the default role, then mails an email verification token.
+    /// A username that is already taken is rejected with a validation error.
+    async fn create_user_auth(
+        &self,
+        auth_user: AuthUserDto,
+    ) -> Result<RegisteredUserDto, AppError> {
+        self.check_password_policy("password", &auth_user.password, &auth_user.username)?;
+        let password_hash = self.hash_password(&auth_user.password)?;
 
+        let user_id = Uuid::new_v4().to_string();
         let user_auth = UserAuth {
-            user_id: auth_user.user_id,
+            user_id: user_id.clone(),
             password_hash,
         };
 
-        match self.repo.create(&mut tx, user_auth).await {
+        let mut tx = self.pool.begin().await?;
+        let result = self
+            .register(&mut tx, &user_id, &auth_user, user_auth)
+            .await;
+
+        match result {
             Ok(()) => {
                 tx.commit().await?;
-                Ok(())
+
+                // The user exists at this point; a failed mail must not fail the registration.
+                if let Err(err) = self
+                    .send_email_verification(user_id.clone(), auth_user.email.clone())
+                    .await
+                {
+                    tracing::error!("Error sending email verification: {err}");
+                }
+
+                Ok(RegisteredUserDto {
+                    id: user_id,
+                    username: auth_user.username,
+                    email: auth_user.email,
+                })
             }
             Err(err) => {
-                tracing::error!("Error creating user auth: {err}");
                 tx.rollback().await?;
+                if err
+                    .as_database_error()
+                    .is_some_and(|db_err| db_err.is_unique_violation())
+                {
+                    return Err(AppError::ValidationError("Username already exists".into()));
+                }
+                tracing::error!("Error registering user: {err}");
                 Err(AppError::DatabaseError(err))
             }
         }
@@ -61,27 +218,2426 @@ impl AuthServiceTrait for AuthService {
 
     /// Authenticates a user by checking the provided credentials
     /// against the stored credentials in the database.
-    /// If the credentials are valid, it generates a JWT token for the user.
-    /// If the credentials are invalid, it returns an error.
-    async fn login_user(&self, auth_payload: AuthPayload) -> Result<AuthBody, AppError> {
-        if auth_payload.client_id.is_empty() || auth_payload.client_secret.is_empty() {
+    /// If the credentials are valid, it generates a JWT token for the user
+    /// together with a refresh token starting a new token family.
+    /// Unknown users and wrong passwords are rejected with the same error, and failed
+    /// attempts are throttled per username and per client IP.
+    /// Users with MFA enabled receive a short-lived MFA challenge instead, which is
+    /// exchanged for the tokens at `login_mfa`.
+    async fn login_user(
+        &self,
+        auth_payload: AuthPayload,
+        client: LoginClient,
+    ) -> Result<LoginResponseDto, AppError> {
+        let user_auth = self
+            .authenticate_password(
+                &auth_payload.client_id,
+                &auth_payload.client_secret,
+                &client,
+            )
+            .await?;
+
+        if self.find_enabled_mfa(&user_auth.user_id).await?.is_some() {
+            let challenge = self.create_mfa_challenge(&user_auth.user_id).await?;
+            return Ok(LoginResponseDto::MfaRequired(challenge));
+        }
+
+        let auth_body = self
+            .issue_tokens(&user_auth.user_id, &auth_payload.client_id, client)
+            .await?;
+        Ok(LoginResponseDto::Authenticated(auth_body))
+    }
+
+    /// Completes a login of a user with MFA enabled.
+    /// The challenge must be unused and not expired, and the code must be a TOTP code of
+    /// a time step that has not been used yet, or an unused recovery code.
+    /// Wrong codes count as failed logins; after `mfa_max_attempts` wrong codes the
+    /// challenge is given up and the user has to log in with the password again.
+    async fn login_mfa(
+        &self,
+        payload: MfaLoginDto,
+        client: LoginClient,
+    ) -> Result<AuthBody, AppError> {
+        if payload.mfa_token.is_empty() || payload.code.is_empty() {
             return Err(AppError::MissingCredentials);
         }
 
-        let user_auth = self
+        let mut tx = self.pool.begin().await?;
+
+        let token_hash = hash_util::hash_token(&payload.mfa_token);
+        let stored = self
+            .mfa_challenge_repo
+            .find_by_hash_for_update(&mut tx, token_hash)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving MFA challenge: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        let Some(challenge) = stored.filter(|c| c.used_at.is_none() && c.expires_at > Utc::now())
+        else {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        };
+
+        let account = self
+            .repo
+            .find_account_by_user_id(self.pool.clone(), challenge.user_id.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .ok_or(AppError::InvalidToken)?;
+        let throttle_keys = self.throttle_keys(&account.username, client.ip);
+        self.check_throttle(
+            &throttle_keys,
+            Some(&challenge.user_id),
+            &account.username,
+            &client,
+        )
+        .await?;
+
+        let mfa = self
+            .find_enabled_mfa(&challenge.user_id)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        if !self.use_mfa_code(&mut tx, &mfa, &payload.code).await? {
+            let failed_attempts = self
+                .mfa_challenge_repo
+                .record_failure(&mut tx, challenge.id.clone())
+                .await?;
+            if failed_attempts >= self.config.mfa_max_attempts {
+                tracing::warn!(
+                    "MFA challenge of user {} given up after {failed_attempts} wrong codes",
+                    challenge.user_id
+                );
+                self.mfa_challenge_repo
+                    .mark_used(&mut tx, challenge.id)
+                    .await?;
+            }
+            tx.commit().await?;
+
+            self.record_failed_login(
+                &throttle_keys,
+                Some(&challenge.user_id),
+                &account.username,
+                &client,
+                "wrong_mfa_code",
+            )
+            .await?;
+            return Err(AppError::WrongCredentials);
+        }
+
+        self.mfa_challenge_repo
+            .mark_used(&mut tx, challenge.id)
+            .await?;
+        tx.commit().await?;
+
+        self.issue_tokens(&challenge.user_id, &account.username, client)
+            .await
+    }
+
+    /// Starts a login with the OpenID Connect provider.
+    /// The state, the PKCE code verifier and the nonce of the login are stored until the
+    /// provider redirects back; only the hash of the state is persisted.
+    async fn start_oidc_login(&self) -> Result<String, AppError> {
+        let provider = self.oidc_provider()?;
+
+        let state = hash_util::generate_token();
+        let now = Utc::now();
+        let login = OidcLogin {
+            id: Uuid::new_v4().to_string(),
+            state_hash: hash_util::hash_token(&state),
+            code_verifier: hash_util::generate_token(),
+            nonce: hash_util::generate_token(),
+            expires_at: now + Duration::seconds(self.config.oidc_login_ttl_seconds),
+            used_at: None,
+            created_at: now,
+        };
+        let code_challenge = oidc::code_challenge(&login.code_verifier);
+        let url = provider
+            .authorization_url(&state, &login.nonce, &code_challenge)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error starting OpenID Connect login: {err}");
+                AppError::IdentityProvider
+            })?;
+
+        let mut tx = self.pool.begin().await?;
+        self.oidc_login_repo
+            .create(&mut tx, login)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating OpenID Connect login: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        Ok(url)
+    }
+
+    /// Completes a login with the OpenID Connect provider.
+    /// The state must belong to an unused login that has not expired; the login is used up
+    /// before the code is exchanged, so a replayed callback is rejected.
+    /// The external identity is mapped to the user linked to it. An identity that is not
+    /// linked yet is linked to the user with the same verified email address, if the
+    /// provider verified it too, or otherwise to a new user if auto-provisioning is enabled.
+    /// Users with MFA enabled receive an MFA challenge like after a password login.
+    async fn complete_oidc_login(
+        &self,
+        payload: OidcCallbackDto,
+        client: LoginClient,
+    ) -> Result<LoginResponseDto, AppError> {
+        let provider = self.oidc_provider()?;
+
+        if let Some(error) = payload.error {
+            tracing::warn!(
+                "OpenID Connect login rejected by the provider: {error} {}",
+                payload.error_description.unwrap_or_default()
+            );
+            self.record_auth_event(
+                AuthEventType::LoginFailure,
+                None,
+                None,
+                &client,
+                Some("oidc_rejected"),
+            )
+            .await;
+            return Err(AppError::WrongCredentials);
+        }
+        let (Some(code), Some(state)) = (
+            payload.code.filter(|code| !code.is_empty()),
+            payload.state.filter(|state| !state.is_empty()),
+        ) else {
+            return Err(AppError::MissingCredentials);
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let stored = self
+            .oidc_login_repo
+            .find_by_state_hash_for_update(&mut tx, hash_util::hash_token(&state))
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving OpenID Connect login: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        let Some(login) = stored.filter(|l| l.used_at.is_none() && l.expires_at > Utc::now())
+        else {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        };
+        self.oidc_login_repo
+            .mark_used(&mut tx, login.id.clone())
+            .await?;
+        tx.commit().await?;
+
+        let identity = provider
+            .exchange_code(&code, &login.code_verifier, &login.nonce)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error completing OpenID Connect login: {err}");
+                AppError::IdentityProvider
+            })?;
+
+        let Some(account) = self.find_or_link_identity(&identity).await? else {
+            tracing::warn!(
+                "No user is linked to subject {} of {}",
+                identity.subject,
+                identity.issuer
+            );
+            self.record_auth_event(
+                AuthEventType::LoginFailure,
+                None,
+                identity.email.as_deref(),
+                &client,
+                Some("identity_not_linked"),
+            )
+            .await;
+            return Err(AppError::IdentityNotLinked);
+        };
+
+        // Users provisioned with an email address the provider did not verify must verify
+        // it before they can log in, like users logging in with a password.
+        if self.config.require_verified_email && account.email_verified_at.is_none() {
+            self.record_auth_event(
+                AuthEventType::LoginFailure,
+                Some(&account.user_id),
+                Some(&account.username),
+                &client,
+                Some("email_not_verified"),
+            )
+            .await;
+            return Err(AppError::EmailNotVerified);
+        }
+
+        if self.find_enabled_mfa(&account.user_id).await?.is_some() {
+            let challenge = self.create_mfa_challenge(&account.user_id).await?;
+            return Ok(LoginResponseDto::MfaRequired(challenge));
+        }
+
+        let auth_body = self
+            .issue_tokens(&account.user_id, &account.username, client)
+            .await?;
+        Ok(LoginResponseDto::Authenticated(auth_body))
+    }
+
+    /// Generates a new TOTP secret for the user and returns it with its `otpauth://` URI.
+    /// MFA is not enforced until the enrollment is confirmed, so a lost enrollment can
+    /// simply be started again. Users with MFA enabled cannot enroll again.
+    async fn enroll_totp(&self, claims: Claims) -> Result<TotpEnrollmentDto, AppError> {
+        let account = self
             .repo
-            .find_by_user_name(self.pool.clone(), auth_payload.client_id.clone())
+            .find_account_by_user_id(self.pool.clone(), claims.sub.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .ok_or(AppError::UserNotFound)?;
+
+        let secret = totp::generate_secret();
+
+        let mut tx = self.pool.begin().await?;
+        let saved = self
+            .mfa_repo
+            .save_secret(&mut tx, claims.sub, secret.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error saving TOTP secret: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        if !saved {
+            tx.rollback().await?;
+            return Err(AppError::ValidationError("MFA is already enabled".into()));
+        }
+        tx.commit().await?;
+
+        Ok(TotpEnrollmentDto {
+            otpauth_uri: totp::otpauth_uri(&secret, &self.config.totp_issuer, &account.username),
+            secret,
+        })
+    }
+
+    /// Enables MFA if the code matches the pending enrollment, and replaces the
+    /// recovery codes of the user. Only the hashes of the codes are stored, so the
+    /// returned codes cannot be shown again.
+    async fn confirm_totp(
+        &self,
+        claims: Claims,
+        payload: TotpCodeDto,
+    ) -> Result<RecoveryCodesDto, AppError> {
+        let mfa = self
+            .mfa_repo
+            .find_by_user_id(self.pool.clone(), claims.sub.clone())
             .await
             .map_err(AppError::DatabaseError)?;
 
-        let user_auth = user_auth.ok_or(AppError::UserNotFound)?;
+        let Some(mfa) = mfa.filter(|m| m.enabled_at.is_none()) else {
+            return Err(AppError::ValidationError(
+                "No pending TOTP enrollment".into(),
+            ));
+        };
+
+        let Some(step) = totp::verify_code(
+            &mfa.totp_secret,
+            payload.code.trim(),
+            Utc::now().timestamp(),
+        ) else {
+            return Err(AppError::ValidationError("Invalid TOTP code".into()));
+        };
+
+        let now = Utc::now();
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| hash_util::generate_recovery_code())
+            .collect();
+        let stored_codes = recovery_codes
+            .iter()
+            .map(|code| MfaRecoveryCode {
+                id: Uuid::new_v4().to_string(),
+                user_id: claims.sub.clone(),
+                code_hash: hash_util::hash_token(&hash_util::normalize_recovery_code(code)),
+                created_at: now,
+            })
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        let enabled = self
+            .mfa_repo
+            .enable(&mut tx, claims.sub.clone(), step)
+            .await?;
+        if !enabled {
+            tx.rollback().await?;
+            return Err(AppError::ValidationError(
+                "No pending TOTP enrollment".into(),
+            ));
+        }
+        self.mfa_repo
+            .replace_recovery_codes(&mut tx, claims.sub, stored_codes)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error storing recovery codes: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        Ok(RecoveryCodesDto { recovery_codes })
+    }
+
+    /// Exchanges a refresh token for a new token pair.
+    /// The presented token is invalidated and replaced by a new one of the same family.
+    /// If a token that was already rotated is presented again, the token has most
+    /// likely been stolen, so every token of its family is revoked.
+    /// Refresh tokens issued to an OAuth2 client are only accepted at the token endpoint.
+    async fn refresh_token(
+        &self,
+        payload: RefreshTokenDto,
+        client: LoginClient,
+    ) -> Result<AuthBody, AppError> {
+        if payload.refresh_token.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let (stored, refresh_token) = self
+            .rotate_refresh_token(&payload.refresh_token, None)
+            .await?;
+        let token = self
+            .sign_access_token(&stored.user_id, None, Some(&stored.family_id))
+            .await?;
+        self.record_auth_event(
+            AuthEventType::TokenRefresh,
+            Some(&stored.user_id),
+            None,
+            &client,
+            None,
+        )
+        .await;
 
-        if !hash_util::verify_password(&user_auth.password_hash, &auth_payload.client_secret) {
+        Ok(AuthBody::new(token, self.config.access_token_ttl_seconds)
+            .with_refresh_token(refresh_token))
+    }
+
+    /// Checks the current password and replaces it with the new one.
+    /// Existing sessions stay valid; use `revoke_all_sessions` to end them.
+    async fn change_password(
+        &self,
+        claims: Claims,
+        payload: ChangePasswordDto,
+        client: LoginClient,
+    ) -> Result<(), AppError> {
+        let user_auth = self
+            .repo
+            .find_by_user_id(self.pool.clone(), claims.sub.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .ok_or(AppError::UserNotFound)?;
+
+        if !hash_util::verify_password(&user_auth.password_hash, &payload.current_password) {
             return Err(AppError::WrongCredentials);
         }
 
-        let token = make_jwt_token(&user_auth.user_id).map_err(|_| AppError::InternalError)?;
+        let username = self.find_username(&claims.sub).await?.unwrap_or_default();
+        self.check_password_policy("new_password", &payload.new_password, &username)?;
+        let password_hash = self.hash_password(&payload.new_password)?;
+
+        let mut tx = self.pool.begin().await?;
+        self.repo
+            .update_password(&mut tx, claims.sub.clone(), password_hash)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error updating password: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        self.record_auth_event(
+            AuthEventType::PasswordChange,
+            Some(&claims.sub),
+            Some(&username),
+            &client,
+            None,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Creates a password reset token and mails it to the user.
+    /// Unknown usernames and users without a password (such as users provisioned through
+    /// OpenID Connect) are silently ignored and mail failures are only logged, so that the
+    /// response does not reveal which accounts exist.
+    async fn request_password_reset(&self, payload: ForgotPasswordDto) -> Result<(), AppError> {
+        let account = self
+            .repo
+            .find_account_by_user_name(self.pool.clone(), payload.username)
+            .await
+            .map_err(AppError::DatabaseError)?;
+
+        let Some(account) = account else {
+            return Ok(());
+        };
+        let has_password = self
+            .repo
+            .find_by_user_id(self.pool.clone(), account.user_id.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .is_some();
+        if !has_password {
+            return Ok(());
+        }
+
+        let token = hash_util::generate_token();
+        let now = Utc::now();
+        let reset_token = PasswordResetToken {
+            id: Uuid::new_v4().to_string(),
+            user_id: account.user_id,
+            token_hash: hash_util::hash_token(&token),
+            expires_at: now + Duration::seconds(self.config.password_reset_ttl_seconds),
+            used_at: None,
+            created_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.reset_token_repo
+            .create(&mut tx, reset_token)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating password reset token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        let mail = Mail {
+            to: account.email,
+            subject: "Reset your password".into(),
+            body: format!(
+                "Hello {},\n\n\
+                 Use the following token to reset your password.\n\
+                 It expires in {} minutes and can only be used once.\n\n\
+                 Reset token: {}\n\n\
+                 If you did not request a password reset, ignore this message.\n",
+                account.username,
+                self.config.password_reset_ttl_seconds / 60,
+                token
+            ),
+        };
+        if let Err(err) = self.mail_sender.send(mail).await {
+            tracing::error!("Error sending password reset mail: {err}");
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the password if the reset token is valid, unused and not expired.
+    /// Every outstanding reset token of the user is used up, and all sessions are
+    /// revoked since the account may have been compromised.
+    async fn reset_password(
+        &self,
+        payload: ResetPasswordDto,
+        client: LoginClient,
+    ) -> Result<(), AppError> {
+        if payload.token.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let token_hash = hash_util::hash_token(&payload.token);
+        let stored = self
+            .reset_token_repo
+            .find_by_hash_for_update(&mut tx, token_hash)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving password reset token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        let Some(stored) = stored.filter(|t| t.used_at.is_none() && t.expires_at > Utc::now())
+        else {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        };
+
+        let username = self
+            .find_username(&stored.user_id)
+            .await?
+            .unwrap_or_default();
+        self.check_password_policy("new_password", &payload.new_password, &username)?;
+        let password_hash = self.hash_password(&payload.new_password)?;
+
+        self.repo
+            .update_password(&mut tx, stored.user_id.clone(), password_hash)
+            .await?;
+        self.reset_token_repo
+            .use_all_for_user(&mut tx, stored.user_id.clone())
+            .await?;
+        let revoked_at = self.revoke_sessions(&mut tx, &stored.user_id).await?;
+
+        tx.commit().await?;
+        self.revocation_cache
+            .insert_user_cutoff(stored.user_id.clone(), revoked_at.timestamp());
+
+        self.record_auth_event(
+            AuthEventType::PasswordReset,
+            Some(&stored.user_id),
+            Some(&username),
+            &client,
+            None,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Creates an email verification token for the address and mails it there.
+    async fn send_email_verification(
+        &self,
+        user_id: String,
+        email: String,
+    ) -> Result<(), AppError> {
+        let token = hash_util::generate_token();
+        let now = Utc::now();
+        let verification_token = EmailVerificationToken {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            email: email.clone(),
+            token_hash: hash_util::hash_token(&token),
+            expires_at: now + Duration::seconds(self.config.email_verification_ttl_seconds),
+            used_at: None,
+            created_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.verification_token_repo
+            .create(&mut tx, verification_token)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating email verification token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        let mail = Mail {
+            to: email,
+            subject: "Verify your email address".into(),
+            body: format!(
+                "Hello,\n\n\
+                 Use the following token to verify your email address.\n\
+                 It expires in {} hours and can only be used once.\n\n\
+                 Verification token: {}\n\n\
+                 If you did not create an account, ignore this message.\n",
+                self.config.email_verification_ttl_seconds / 3600,
+                token
+            ),
+        };
+        self.mail_sender.send(mail).await.map_err(|err| {
+            tracing::error!("Error sending email verification mail: {err}");
+            AppError::InternalError
+        })?;
+
+        Ok(())
+    }
+
+    /// Marks the address as verified if the token is valid, unused and not expired,
+    /// and the user still has the address the token was sent to.
+    /// Every outstanding verification token of the user is used up.
+    async fn verify_email(&self, payload: VerifyEmailDto) -> Result<(), AppError> {
+        if payload.token.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let token_hash = hash_util::hash_token(&payload.token);
+        let stored = self
+            .verification_token_repo
+            .find_by_hash_for_update(&mut tx, token_hash)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving email verification token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        let Some(stored) = stored.filter(|t| t.used_at.is_none() && t.expires_at > Utc::now())
+        else {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        };
+
+        let verified = self
+            .repo
+            .mark_email_verified(&mut tx, stored.user_id.clone(), stored.email)
+            .await?;
+        if !verified {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        }
+
+        self.verification_token_repo
+            .use_all_for_user(&mut tx, stored.user_id)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Creates an API key and returns it in clear; only the hash of its secret is stored.
+    /// The key can never grant more than its creator, so every scope must be one of the
+    /// creator's permissions. Impersonation tokens cannot create keys, since the key would
+    /// outlive the impersonation and its audit trail.
+    async fn create_api_key(
+        &self,
+        claims: Claims,
+        permissions: Permissions,
+        payload: CreateApiKeyDto,
+    ) -> Result<CreatedApiKeyDto, AppError> {
+        if claims.act.is_some() {
+            tracing::warn!(
+                "User {} tried to create an API key while impersonating",
+                claims.actor_id()
+            );
+            return Err(AppError::Forbidden);
+        }
+        if let Some(scope) = payload
+            .scopes
+            .iter()
+            .find(|scope| !permissions.contains(scope))
+        {
+            return Err(AppError::ValidationError(format!(
+                "Scope {scope} is not granted to the user"
+            )));
+        }
+
+        let now = Utc::now();
+        if payload
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return Err(AppError::ValidationError(
+                "Expiry must be in the future".into(),
+            ));
+        }
+
+        let mut scopes = payload.scopes;
+        scopes.sort();
+        scopes.dedup();
+
+        let prefix = hash_util::generate_api_key_prefix();
+        let secret = hash_util::generate_token();
+        let api_key = ApiKey {
+            id: Uuid::new_v4().to_string(),
+            user_id: claims.sub,
+            name: payload.name,
+            prefix: prefix.clone(),
+            secret_hash: hash_util::hash_token(&secret),
+            scopes,
+            expires_at: payload.expires_at,
+            last_used_at: None,
+            created_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.api_key_repo
+            .create(&mut tx, api_key.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating API key: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        Ok(CreatedApiKeyDto {
+            key: format!("{prefix}.{secret}"),
+            api_key: ApiKeyDto::from(api_key),
+        })
+    }
+
+    /// Lists the API keys of the authenticated user, newest first.
+    async fn list_api_keys(&self, claims: Claims) -> Result<Vec<ApiKeyDto>, AppError> {
+        let api_keys = self
+            .api_key_repo
+            .find_by_user_id(self.pool.clone(), claims.sub)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving API keys: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(api_keys.into_iter().map(ApiKeyDto::from).collect())
+    }
+
+    /// Deletes an API key, which is rejected from the next request on.
+    async fn delete_api_key(&self, claims: Claims, id: String) -> Result<(), AppError> {
+        let api_key = self
+            .api_key_repo
+            .find_by_id(self.pool.clone(), id.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .ok_or_else(|| AppError::NotFound("API key not found".into()))?;
+        ensure_owner_or_admin(&claims, &api_key.user_id)?;
+
+        let mut tx = self.pool.begin().await?;
+        self.api_key_repo.delete(&mut tx, id).await.map_err(|err| {
+            tracing::error!("Error deleting API key: {err}");
+            AppError::DatabaseError(err)
+        })?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Looks the key up by its prefix and checks the secret and the expiry.
+    /// The claims carry the user's current roles, like a freshly issued access token,
+    /// while the permissions are limited to the scopes of the key.
+    async fn authenticate_api_key(&self, api_key: &str) -> Result<(Claims, Permissions), AppError> {
+        let Some((prefix, secret)) = api_key.split_once('.') else {
+            return Err(AppError::InvalidToken);
+        };
+
+        let stored = self
+            .api_key_repo
+            .find_by_prefix(self.pool.clone(), prefix.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving API key: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        let now = Utc::now();
+        let Some(stored) = stored.filter(|k| {
+            k.secret_hash == hash_util::hash_token(secret)
+                && k.expires_at.is_none_or(|expires_at| expires_at > now)
+        }) else {
+            return Err(AppError::InvalidToken);
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.api_key_repo.touch(&mut tx, stored.id).await?;
+        tx.commit().await?;
+
+        let roles = self.find_roles(&stored.user_id).await?;
+        let permissions = self
+            .resolve_permissions(&roles)
+            .await?
+            .restrict(&stored.scopes);
+
+        Ok((
+            Claims::new(&stored.user_id, roles, &self.config),
+            permissions,
+        ))
+    }
+
+    /// Registers an OAuth2 client and returns its secret in clear; only the hash is stored.
+    /// The client can never grant more than the user registering it, so every scope must
+    /// be one of the user's permissions. Like API keys, clients cannot be registered with
+    /// an impersonation token.
+    async fn create_oauth_client(
+        &self,
+        claims: Claims,
+        permissions: Permissions,
+        payload: CreateOAuthClientDto,
+    ) -> Result<CreatedOAuthClientDto, AppError> {
+        if claims.act.is_some() {
+            tracing::warn!(
+                "User {} tried to register an OAuth2 client while impersonating",
+                claims.actor_id()
+            );
+            return Err(AppError::Forbidden);
+        }
+        if let Some(grant_type) = payload
+            .grant_types
+            .iter()
+            .find(|grant_type| !GRANT_TYPES.contains(&grant_type.as_str()))
+        {
+            return Err(AppError::ValidationError(format!(
+                "Unsupported grant type {grant_type}"
+            )));
+        }
+        if let Some(scope) = payload
+            .scopes
+            .iter()
+            .find(|scope| !permissions.contains(scope))
+        {
+            return Err(AppError::ValidationError(format!(
+                "Scope {scope} is not granted to the user"
+            )));
+        }
+
+        let mut scopes = payload.scopes;
+        scopes.sort();
+        scopes.dedup();
+        let mut grant_types = payload.grant_types;
+        grant_types.sort();
+        grant_types.dedup();
+
+        let client_secret = hash_util::generate_token();
+        let client = OAuthClient {
+            client_id: Uuid::new_v4().to_string(),
+            client_secret_hash: hash_util::hash_token(&client_secret),
+            name: payload.name,
+            user_id: claims.sub,
+            scopes,
+            grant_types,
+            created_at: Utc::now(),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.oauth_client_repo
+            .create(&mut tx, client.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating OAuth client: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        Ok(CreatedOAuthClientDto {
+            client_secret,
+            client: OAuthClientDto::from(client),
+        })
+    }
+
+    /// Lists every registered OAuth2 client, newest first.
+    async fn list_oauth_clients(&self) -> Result<Vec<OAuthClientDto>, AppError> {
+        let clients = self
+            .oauth_client_repo
+            .find_all(self.pool.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving OAuth clients: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(clients.into_iter().map(OAuthClientDto::from).collect())
+    }
+
+    /// Deletes an OAuth2 client. Its refresh tokens are deleted with it, while access
+    /// tokens already issued stay valid until they expire.
+    async fn delete_oauth_client(&self, client_id: String) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = self
+            .oauth_client_repo
+            .delete(&mut tx, client_id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error deleting OAuth client: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        if !deleted {
+            tx.rollback().await?;
+            return Err(AppError::NotFound("OAuth client not found".into()));
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Issues tokens for the `password`, `client_credentials` and `refresh_token` grants.
+    /// The client must be registered for the grant type and the scope must be one the
+    /// client was registered with; tokens are bound to the client and limited to the scope.
+    /// The `password` grant is throttled and recorded like `login_user` and rejects users
+    /// with MFA enabled, who have to log in through `/auth/login`. Tokens of the
+    /// `client_credentials` grant act on behalf of the client's owner and cannot be refreshed.
+    async fn oauth_token(
+        &self,
+        client: ClientCredentials,
+        payload: OAuthTokenRequestDto,
+        login_client: LoginClient,
+    ) -> Result<OAuthTokenDto, AppError> {
+        let client = self.authenticate_client(&client).await?;
+
+        let grant_type = payload.grant_type.as_str();
+        if !GRANT_TYPES.contains(&grant_type) {
+            return Err(AppError::OAuth(
+                OAuthErrorCode::UnsupportedGrantType,
+                format!("Unsupported grant type {grant_type}"),
+            ));
+        }
+        if !client.grant_types.iter().any(|g| g == grant_type) {
+            return Err(AppError::OAuth(
+                OAuthErrorCode::UnauthorizedClient,
+                format!("Client is not allowed to use the {grant_type} grant"),
+            ));
+        }
+        let refresh = client.grant_types.iter().any(|g| g == REFRESH_TOKEN_GRANT);
+
+        let invalid_scope = || {
+            AppError::OAuth(
+                OAuthErrorCode::InvalidScope,
+                "Requested scope is not granted to the client".into(),
+            )
+        };
+
+        let (auth_body, scope) = match grant_type {
+            PASSWORD_GRANT => {
+                let (Some(username), Some(password)) = (payload.username, payload.password) else {
+                    return Err(AppError::OAuth(
+                        OAuthErrorCode::InvalidRequest,
+                        "Missing username or password".into(),
+                    ));
+                };
+                let scope = grant_scope(payload.scope.as_deref(), &client.scopes)
+                    .ok_or_else(invalid_scope)?;
+
+                let user_auth = self
+                    .authenticate_password(&username, &password, &login_client)
+                    .await
+                    .map_err(|err| match err {
+                        AppError::WrongCredentials | AppError::MissingCredentials => {
+                            AppError::OAuth(
+                                OAuthErrorCode::InvalidGrant,
+                                "Invalid username or password".into(),
+                            )
+                        }
+                        AppError::EmailNotVerified => AppError::OAuth(
+                            OAuthErrorCode::InvalidGrant,
+                            "Email address not verified".into(),
+                        ),
+                        err => err,
+                    })?;
+                if self.find_enabled_mfa(&user_auth.user_id).await?.is_some() {
+                    return Err(AppError::OAuth(
+                        OAuthErrorCode::InvalidGrant,
+                        "Multi-factor authentication is required".into(),
+                    ));
+                }
+
+                let grant = ClientGrant {
+                    client_id: client.client_id,
+                    scope: scope.clone(),
+                };
+                let mut tx = self.pool.begin().await?;
+                self.throttle_repo
+                    .reset(&mut tx, USERNAME_SCOPE.to_string(), username.clone())
+                    .await?;
+                let auth_body = self
+                    .issue_token_pair(&mut tx, &user_auth.user_id, Some(&grant), refresh)
+                    .await?;
+                tx.commit().await?;
+                self.record_auth_event(
+                    AuthEventType::LoginSuccess,
+                    Some(&user_auth.user_id),
+                    Some(&username),
+                    &login_client,
+                    None,
+                )
+                .await;
+
+                (auth_body, scope)
+            }
+            CLIENT_CREDENTIALS_GRANT => {
+                let scope = grant_scope(payload.scope.as_deref(), &client.scopes)
+                    .ok_or_else(invalid_scope)?;
+                let grant = ClientGrant {
+                    client_id: client.client_id,
+                    scope: scope.clone(),
+                };
+
+                let mut tx = self.pool.begin().await?;
+                let auth_body = self
+                    .issue_token_pair(&mut tx, &client.user_id, Some(&grant), false)
+                    .await?;
+                tx.commit().await?;
+
+                (auth_body, scope)
+            }
+            _ => {
+                let invalid_grant = || {
+                    AppError::OAuth(OAuthErrorCode::InvalidGrant, "Invalid refresh token".into())
+                };
+                let Some(token) = payload.refresh_token.filter(|t| !t.is_empty()) else {
+                    return Err(AppError::OAuth(
+                        OAuthErrorCode::InvalidRequest,
+                        "Missing refresh token".into(),
+                    ));
+                };
+
+                // The scope may only be narrowed, so check it before the token is used up.
+                let stored = self
+                    .refresh_token_repo
+                    .find_by_hash(self.pool.clone(), hash_util::hash_token(&token))
+                    .await
+                    .map_err(|err| {
+                        tracing::error!("Error retrieving refresh token: {err}");
+                        AppError::DatabaseError(err)
+                    })?
+                    .filter(|t| t.client_id.as_ref() == Some(&client.client_id))
+                    .ok_or_else(invalid_grant)?;
+                let granted = split_scope(stored.scope.as_deref().unwrap_or_default());
+                let scope =
+                    grant_scope(payload.scope.as_deref(), &granted).ok_or_else(invalid_scope)?;
+
+                let (stored, refresh_token) = self
+                    .rotate_refresh_token(&token, Some(&client.client_id))
+                    .await
+                    .map_err(|err| match err {
+                        AppError::InvalidToken => invalid_grant(),
+                        err => err,
+                    })?;
+                let grant = ClientGrant {
+                    client_id: client.client_id,
+                    scope: scope.clone(),
+                };
+                let token = self
+                    .sign_access_token(&stored.user_id, Some(&grant), None)
+                    .await?;
+                self.record_auth_event(
+                    AuthEventType::TokenRefresh,
+                    Some(&stored.user_id),
+                    None,
+                    &login_client,
+                    None,
+                )
+                .await;
+
+                (
+                    AuthBody::new(token, self.config.access_token_ttl_seconds)
+                        .with_refresh_token(refresh_token),
+                    scope,
+                )
+            }
+        };
+
+        Ok(OAuthTokenDto {
+            access_token: auth_body.access_token,
+            token_type: auth_body.token_type,
+            expires_in: auth_body.expires_in,
+            refresh_token: auth_body.refresh_token,
+            scope,
+        })
+    }
+
+    /// Describes an access token to any authenticated client, so that resource servers
+    /// can validate tokens, while refresh tokens are only described to the client they
+    /// were issued to. Unknown, expired and revoked tokens are reported as inactive.
+    /// The token type is told apart by its format, so `token_type_hint` is not needed.
+    async fn introspect_token(
+        &self,
+        client: ClientCredentials,
+        payload: OAuthTokenRefDto,
+    ) -> Result<IntrospectionDto, AppError> {
+        let client = self.authenticate_client(&client).await?;
+
+        if let Ok(token_data) =
+            KEYS.decode::<Claims>(&payload.token, &make_validation(&self.config))
+        {
+            let claims = token_data.claims;
+            if self.is_token_revoked(&claims).await? {
+                return Ok(IntrospectionDto::default());
+            }
+
+            return Ok(IntrospectionDto {
+                active: true,
+                username: self.find_username(&claims.sub).await?,
+                scope: claims.scope,
+                client_id: claims.client_id,
+                token_type: Some("Bearer".into()),
+                exp: Some(claims.exp as i64),
+                iat: Some(claims.iat as i64),
+                sub: Some(claims.sub),
+                aud: Some(claims.aud),
+                iss: Some(claims.iss),
+                jti: Some(claims.jti),
+            });
+        }
+
+        let stored = self
+            .refresh_token_repo
+            .find_by_hash(self.pool.clone(), hash_util::hash_token(&payload.token))
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving refresh token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        let Some(stored) = stored.filter(|t| {
+            t.client_id.as_ref() == Some(&client.client_id)
+                && t.revoked_at.is_none()
+                && t.expires_at > Utc::now()
+        }) else {
+            return Ok(IntrospectionDto::default());
+        };
+
+        Ok(IntrospectionDto {
+            active: true,
+            username: self.find_username(&stored.user_id).await?,
+            scope: stored.scope,
+            client_id: stored.client_id,
+            token_type: Some("refresh_token".into()),
+            exp: Some(stored.expires_at.timestamp()),
+            iat: Some(stored.created_at.timestamp()),
+            sub: Some(stored.user_id),
+            ..Default::default()
+        })
+    }
+
+    /// Revokes an access token, or the whole family of a refresh token, if it was issued
+    /// to the client. As required by RFC 7009, unknown tokens and tokens of other clients
+    /// are silently ignored.
+    async fn revoke_oauth_token(
+        &self,
+        client: ClientCredentials,
+        payload: OAuthTokenRefDto,
+    ) -> Result<(), AppError> {
+        let client = self.authenticate_client(&client).await?;
+
+        if let Ok(token_data) =
+            KEYS.decode::<Claims>(&payload.token, &make_validation(&self.config))
+        {
+            let claims = token_data.claims;
+            if claims.client_id.as_ref() != Some(&client.client_id) {
+                return Ok(());
+            }
+
+            let mut tx = self.pool.begin().await?;
+            self.revoke_access_token(&mut tx, &claims)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Error revoking token: {err}");
+                    AppError::DatabaseError(err)
+                })?;
+            tx.commit().await?;
+            self.revocation_cache.insert_jti(claims.jti);
+
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let stored = self
+            .refresh_token_repo
+            .find_by_hash_for_update(&mut tx, hash_util::hash_token(&payload.token))
+            .await?;
+        if let Some(stored) = stored.filter(|t| t.client_id.as_ref() == Some(&client.client_id)) {
+            self.refresh_token_repo
+                .revoke_family(&mut tx, stored.family_id)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Adds the access token to the revocation list and ends the session it belongs to,
+    /// together with the refresh tokens of the session.
+    /// If a refresh token of the same user is given, its whole family is revoked too.
+    async fn logout(&self, claims: Claims, payload: LogoutDto) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        self.revoke_access_token(&mut tx, &claims)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error revoking token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        if let Some(session_id) = &claims.sid {
+            self.revoke_session_tokens(&mut tx, session_id).await?;
+        }
+
+        if let Some(refresh_token) = payload.refresh_token.filter(|t| !t.is_empty()) {
+            let token_hash = hash_util::hash_token(&refresh_token);
+            let stored = self
+                .refresh_token_repo
+                .find_by_hash_for_update(&mut tx, token_hash)
+                .await?;
+
+            // Silently ignore tokens of other users so that logout cannot be abused.
+            if let Some(stored) = stored.filter(|t| t.user_id == claims.sub) {
+                self.refresh_token_repo
+                    .revoke_family(&mut tx, stored.family_id)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+        self.revocation_cache.insert_jti(claims.jti);
+        if let Some(session_id) = claims.sid {
+            self.revocation_cache.insert_session(session_id);
+        }
+
+        Ok(())
+    }
+
+    /// Lists the sessions of the authenticated user that are not revoked and whose
+    /// refresh token has not expired, most recently seen first.
+    async fn list_sessions(&self, claims: Claims) -> Result<Vec<SessionDto>, AppError> {
+        let since = Utc::now() - Duration::seconds(self.config.refresh_token_ttl_seconds);
+        let sessions = self
+            .session_repo
+            .find_active_by_user_id(self.pool.clone(), claims.sub, since)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving sessions: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(sessions
+            .into_iter()
+            .map(|session| SessionDto {
+                current: claims.sid.as_ref() == Some(&session.id),
+                ..SessionDto::from(session)
+            })
+            .collect())
+    }
+
+    /// Revokes a session together with its refresh tokens.
+    /// Access tokens of the session are rejected from the next request on.
+    async fn revoke_session(&self, claims: Claims, id: String) -> Result<(), AppError> {
+        let session = self
+            .session_repo
+            .find_by_id(self.pool.clone(), id.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .filter(|session| session.revoked_at.is_none())
+            .ok_or_else(|| AppError::NotFound("Session not found".into()))?;
+        ensure_owner_or_admin(&claims, &session.user_id)?;
+
+        let mut tx = self.pool.begin().await?;
+        self.revoke_session_tokens(&mut tx, &id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error revoking session: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+        self.revocation_cache.insert_session(id);
+
+        Ok(())
+    }
+
+    /// Records a per-user cut-off and revokes all refresh tokens of the user.
+    /// Tokens issued within the same second as the revocation are rejected as well.
+    async fn revoke_all_sessions(&self, user_id: String) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let revoked_at = match self.revoke_sessions(&mut tx, &user_id).await {
+            Ok(revoked_at) => revoked_at,
+            Err(err) => {
+                tx.rollback().await?;
+                if err
+                    .as_database_error()
+                    .is_some_and(|e| e.is_foreign_key_violation())
+                {
+                    return Err(AppError::NotFound("User not found".into()));
+                }
+                tracing::error!("Error revoking sessions: {err}");
+                return Err(AppError::DatabaseError(err));
+            }
+        };
+
+        tx.commit().await?;
+        self.revocation_cache
+            .insert_user_cutoff(user_id, revoked_at.timestamp());
+
+        Ok(())
+    }
+
+    /// Issues an access token for acting as the user, carrying the admin as actor.
+    /// The token cannot be refreshed and is recorded in the impersonation audit trail.
+    /// Impersonation tokens cannot impersonate again and admins cannot be impersonated,
+    /// so that impersonating never grants more than the permissions of the admin.
+    async fn impersonate(&self, claims: Claims, user_id: String) -> Result<AuthBody, AppError> {
+        if claims.act.is_some() {
+            tracing::warn!(
+                "User {} tried to impersonate while impersonating",
+                claims.actor_id()
+            );
+            return Err(AppError::Forbidden);
+        }
+        if claims.sub == user_id {
+            return Err(AppError::ValidationError(
+                "Cannot impersonate yourself".into(),
+            ));
+        }
+        let exists = self
+            .repo
+            .user_exists(self.pool.clone(), user_id.clone())
+            .await
+            .map_err(AppError::DatabaseError)?;
+        if !exists {
+            return Err(AppError::NotFound("User not found".into()));
+        }
+
+        let roles = self.find_roles(&user_id).await?;
+        if roles.iter().any(|role| role == ADMIN_ROLE) {
+            tracing::warn!("User {} tried to impersonate admin {user_id}", claims.sub);
+            return Err(AppError::Forbidden);
+        }
+
+        let impersonation =
+            Claims::new(&user_id, roles, &self.config).with_actor(claims.sub.clone());
+        let token = encode_claims(&impersonation)?;
+
+        // Issuing the token is recorded as the request to the impersonation endpoint.
+        let path = format!("/auth/impersonate/{user_id}");
+        self.insert_impersonation_audit(&impersonation, "POST", &path, 200)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error recording impersonation: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tracing::info!("User {} started impersonating user {user_id}", claims.sub);
+
+        Ok(AuthBody::new(token, self.config.access_token_ttl_seconds))
+    }
+
+    /// Records a request made with an impersonation token in the audit trail.
+    async fn record_impersonated_request(
+        &self,
+        claims: &Claims,
+        method: &str,
+        path: &str,
+        status: u16,
+    ) {
+        if let Err(err) = self
+            .insert_impersonation_audit(claims, method, path, status)
+            .await
+        {
+            tracing::error!("Error recording impersonated request: {err}");
+        }
+    }
+
+    /// Returns a page of the auth event log, most recent first.
+    async fn list_auth_events(
+        &self,
+        query: AuthEventQueryDto,
+    ) -> Result<PageDto<AuthEventDto>, AppError> {
+        let page = query.page.unwrap_or(1);
+        let page_size = query.page_size.unwrap_or(DEFAULT_EVENT_PAGE_SIZE);
+        let filter = query.filter();
+
+        let total = self
+            .auth_event_repo
+            .count(self.pool.clone(), filter.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error counting auth events: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        let events = self
+            .auth_event_repo
+            .find(
+                self.pool.clone(),
+                filter,
+                page_size,
+                (page - 1).saturating_mul(page_size),
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving auth events: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(PageDto {
+            items: events.into_iter().map(AuthEventDto::from).collect(),
+            page,
+            page_size,
+            total,
+        })
+    }
+
+    /// Checks the claims against the cached revocation list,
+    /// reloading the cache from the database when it is stale.
+    async fn is_token_revoked(&self, claims: &Claims) -> Result<bool, AppError> {
+        if self.revocation_cache.is_stale() {
+            self.reload_revocation_cache().await?;
+        }
+
+        Ok(self.revocation_cache.is_revoked(claims))
+    }
+
+    /// Resolves the permissions from the cached role mapping,
+    /// reloading the cache from the database when it is stale.
+    async fn resolve_permissions(&self, roles: &[String]) -> Result<Permissions, AppError> {
+        if self.permission_cache.is_stale() {
+            let role_permissions = self
+                .role_repo
+                .find_role_permissions(self.pool.clone())
+                .await
+                .map_err(|err| {
+                    tracing::error!("Error loading role permissions: {err}");
+                    AppError::DatabaseError(err)
+                })?;
+            self.permission_cache.replace(role_permissions);
+        }
+
+        Ok(self.permission_cache.resolve(roles))
+    }
+}
+
+/// Internal helper methods defined on `AuthService`.
+impl AuthService {
+    /// Checks a username and password, throttling failed attempts per username and per
+    /// client IP. Unknown users and wrong passwords are rejected with the same error.
+    async fn authenticate_password(
+        &self,
+        username: &str,
+        password: &str,
+        client: &LoginClient,
+    ) -> Result<UserAuth, AppError> {
+        if username.is_empty() || password.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let throttle_keys = self.throttle_keys(username, client.ip);
+        self.check_throttle(&throttle_keys, None, username, client)
+            .await?;
+
+        let user_auth = self
+            .repo
+            .find_by_user_name(self.pool.clone(), username.to_string())
+            .await
+            .map_err(AppError::DatabaseError)?;
+
+        let params = PasswordParams::from_config(&self.config);
+        let verified = hash_util::verify_password_or_dummy(
+            user_auth.as_ref().map(|u| u.password_hash.as_str()),
+            password,
+            params,
+        );
+        let user_auth = match user_auth {
+            Some(user_auth) if verified => user_auth,
+            // Failed logins of existing users are recorded with the user.
+            user_auth => {
+                self.record_failed_login(
+                    &throttle_keys,
+                    user_auth.as_ref().map(|u| u.user_id.as_str()),
+                    username,
+                    client,
+                    "wrong_credentials",
+                )
+                .await?;
+                return Err(AppError::WrongCredentials);
+            }
+        };
+
+        // The password is known now, so an outdated hash can be replaced transparently.
+        // A failed rehash must not fail the login; it is retried on the next one.
+        if hash_util::needs_rehash(&user_auth.password_hash, params) {
+            if let Err(err) = self.rehash_password(&user_auth, password).await {
+                tracing::error!("Error rehashing password: {err}");
+            }
+        }
+
+        if self.config.require_verified_email {
+            let account = self
+                .repo
+                .find_account_by_user_name(self.pool.clone(), username.to_string())
+                .await
+                .map_err(AppError::DatabaseError)?;
+            if account.is_none_or(|account| account.email_verified_at.is_none()) {
+                self.record_auth_event(
+                    AuthEventType::LoginFailure,
+                    Some(&user_auth.user_id),
+                    Some(username),
+                    client,
+                    Some("email_not_verified"),
+                )
+                .await;
+                return Err(AppError::EmailNotVerified);
+            }
+        }
+
+        Ok(user_auth)
+    }
+
+    /// Checks a new password of the user against the password policy.
+    /// Violations are reported as validation errors of the request field `field`.
+    fn check_password_policy(
+        &self,
+        field: &'static str,
+        password: &str,
+        username: &str,
+    ) -> Result<(), AppError> {
+        let violations = self.password_policy.check(password, username);
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+        for violation in violations {
+            errors.add(field, violation);
+        }
+        Err(AppError::InvalidFields(errors))
+    }
+
+    /// Hashes a password with the configured Argon2 parameters.
+    fn hash_password(&self, password: &str) -> Result<String, AppError> {
+        hash_util::hash_password(password, PasswordParams::from_config(&self.config))
+            .map_err(|_| AppError::InternalError)
+    }
+
+    /// Replaces the stored hash of the user with one made with the current parameters,
+    /// unless the password was changed since it was read.
+    async fn rehash_password(&self, user_auth: &UserAuth, password: &str) -> Result<(), AppError> {
+        let new_hash = self.hash_password(password)?;
+
+        let mut tx = self.pool.begin().await?;
+        self.repo
+            .replace_password_hash(
+                &mut tx,
+                user_auth.user_id.clone(),
+                user_auth.password_hash.clone(),
+                new_hash,
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Starts a session of the user and issues an access token and a refresh token bound
+    /// to it, clears the failed login attempts of the username and records the login.
+    /// The session ID is also the family ID of the refresh token.
+    async fn issue_tokens(
+        &self,
+        user_id: &str,
+        username: &str,
+        client: LoginClient,
+    ) -> Result<AuthBody, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // Only the username counter is cleared; the IP counter must not be reset by an
+        // attacker who owns a valid account.
+        self.throttle_repo
+            .reset(&mut tx, USERNAME_SCOPE.to_string(), username.to_string())
+            .await?;
+
+        let now = Utc::now();
+        let session = UserSession {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            device_id: client.device_id.clone(),
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip.map(|ip| ip.to_string()),
+            created_at: now,
+            last_seen_at: now,
+            revoked_at: None,
+        };
+        let session_id = session.id.clone();
+        self.session_repo
+            .create(&mut tx, session)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating session: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        let token = self
+            .sign_access_token(user_id, None, Some(&session_id))
+            .await?;
+        let (refresh_token, _) = self
+            .create_refresh_token(&mut tx, user_id, session_id, None, None)
+            .await?;
+        tx.commit().await?;
+
+        self.record_auth_event(
+            AuthEventType::LoginSuccess,
+            Some(user_id),
+            Some(username),
+            &client,
+            None,
+        )
+        .await;
+
+        Ok(AuthBody::new(token, self.config.access_token_ttl_seconds)
+            .with_refresh_token(refresh_token))
+    }
+
+    /// Issues an access token and, if `refresh` is set, a refresh token starting a new
+    /// token family within the transaction. Tokens issued to an OAuth2 client are bound
+    /// to it and limited to the granted scope.
+    async fn issue_token_pair(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        grant: Option<&ClientGrant>,
+        refresh: bool,
+    ) -> Result<AuthBody, AppError> {
+        let token = self.sign_access_token(user_id, grant, None).await?;
+        let auth_body = AuthBody::new(token, self.config.access_token_ttl_seconds);
+        if !refresh {
+            return Ok(auth_body);
+        }
+
+        let family_id = Uuid::new_v4().to_string();
+        let (refresh_token, _) = self
+            .create_refresh_token(
+                tx,
+                user_id,
+                family_id,
+                grant.map(|g| g.client_id.clone()),
+                grant.map(|g| g.scope.clone()),
+            )
+            .await?;
+
+        Ok(auth_body.with_refresh_token(refresh_token))
+    }
+
+    /// Signs an access token carrying the current roles of the user,
+    /// bound to the OAuth2 client grant or to the login session, if given.
+    async fn sign_access_token(
+        &self,
+        user_id: &str,
+        grant: Option<&ClientGrant>,
+        session_id: Option<&str>,
+    ) -> Result<String, AppError> {
+        let roles = self.find_roles(user_id).await?;
+        let mut claims = Claims::new(user_id, roles, &self.config);
+        if let Some(grant) = grant {
+            claims = claims.with_client(grant.client_id.clone(), grant.scope.clone());
+        }
+        if let Some(session_id) = session_id {
+            claims = claims.with_session(session_id.to_string());
+        }
+
+        encode_claims(&claims).map_err(|_| AppError::InternalError)
+    }
+
+    /// Invalidates the presented refresh token and replaces it by a new one of the same
+    /// family, bound to the same client and scope. Returns the presented token together
+    /// with the new plain token.
+    /// If a token that was already rotated is presented again, the token has most
+    /// likely been stolen, so every token of its family is revoked, as is the session
+    /// of a login family. A successful rotation records activity of the session.
+    /// Tokens are only accepted from the client they were issued to (`None` for tokens
+    /// issued by `/auth/login`).
+    async fn rotate_refresh_token(
+        &self,
+        token: &str,
+        client_id: Option<&str>,
+    ) -> Result<(RefreshToken, String), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let token_hash = hash_util::hash_token(token);
+        let stored = self
+            .refresh_token_repo
+            .find_by_hash_for_update(&mut tx, token_hash)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving refresh token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        let Some(stored) = stored.filter(|t| t.client_id.as_deref() == client_id) else {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        };
+
+        if stored.revoked_at.is_some() {
+            tracing::warn!(
+                "Refresh token reuse detected for user {}, revoking token family {}",
+                stored.user_id,
+                stored.family_id
+            );
+            self.revoke_session_tokens(&mut tx, &stored.family_id)
+                .await?;
+            tx.commit().await?;
+            self.revocation_cache.insert_session(stored.family_id);
+            return Err(AppError::InvalidToken);
+        }
+
+        if stored.expires_at <= Utc::now() {
+            tx.rollback().await?;
+            return Err(AppError::InvalidToken);
+        }
+
+        let (refresh_token, new_id) = self
+            .create_refresh_token(
+                &mut tx,
+                &stored.user_id,
+                stored.family_id.clone(),
+                stored.client_id.clone(),
+                stored.scope.clone(),
+            )
+            .await?;
+        self.refresh_token_repo
+            .mark_rotated(&mut tx, stored.id.clone(), new_id)
+            .await?;
+        self.session_repo
+            .touch(&mut tx, stored.family_id.clone())
+            .await?;
+        tx.commit().await?;
+
+        Ok((stored, refresh_token))
+    }
+
+    /// Looks up an OAuth2 client and checks its secret.
+    async fn authenticate_client(
+        &self,
+        client: &ClientCredentials,
+    ) -> Result<OAuthClient, AppError> {
+        let stored = self
+            .oauth_client_repo
+            .find_by_client_id(self.pool.clone(), client.client_id.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving OAuth client: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        stored
+            .filter(|c| c.client_secret_hash == hash_util::hash_token(&client.client_secret))
+            .ok_or_else(|| {
+                AppError::OAuth(
+                    OAuthErrorCode::InvalidClient,
+                    "Client authentication failed".into(),
+                )
+            })
+    }
+
+    /// Adds the access token to the revocation list within the transaction.
+    /// The caller adds the `jti` to the revocation cache once the transaction is committed.
+    async fn revoke_access_token(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        claims: &Claims,
+    ) -> Result<(), sqlx::Error> {
+        // The token is accepted until `exp` plus the leeway, so keep it listed until then.
+        let expires_at =
+            DateTime::from_timestamp(claims.exp as i64 + self.config.jwt_leeway_seconds as i64, 0)
+                .unwrap_or_else(Utc::now);
+        self.revocation_repo
+            .revoke_token(
+                tx,
+                RevokedToken {
+                    jti: claims.jti.clone(),
+                    user_id: claims.sub.clone(),
+                    expires_at,
+                },
+            )
+            .await
+    }
+
+    /// Inserts an entry of the impersonation audit trail for the impersonation token.
+    async fn insert_impersonation_audit(
+        &self,
+        claims: &Claims,
+        method: &str,
+        path: &str,
+        status: u16,
+    ) -> Result<(), sqlx::Error> {
+        let Some(actor) = &claims.act else {
+            return Ok(());
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.impersonation_audit_repo
+            .create(
+                &mut tx,
+                ImpersonationAudit {
+                    id: Uuid::new_v4().to_string(),
+                    actor_id: actor.sub.clone(),
+                    user_id: claims.sub.clone(),
+                    token_id: claims.jti.clone(),
+                    method: method.to_string(),
+                    path: path.chars().take(MAX_AUDIT_PATH_CHARS).collect(),
+                    status: status as i16,
+                    created_at: Utc::now(),
+                },
+            )
+            .await?;
+        tx.commit().await
+    }
+
+    /// Returns the username of the user, if the user still exists.
+    async fn find_username(&self, user_id: &str) -> Result<Option<String>, AppError> {
+        let account = self
+            .repo
+            .find_account_by_user_id(self.pool.clone(), user_id.to_string())
+            .await
+            .map_err(AppError::DatabaseError)?;
+
+        Ok(account.map(|account| account.username))
+    }
+
+    /// Returns the TOTP second factor of the user if MFA is enabled.
+    async fn find_enabled_mfa(&self, user_id: &str) -> Result<Option<UserMfa>, AppError> {
+        let mfa = self
+            .mfa_repo
+            .find_by_user_id(self.pool.clone(), user_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving MFA settings: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(mfa.filter(|m| m.enabled_at.is_some()))
+    }
+
+    /// Generates a new opaque MFA challenge token and stores its hash.
+    async fn create_mfa_challenge(&self, user_id: &str) -> Result<MfaChallengeDto, AppError> {
+        let token = hash_util::generate_token();
+        let now = Utc::now();
+        let challenge = MfaChallenge {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            token_hash: hash_util::hash_token(&token),
+            failed_attempts: 0,
+            expires_at: now + Duration::seconds(self.config.mfa_challenge_ttl_seconds),
+            used_at: None,
+            created_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.mfa_challenge_repo
+            .create(&mut tx, challenge)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating MFA challenge: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        Ok(MfaChallengeDto {
+            mfa_token: token,
+            expires_in: self.config.mfa_challenge_ttl_seconds,
+        })
+    }
+
+    /// Accepts a TOTP code or a recovery code within the transaction.
+    /// A TOTP code is only accepted once, and a recovery code is used up.
+    async fn use_mfa_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        mfa: &UserMfa,
+        code: &str,
+    ) -> Result<bool, sqlx::Error> {
+        if let Some(step) = totp::verify_code(&mfa.totp_secret, code.trim(), Utc::now().timestamp())
+        {
+            return self.mfa_repo.use_step(tx, mfa.user_id.clone(), step).await;
+        }
+
+        let code_hash = hash_util::hash_token(&hash_util::normalize_recovery_code(code));
+        let used = self
+            .mfa_repo
+            .use_recovery_code(tx, mfa.user_id.clone(), code_hash)
+            .await?;
+        if used {
+            tracing::info!("Recovery code used by user {}", mfa.user_id);
+        }
+
+        Ok(used)
+    }
+
+    /// Generates a new opaque refresh token, stores its hash and returns the
+    /// plain token together with the id of the stored record.
+    async fn create_refresh_token(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        family_id: String,
+        client_id: Option<String>,
+        scope: Option<String>,
+    ) -> Result<(String, String), AppError> {
+        let token = hash_util::generate_token();
+        let now = Utc::now();
+        let refresh_token = RefreshToken {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            family_id,
+            token_hash: hash_util::hash_token(&token),
+            expires_at: now + Duration::seconds(self.config.refresh_token_ttl_seconds),
+            revoked_at: None,
+            client_id,
+            scope,
+            created_at: now,
+        };
+        let id = refresh_token.id.clone();
+
+        self.refresh_token_repo
+            .create(tx, refresh_token)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error creating refresh token: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok((token, id))
+    }
+
+    /// Returns the throttle keys of a login attempt with their policies.
+    fn throttle_keys(
+        &self,
+        username: &str,
+        client_ip: Option<IpAddr>,
+    ) -> Vec<(&'static str, String, ThrottlePolicy)> {
+        let mut keys = vec![(
+            USERNAME_SCOPE,
+            username.to_string(),
+            ThrottlePolicy::for_username(&self.config),
+        )];
+        if let Some(ip) = client_ip {
+            keys.push((
+                IP_SCOPE,
+                ip.to_string(),
+                ThrottlePolicy::for_ip(&self.config),
+            ));
+        }
+        keys
+    }
+
+    /// Rejects the attempt if any of the keys is locked, recording it as a failed login.
+    async fn check_throttle(
+        &self,
+        keys: &[(&'static str, String, ThrottlePolicy)],
+        user_id: Option<&str>,
+        username: &str,
+        client: &LoginClient,
+    ) -> Result<(), AppError> {
+        let now = Utc::now();
+        for (scope, key, _) in keys {
+            let locked_until = self
+                .throttle_repo
+                .find_locked_until(self.pool.clone(), scope.to_string(), key.clone())
+                .await
+                .map_err(|err| {
+                    tracing::error!("Error retrieving login throttle: {err}");
+                    AppError::DatabaseError(err)
+                })?;
+
+            if let Some(locked_until) = locked_until.filter(|t| *t > now) {
+                let retry_after = ((locked_until - now).num_milliseconds() + 999) / 1000;
+                self.record_auth_event(
+                    AuthEventType::LoginFailure,
+                    user_id,
+                    Some(username),
+                    client,
+                    Some("throttled"),
+                )
+                .await;
+                return Err(AppError::TooManyAttempts(retry_after));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts a failed attempt for every key and applies the backoff or lockout.
+    /// The attempt and the lockouts it causes are recorded in the auth event log,
+    /// with `reason` as detail of the failure and the locked scope as detail of a lockout.
+    async fn record_failed_login(
+        &self,
+        keys: &[(&'static str, String, ThrottlePolicy)],
+        user_id: Option<&str>,
+        username: &str,
+        client: &LoginClient,
+        reason: &str,
+    ) -> Result<(), AppError> {
+        let now = Utc::now();
+        let window_start = now - Duration::seconds(self.config.login_attempt_window_seconds);
+
+        let mut locked_scopes = Vec::new();
+        let mut tx = self.pool.begin().await?;
+        for (scope, key, policy) in keys {
+            let failed_attempts = self
+                .throttle_repo
+                .record_failure(&mut tx, scope.to_string(), key.clone(), window_start)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Error recording failed login: {err}");
+                    AppError::DatabaseError(err)
+                })?;
+
+            match policy.action(failed_attempts) {
+                ThrottleAction::None => {}
+                ThrottleAction::Backoff(delay) => {
+                    self.throttle_repo
+                        .lock(&mut tx, scope.to_string(), key.clone(), now + delay)
+                        .await?;
+                }
+                ThrottleAction::Lockout(duration) => {
+                    tracing::warn!(
+                        "Login locked out for {scope} {key} after {failed_attempts} failed attempts"
+                    );
+                    let locked_until = now + duration;
+                    self.throttle_repo
+                        .lock(&mut tx, scope.to_string(), key.clone(), locked_until)
+                        .await?;
+                    self.throttle_repo
+                        .record_lockout(
+                            &mut tx,
+                            LoginLockout {
+                                id: Uuid::new_v4().to_string(),
+                                scope: scope.to_string(),
+                                key: key.clone(),
+                                failed_attempts,
+                                locked_until,
+                                created_at: now,
+                            },
+                        )
+                        .await?;
+                    locked_scopes.push(*scope);
+                }
+            }
+        }
+        tx.commit().await?;
+
+        self.record_auth_event(
+            AuthEventType::LoginFailure,
+            user_id,
+            Some(username),
+            client,
+            Some(reason),
+        )
+        .await;
+        for scope in locked_scopes {
+            self.record_auth_event(
+                AuthEventType::LoginLockout,
+                user_id,
+                Some(username),
+                client,
+                Some(scope),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    /// Records an authentication event in the auth event log and emits it as a log event.
+    /// A failure to record the event must not fail the request, so it is only logged.
+    async fn record_auth_event(
+        &self,
+        event_type: AuthEventType,
+        user_id: Option<&str>,
+        username: Option<&str>,
+        client: &LoginClient,
+        detail: Option<&str>,
+    ) {
+        let event = AuthEvent {
+            id: Uuid::new_v4().to_string(),
+            event_type: event_type.as_str().to_string(),
+            user_id: user_id.map(str::to_string),
+            username: username.map(|name| name.chars().take(MAX_EVENT_USERNAME_CHARS).collect()),
+            ip_address: client.ip.map(|ip| ip.to_string()),
+            user_agent: client.user_agent.clone(),
+            detail: detail.map(str::to_string),
+            created_at: Utc::now(),
+        };
+        tracing::info!(
+            event = %event.event_type,
+            user_id = ?event.user_id,
+            username = ?event.username,
+            ip = ?event.ip_address,
+            detail = ?event.detail,
+            "Authentication event"
+        );
+
+        let result = async {
+            let mut tx = self.pool.begin().await?;
+            self.auth_event_repo.create(&mut tx, event).await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::error!("Error recording authentication event: {err}");
+        }
+    }
+
+    /// Records a per-user cut-off and revokes all refresh tokens and sessions of the user
+    /// within the transaction. Returns the cut-off, which the caller adds to the revocation
+    /// cache once the transaction is committed.
+    async fn revoke_sessions(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+    ) -> Result<DateTime<Utc>, sqlx::Error> {
+        let revoked_at = Utc::now();
+        self.revocation_repo
+            .revoke_all_for_user(
+                tx,
+                UserTokenRevocation {
+                    user_id: user_id.to_string(),
+                    revoked_at,
+                },
+            )
+            .await?;
+        self.refresh_token_repo
+            .revoke_all_for_user(tx, user_id.to_string())
+            .await?;
+        self.session_repo
+            .revoke_all_for_user(tx, user_id.to_string())
+            .await?;
+
+        Ok(revoked_at)
+    }
+
+    /// Revokes a session and every refresh token of its family within the transaction.
+    /// The caller adds the session to the revocation cache once the transaction is committed.
+    async fn revoke_session_tokens(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        session_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        self.session_repo.revoke(tx, session_id.to_string()).await?;
+        self.refresh_token_repo
+            .revoke_family(tx, session_id.to_string())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the OpenID Connect provider, or an error if none is configured.
+    fn oidc_provider(&self) -> Result<&OidcProvider, AppError> {
+        self.oidc_provider
+            .as_deref()
+            .ok_or_else(|| AppError::NotFound("OpenID Connect login is not configured".into()))
+    }
+
+    /// Returns the account of the user linked to the external identity, linking the
+    /// identity first if needed (see `complete_oidc_login`).
+    /// Returns `None` if no user can be linked.
+    async fn find_or_link_identity(
+        &self,
+        identity: &ExternalIdentity,
+    ) -> Result<Option<UserAccount>, AppError> {
+        let linked = self
+            .identity_repo
+            .find_account(
+                self.pool.clone(),
+                identity.issuer.clone(),
+                identity.subject.clone(),
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving linked user: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        if linked.is_some() {
+            return Ok(linked);
+        }
+
+        if let Some(email) = identity.email.as_ref().filter(|_| identity.email_verified) {
+            let accounts = self
+                .identity_repo
+                .find_accounts_by_verified_email(self.pool.clone(), email.clone())
+                .await
+                .map_err(|err| {
+                    tracing::error!("Error retrieving users by email: {err}");
+                    AppError::DatabaseError(err)
+                })?;
+            match accounts.as_slice() {
+                [account] => {
+                    let mut tx = self.pool.begin().await?;
+                    self.link_identity(&mut tx, &account.user_id, identity)
+                        .await
+                        .map_err(|err| {
+                            tracing::error!("Error linking external identity: {err}");
+                            AppError::DatabaseError(err)
+                        })?;
+                    tx.commit().await?;
+                    tracing::info!(
+                        "Linked subject {} of {} to user {} by email",
+                        identity.subject,
+                        identity.issuer,
+                        account.user_id
+                    );
+                    return Ok(Some(account.clone()));
+                }
+                [] => {}
+                _ => tracing::warn!(
+                    "Not linking subject {} of {}: several users have its email address",
+                    identity.subject,
+                    identity.issuer
+                ),
+            }
+        }
+
+        if self.config.oidc_auto_provision {
+            return self.provision_user(identity).await;
+        }
+        Ok(None)
+    }
+
+    /// Creates a user with the default role for the external identity and links it.
+    /// The username is the preferred username of the identity, or the local part of its
+    /// email address, with a random suffix if it is already taken.
+    /// Returns `None` if the provider did not report an email address.
+    async fn provision_user(
+        &self,
+        identity: &ExternalIdentity,
+    ) -> Result<Option<UserAccount>, AppError> {
+        let Some(email) = identity.email.clone().filter(|email| !email.is_empty()) else {
+            tracing::warn!(
+                "Cannot provision a user for subject {} of {} without an email address",
+                identity.subject,
+                identity.issuer
+            );
+            return Ok(None);
+        };
+        let base_name = identity
+            .preferred_username
+            .as_deref()
+            .or_else(|| email.split('@').next())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or("user");
+
+        for attempt in 0..MAX_PROVISION_ATTEMPTS {
+            let username = if attempt == 0 {
+                base_name.chars().take(MAX_USERNAME_CHARS).collect()
+            } else {
+                let suffix = &Uuid::new_v4().simple().to_string()[..6];
+                let base: String = base_name.chars().take(MAX_USERNAME_CHARS - 7).collect();
+                format!("{base}-{suffix}")
+            };
+            let account = UserAccount {
+                user_id: Uuid::new_v4().to_string(),
+                username,
+                email: email.clone(),
+                email_verified_at: identity.email_verified.then(Utc::now),
+            };
+
+            let mut tx = self.pool.begin().await?;
+            match self.create_identity_user(&mut tx, &account, identity).await {
+                Ok(()) => {
+                    tx.commit().await?;
+                    tracing::info!(
+                        "Provisioned user {} for subject {} of {}",
+                        account.user_id,
+                        identity.subject,
+                        identity.issuer
+                    );
+                    return Ok(Some(account));
+                }
+                Err(err)
+                    if err
+                        .as_database_error()
+                        .is_some_and(|db_err| db_err.is_unique_violation()) =>
+                {
+                    tx.rollback().await?;
+                }
+                Err(err) => {
+                    tx.rollback().await?;
+                    tracing::error!("Error provisioning user: {err}");
+                    return Err(AppError::DatabaseError(err));
+                }
+            }
+        }
+
+        tracing::warn!(
+            "Cannot provision a user for subject {} of {}: username {base_name} is taken",
+            identity.subject,
+            identity.issuer
+        );
+        Ok(None)
+    }
+
+    /// Inserts the user of an external identity, its default role and its link to the
+    /// identity within the transaction. The email address is marked as verified if the
+    /// provider verified it.
+    async fn create_identity_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        account: &UserAccount,
+        identity: &ExternalIdentity,
+    ) -> Result<(), sqlx::Error> {
+        self.repo
+            .create_user(
+                tx,
+                account.user_id.clone(),
+                account.username.clone(),
+                account.email.clone(),
+            )
+            .await?;
+        self.role_repo
+            .assign_role(tx, account.user_id.clone(), DEFAULT_ROLE.to_string())
+            .await?;
+        if account.email_verified_at.is_some() {
+            self.repo
+                .mark_email_verified(tx, account.user_id.clone(), account.email.clone())
+                .await?;
+        }
+        self.link_identity(tx, &account.user_id, identity).await
+    }
+
+    /// Links the external identity to the user within the transaction.
+    async fn link_identity(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        identity: &ExternalIdentity,
+    ) -> Result<(), sqlx::Error> {
+        self.identity_repo
+            .create(
+                tx,
+                UserIdentity {
+                    id: Uuid::new_v4().to_string(),
+                    user_id: user_id.to_string(),
+                    issuer: identity.issuer.clone(),
+                    subject: identity.subject.clone(),
+                    email: identity.email.clone(),
+                    created_at: Utc::now(),
+                },
+            )
+            .await
+    }
+
+    /// Inserts the user, its credentials and its default role within the transaction.
+    async fn register(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        auth_user: &AuthUserDto,
+        user_auth: UserAuth,
+    ) -> Result<(), sqlx::Error> {
+        self.repo
+            .create_user(
+                tx,
+                user_id.to_string(),
+                auth_user.username.clone(),
+                auth_user.email.clone(),
+            )
+            .await?;
+        self.repo.create(tx, user_auth).await?;
+        self.role_repo
+            .assign_role(tx, user_id.to_string(), DEFAULT_ROLE.to_string())
+            .await
+    }
+
+    /// Returns the names of the roles assigned to the user.
+    async fn find_roles(&self, user_id: &str) -> Result<Vec<String>, AppError> {
+        self.role_repo
+            .find_role_names_by_user_id(self.pool.clone(), user_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving user roles: {err}");
+                AppError::DatabaseError(err)
+            })
+    }
+
+    /// Loads the active part of the revocation list into the in-process cache.
+    async fn reload_revocation_cache(&self) -> Result<(), AppError> {
+        let revoked_jtis = self
+            .revocation_repo
+            .find_active_revoked_tokens(self.pool.clone())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error loading revoked tokens: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        // Cut-offs older than the access token lifetime cannot match a valid token.
+        let since = Utc::now()
+            - Duration::seconds(
+                self.config.access_token_ttl_seconds + self.config.jwt_leeway_seconds as i64,
+            );
+        let revoked_before = self
+            .revocation_repo
+            .find_user_revocations_since(self.pool.clone(), since)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error loading user revocations: {err}");
+                AppError::DatabaseError(err)
+            })?
+            .into_iter()
+            .map(|r| (r.user_id, r.revoked_at.timestamp()))
+            .collect();
+        let revoked_sessions = self
+            .session_repo
+            .find_revoked_since(self.pool.clone(), since)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error loading revoked sessions: {err}");
+                AppError::DatabaseError(err)
+            })?;
+
+        self.revocation_cache
+            .replace(revoked_jtis, revoked_before, revoked_sessions);
 
-        Ok(AuthBody::new(token))
+        Ok(())
     }
 }
@@ -0,0 +1,127 @@
+//! Login throttling policy.
+//!
+//! Failed logins are counted per username and per client IP within a sliding window.
+//! Once a counter reaches `backoff_after`, every further failure locks the key for an
+//! exponentially growing delay. Once it reaches `lockout_after`, the key is locked for
+//! the full lockout duration and the lockout is recorded.
+
+use chrono::Duration;
+
+use crate::common::config::Config;
+
+/// Throttle scope for failed attempts against a username.
+pub const USERNAME_SCOPE: &str = "username";
+
+/// Throttle scope for failed attempts from a client IP.
+pub const IP_SCOPE: &str = "ip";
+
+/// What to do after a failed attempt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThrottleAction {
+    None,
+    Backoff(Duration),
+    Lockout(Duration),
+}
+
+/// Thresholds applied to the failed attempts of one scope.
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottlePolicy {
+    backoff_after: i32,
+    lockout_after: i32,
+    backoff_base: Duration,
+    lockout: Duration,
+}
+
+impl ThrottlePolicy {
+    /// Policy for failed attempts against a username.
+    pub fn for_username(config: &Config) -> Self {
+        Self {
+            backoff_after: config.login_user_backoff_after,
+            lockout_after: config.login_user_lockout_after,
+            backoff_base: Duration::seconds(config.login_backoff_base_seconds),
+            lockout: Duration::seconds(config.login_lockout_seconds),
+        }
+    }
+
+    /// Policy for failed attempts from a client IP.
+    /// An IP is usually shared by more users, so its thresholds are higher.
+    pub fn for_ip(config: &Config) -> Self {
+        Self {
+            backoff_after: config.login_ip_backoff_after,
+            lockout_after: config.login_ip_lockout_after,
+            backoff_base: Duration::seconds(config.login_backoff_base_seconds),
+            lockout: Duration::seconds(config.login_lockout_seconds),
+        }
+    }
+
+    /// Returns the action for the given number of consecutive failed attempts.
+    /// The backoff doubles with every failure and never exceeds the lockout duration.
+    pub fn action(&self, failed_attempts: i32) -> ThrottleAction {
+        if failed_attempts >= self.lockout_after {
+            return ThrottleAction::Lockout(self.lockout);
+        }
+        if failed_attempts < self.backoff_after {
+            return ThrottleAction::None;
+        }
+
+        let exponent = (failed_attempts - self.backoff_after).min(30) as u32;
+        let backoff = self
+            .backoff_base
+            .checked_mul(2_i32.pow(exponent))
+            .unwrap_or(self.lockout);
+        ThrottleAction::Backoff(backoff.min(self.lockout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ThrottlePolicy {
+        ThrottlePolicy {
+            backoff_after: 3,
+            lockout_after: 10,
+            backoff_base: Duration::seconds(1),
+            lockout: Duration::seconds(60),
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let policy = policy();
+
+        assert_eq!(policy.action(2), ThrottleAction::None);
+        assert_eq!(
+            policy.action(3),
+            ThrottleAction::Backoff(Duration::seconds(1))
+        );
+        assert_eq!(
+            policy.action(4),
+            ThrottleAction::Backoff(Duration::seconds(2))
+        );
+        assert_eq!(
+            policy.action(6),
+            ThrottleAction::Backoff(Duration::seconds(8))
+        );
+    }
+
+    #[test]
+    fn test_backoff_capped_by_lockout() {
+        assert_eq!(
+            policy().action(9),
+            ThrottleAction::Backoff(Duration::seconds(60))
+        );
+    }
+
+    #[test]
+    fn test_lockout() {
+        assert_eq!(
+            policy().action(10),
+            ThrottleAction::Lockout(Duration::seconds(60))
+        );
+        assert_eq!(
+            policy().action(42),
+            ThrottleAction::Lockout(Duration::seconds(60))
+        );
+    }
+}
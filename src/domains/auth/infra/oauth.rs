@@ -0,0 +1,81 @@
+//! OAuth2 grant types and scopes of the token endpoint.
+//!
+//! Scopes are permission names. A client may only request scopes it was registered
+//! with, and a refreshed token may only narrow the scope of the original grant.
+
+/// Grant exchanging a username and password for tokens.
+pub const PASSWORD_GRANT: &str = "password";
+
+/// Grant issuing a token to the client itself, acting on behalf of its owner.
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+
+/// Grant exchanging a refresh token for a new token pair.
+pub const REFRESH_TOKEN_GRANT: &str = "refresh_token";
+
+/// Grant types a client can be registered with.
+pub const GRANT_TYPES: [&str; 3] = [
+    PASSWORD_GRANT,
+    CLIENT_CREDENTIALS_GRANT,
+    REFRESH_TOKEN_GRANT,
+];
+
+/// Returns the scope granted for a request as a sorted, space-delimited list.
+/// Without a requested scope, every allowed scope is granted.
+/// Returns `None` if a requested scope is not allowed or nothing would be granted.
+pub fn grant_scope(requested: Option<&str>, allowed: &[String]) -> Option<String> {
+    let mut scopes: Vec<&str> = match requested.filter(|scope| !scope.trim().is_empty()) {
+        Some(requested) => requested.split_whitespace().collect(),
+        None => allowed.iter().map(String::as_str).collect(),
+    };
+    if scopes.is_empty()
+        || !scopes
+            .iter()
+            .all(|scope| allowed.iter().any(|a| a == scope))
+    {
+        return None;
+    }
+
+    scopes.sort_unstable();
+    scopes.dedup();
+    Some(scopes.join(" "))
+}
+
+/// Splits a space-delimited scope into its permission names.
+pub fn split_scope(scope: &str) -> Vec<String> {
+    scope.split_whitespace().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed() -> Vec<String> {
+        vec!["device:read".into(), "file:read".into(), "user:read".into()]
+    }
+
+    #[test]
+    fn test_grant_scope_defaults_to_allowed() {
+        assert_eq!(
+            grant_scope(None, &allowed()).as_deref(),
+            Some("device:read file:read user:read")
+        );
+        assert_eq!(
+            grant_scope(Some("  "), &allowed()).as_deref(),
+            Some("device:read file:read user:read")
+        );
+    }
+
+    #[test]
+    fn test_grant_scope_narrows() {
+        assert_eq!(
+            grant_scope(Some("user:read device:read user:read"), &allowed()).as_deref(),
+            Some("device:read user:read")
+        );
+    }
+
+    #[test]
+    fn test_grant_scope_rejects_unknown() {
+        assert_eq!(grant_scope(Some("user:read user:delete"), &allowed()), None);
+        assert_eq!(grant_scope(None, &[]), None);
+    }
+}
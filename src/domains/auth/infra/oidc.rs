@@ -0,0 +1,264 @@
+//! Login with an external OpenID Connect provider.
+//!
+//! The provider is configured by its discovery URL. A login redirects the browser to the
+//! authorization endpoint with a random `state`, a `nonce` and a PKCE code challenge (S256);
+//! the provider redirects back to our callback with an authorization code, which is exchanged
+//! together with the code verifier at the token endpoint for an ID token.
+//!
+//! The ID token must be signed with an asymmetric key published in the provider's JWKS, be
+//! issued by the discovered issuer for our client ID and carry the nonce of the login.
+//! Discovery metadata is fetched once; the JWKS is fetched again when a token is signed with
+//! an unknown key, so that the provider can rotate its keys.
+
+use std::{sync::RwLock, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+use crate::common::{
+    config::Config,
+    http_client::{HttpClient, HttpError},
+};
+
+/// Maximum duration of a request to the provider.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Algorithms accepted for ID tokens. Symmetric algorithms are rejected, since they would
+/// have to be keyed with the client secret.
+const ID_TOKEN_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::EdDSA,
+];
+
+/// OidcError is returned when a login with the provider cannot be completed.
+#[derive(Error, Debug)]
+pub enum OidcError {
+    #[error("Request to the provider failed: {0}")]
+    Http(#[from] HttpError),
+
+    #[error("Invalid ID token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+
+    #[error("ID token signed with unsupported algorithm {0:?}")]
+    UnsupportedAlgorithm(Algorithm),
+
+    #[error("ID token signed with unknown key")]
+    UnknownKey,
+
+    #[error("ID token nonce does not match the login")]
+    NonceMismatch,
+}
+
+/// The endpoints of the provider, as published in its discovery document.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// The part of the token endpoint response we use.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The claims of an ID token we use besides the validated `iss`, `aud` and `exp`.
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    preferred_username: Option<String>,
+}
+
+/// The user authenticated by the provider.
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+}
+
+/// OidcProvider performs the requests of a login to the configured provider.
+pub struct OidcProvider {
+    discovery_url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scopes: String,
+    leeway: u64,
+    http: HttpClient,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<JwkSet>,
+}
+
+impl OidcProvider {
+    /// Creates the provider from the configuration.
+    /// Returns `None` if no discovery URL is configured.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let discovery_url = config.oidc_discovery_url.clone()?;
+        Some(Self {
+            discovery_url,
+            client_id: config.oidc_client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            redirect_uri: config.oidc_redirect_uri.clone(),
+            scopes: config.oidc_scopes.clone(),
+            leeway: config.jwt_leeway_seconds,
+            http: HttpClient::new(REQUEST_TIMEOUT),
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(JwkSet { keys: Vec::new() }),
+        })
+    }
+
+    /// Returns the URL of the authorization endpoint the browser is redirected to.
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("scope", self.scopes.as_str()),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", code_challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(|err| HttpError::InvalidUrl(err.to_string()))?;
+
+        let endpoint = &metadata.authorization_endpoint;
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        Ok(format!("{endpoint}{separator}{query}"))
+    }
+
+    /// Exchanges the authorization code for an ID token and returns the identity it asserts.
+    /// The client authenticates with HTTP Basic credentials if a client secret is configured.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<ExternalIdentity, OidcError> {
+        let metadata = self.metadata().await?;
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("code_verifier", code_verifier),
+            ("client_id", self.client_id.as_str()),
+        ];
+        let basic_auth = self
+            .client_secret
+            .as_deref()
+            .map(|secret| (self.client_id.as_str(), secret));
+        let response: TokenResponse = self
+            .http
+            .post_form(&metadata.token_endpoint, &form, basic_auth)
+            .await?;
+
+        let claims = self.verify_id_token(metadata, &response.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::NonceMismatch);
+        }
+
+        Ok(ExternalIdentity {
+            issuer: claims.iss,
+            subject: claims.sub,
+            email: claims.email,
+            email_verified: claims.email_verified.unwrap_or(false),
+            preferred_username: claims.preferred_username,
+        })
+    }
+
+    /// Returns the discovery metadata, fetching it on first use.
+    async fn metadata(&self) -> Result<&ProviderMetadata, OidcError> {
+        let metadata = self
+            .metadata
+            .get_or_try_init(|| self.http.get_json(&self.discovery_url))
+            .await?;
+        Ok(metadata)
+    }
+
+    /// Verifies the signature and the `iss`, `aud` and `exp` claims of the ID token.
+    async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(id_token)?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(OidcError::UnsupportedAlgorithm(header.alg));
+        }
+
+        let jwk = match self.find_key(header.kid.as_deref()) {
+            Some(jwk) => jwk,
+            None => {
+                let jwks: JwkSet = self.http.get_json(&metadata.jwks_uri).await?;
+                *self.jwks.write().unwrap_or_else(|e| e.into_inner()) = jwks;
+                self.find_key(header.kid.as_deref())
+                    .ok_or(OidcError::UnknownKey)?
+            }
+        };
+        let key = DecodingKey::from_jwk(&jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.leeway;
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        Ok(decode::<IdTokenClaims>(id_token, &key, &validation)?.claims)
+    }
+
+    /// Finds the key named by `kid` in the cached JWKS.
+    /// Without a `kid`, the only key of the set is used.
+    fn find_key(&self, kid: Option<&str>) -> Option<Jwk> {
+        let jwks = self.jwks.read().unwrap_or_else(|e| e.into_inner());
+        match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+            None => None,
+        }
+    }
+}
+
+/// Derives the S256 PKCE code challenge from the code verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_challenge() {
+        // BASE64URL(SHA256(verifier)) without padding.
+        let challenge = code_challenge("dBjftJeZ4CVP-mB92K2uhbUllF8HeRz9u4Ssk0rIjM");
+        assert_eq!(challenge, "recQrq1iNeraS3hNa6xwNwwPQ3X_d0PrcLomGuC71Do");
+        assert!(!challenge.contains('='));
+    }
+}
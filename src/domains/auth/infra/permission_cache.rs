@@ -0,0 +1,72 @@
+//! In-process cache of the role -> permissions mapping.
+//!
+//! Permissions are resolved from the `roles` claim on every authenticated request.
+//! The mapping changes rarely, so it is kept in memory and reloaded once it is
+//! older than the configured TTL.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::{common::rbac::Permissions, domains::auth::domain::model::RolePermission};
+
+#[derive(Default)]
+struct CacheState {
+    /// role name -> permission names
+    permissions_by_role: HashMap<String, HashSet<String>>,
+    loaded_at: Option<Instant>,
+}
+
+/// Thread-safe cache of the permissions granted by each role.
+pub struct PermissionCache {
+    state: RwLock<CacheState>,
+    ttl: Duration,
+}
+
+impl PermissionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: RwLock::new(CacheState::default()),
+            ttl,
+        }
+    }
+
+    /// Returns `true` if the cache has never been loaded or is older than the TTL.
+    pub fn is_stale(&self) -> bool {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state
+            .loaded_at
+            .is_none_or(|loaded_at| loaded_at.elapsed() >= self.ttl)
+    }
+
+    /// Replaces the cached contents with a fresh snapshot from the database.
+    pub fn replace(&self, role_permissions: Vec<RolePermission>) {
+        let mut permissions_by_role: HashMap<String, HashSet<String>> = HashMap::new();
+        for rp in role_permissions {
+            permissions_by_role
+                .entry(rp.role)
+                .or_default()
+                .insert(rp.permission);
+        }
+
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.permissions_by_role = permissions_by_role;
+        state.loaded_at = Some(Instant::now());
+    }
+
+    /// Returns the union of the permissions granted by the roles.
+    /// Unknown roles grant nothing.
+    pub fn resolve(&self, roles: &[String]) -> Permissions {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        let permissions = roles
+            .iter()
+            .filter_map(|role| state.permissions_by_role.get(role))
+            .flatten()
+            .cloned()
+            .collect();
+
+        Permissions::new(permissions)
+    }
+}
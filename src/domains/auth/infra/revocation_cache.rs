@@ -0,0 +1,103 @@
+//! In-process cache of the access token revocation list.
+//!
+//! The revocation list lives in Postgres so that it is shared between instances.
+//! To avoid a database round trip on every authenticated request, the active part
+//! of the list is kept in memory and reloaded once it is older than the configured TTL.
+//! Revocations made by this instance are applied to the cache immediately; revocations
+//! made by other instances become visible after the next reload.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::common::jwt::Claims;
+
+#[derive(Default)]
+struct CacheState {
+    revoked_jtis: HashSet<String>,
+    /// user id -> unix timestamp; tokens issued at or before it are revoked.
+    revoked_before: HashMap<String, i64>,
+    revoked_sessions: HashSet<String>,
+    loaded_at: Option<Instant>,
+}
+
+/// Thread-safe cache of revoked token IDs, per-user revocation cut-offs and revoked sessions.
+pub struct RevocationCache {
+    state: RwLock<CacheState>,
+    ttl: Duration,
+}
+
+impl RevocationCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: RwLock::new(CacheState::default()),
+            ttl,
+        }
+    }
+
+    /// Returns `true` if the cache has never been loaded or is older than the TTL.
+    pub fn is_stale(&self) -> bool {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state
+            .loaded_at
+            .is_none_or(|loaded_at| loaded_at.elapsed() >= self.ttl)
+    }
+
+    /// Replaces the cached contents with a fresh snapshot from the database.
+    pub fn replace(
+        &self,
+        revoked_jtis: Vec<String>,
+        revoked_before: Vec<(String, i64)>,
+        revoked_sessions: Vec<String>,
+    ) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.revoked_jtis = revoked_jtis.into_iter().collect();
+        state.revoked_before = revoked_before.into_iter().collect();
+        state.revoked_sessions = revoked_sessions.into_iter().collect();
+        state.loaded_at = Some(Instant::now());
+    }
+
+    /// Adds a single revoked token ID.
+    pub fn insert_jti(&self, jti: String) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.revoked_jtis.insert(jti);
+    }
+
+    /// Records a per-user cut-off.
+    pub fn insert_user_cutoff(&self, user_id: String, revoked_before: i64) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.revoked_before.insert(user_id, revoked_before);
+    }
+
+    /// Adds a single revoked session ID.
+    pub fn insert_session(&self, session_id: String) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.revoked_sessions.insert(session_id);
+    }
+
+    /// Checks the claims against the cached revocation list.
+    pub fn is_revoked(&self, claims: &Claims) -> bool {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+
+        if state.revoked_jtis.contains(&claims.jti) {
+            return true;
+        }
+
+        if claims
+            .sid
+            .as_ref()
+            .is_some_and(|sid| state.revoked_sessions.contains(sid))
+        {
+            return true;
+        }
+
+        // Impersonation tokens are also revoked with the sessions of the impersonating admin.
+        [Some(&claims.sub), claims.act.as_ref().map(|act| &act.sub)]
+            .into_iter()
+            .flatten()
+            .filter_map(|user_id| state.revoked_before.get(user_id))
+            .any(|cutoff| claims.iat as i64 <= *cutoff)
+    }
+}
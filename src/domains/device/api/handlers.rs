@@ -1,5 +1,10 @@
 use crate::common::dto::RestApiResponse;
-use crate::common::{app_state::AppState, error::AppError, jwt::Claims};
+use crate::common::{
+    app_state::AppState,
+    error::AppError,
+    etag::{etag_header, IfMatch},
+    jwt::Claims,
+};
 
 use crate::domains::device::dto::device_dto::{
     CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
@@ -15,15 +20,24 @@ use axum::{
 #[utoipa::path(
     get,
     path = "/device/{id}",
-    responses((status = 200, description = "Get device by ID", body = DeviceDto)),
+    responses(
+        (status = 200, description = "Get device by ID", body = DeviceDto,
+            headers(("ETag" = String, description = "Version of the device, for `If-Match`"))),
+        (status = 403, description = "Missing `device:read` permission or not the device owner")
+    ),
+    security(("bearer_auth" = ["device:read"])),
     tag = "Devices"
 )]
 pub async fn get_device_by_id(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let device = state.device_service.get_device_by_id(id).await?;
-    Ok(RestApiResponse::success(device))
+    let device = state.device_service.get_device_by_id(&claims, id).await?;
+    Ok((
+        etag_header(device.version),
+        RestApiResponse::success(device),
+    ))
 }
 
 /// This function creates a router for getting all devices
@@ -31,11 +45,18 @@ pub async fn get_device_by_id(
 #[utoipa::path(
     get,
     path = "/device",
-    responses((status = 200, description = "List all devices", body = [DeviceDto])),
+    responses(
+        (status = 200, description = "List all devices", body = [DeviceDto]),
+        (status = 403, description = "Missing `device:read` permission or not the device owner")
+    ),
+    security(("bearer_auth" = ["device:read"])),
     tag = "Devices"
 )]
-pub async fn get_devices(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let devices = state.device_service.get_devices().await?;
+pub async fn get_devices(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let devices = state.device_service.get_devices(&claims).await?;
     Ok(RestApiResponse::success(devices))
 }
 
@@ -46,7 +67,11 @@ pub async fn get_devices(State(state): State<AppState>) -> Result<impl IntoRespo
     post,
     path = "/device",
     request_body = CreateDeviceDto,
-    responses((status = 200, description = "Create a new device", body = DeviceDto)),
+    responses(
+        (status = 200, description = "Create a new device", body = DeviceDto),
+        (status = 403, description = "Missing `device:create` permission or not the device owner")
+    ),
+    security(("bearer_auth" = ["device:create"])),
     tag = "Devices"
 )]
 pub async fn create_device(
@@ -54,11 +79,11 @@ pub async fn create_device(
     Extension(claims): Extension<Claims>,
     Json(payload): Json<CreateDeviceDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Set the modified_by field to the current user's ID.
+    // Set the modified_by field to the acting user's ID (the admin when impersonating).
     let mut payload = payload;
-    payload.modified_by = claims.sub.clone().to_string();
+    payload.modified_by = claims.actor_id().to_string();
 
-    let device = state.device_service.create_device(payload).await?;
+    let device = state.device_service.create_device(&claims, payload).await?;
     Ok(RestApiResponse::success(device))
 }
 
@@ -69,21 +94,35 @@ pub async fn create_device(
     put,
     path = "/device/{id}",
     request_body = UpdateDeviceDto,
-    responses((status = 200, description = "Update device", body = DeviceDto)),
+    responses(
+        (status = 200, description = "Update device", body = DeviceDto,
+            headers(("ETag" = String, description = "New version of the device"))),
+        (status = 403, description = "Missing `device:update` permission or not the device owner"),
+        (status = 412, description = "Device was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["device:update"])),
     tag = "Devices"
 )]
 pub async fn update_device(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
     Json(payload): Json<UpdateDeviceDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Set the modified_by field to the current user's ID.
+    // Set the modified_by field to the acting user's ID (the admin when impersonating).
     let mut payload = payload;
-    payload.modified_by = claims.sub.clone().to_string();
+    payload.modified_by = claims.actor_id().to_string();
 
-    let device = state.device_service.update_device(id, payload).await?;
-    Ok(RestApiResponse::success(device))
+    let device = state
+        .device_service
+        .update_device(&claims, id, payload, if_match)
+        .await?;
+    Ok((
+        etag_header(device.version),
+        RestApiResponse::success(device),
+    ))
 }
 
 /// This function creates a router for deleting a device
@@ -92,14 +131,25 @@ pub async fn update_device(
 #[utoipa::path(
     delete,
     path = "/device/{id}",
-    responses((status = 200, description = "Device deleted")),
+    responses(
+        (status = 200, description = "Device deleted"),
+        (status = 403, description = "Missing `device:delete` permission or not the device owner"),
+        (status = 412, description = "Device was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["device:delete"])),
     tag = "Devices"
 )]
 pub async fn delete_device(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
 ) -> Result<impl IntoResponse, AppError> {
-    let message = state.device_service.delete_device(id).await?;
+    let message = state
+        .device_service
+        .delete_device(&claims, id, if_match)
+        .await?;
 
     Ok(RestApiResponse::success_with_message(message, ()))
 }
@@ -111,7 +161,11 @@ pub async fn delete_device(
     put,
     path = "/device/batch/{user_id}",
     request_body = UpdateManyDevicesDto,
-    responses((status = 200, description = "Batch update devices")),
+    responses(
+        (status = 200, description = "Batch update devices"),
+        (status = 403, description = "Missing `device:update` permission or not the device owner")
+    ),
+    security(("bearer_auth" = ["device:update"])),
     tag = "Devices"
 )]
 pub async fn update_many_devices(
@@ -120,11 +174,11 @@ pub async fn update_many_devices(
     Path(user_id): Path<String>,
     Json(payload): Json<UpdateManyDevicesDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let modified_by = claims.sub.clone().to_string();
+    let modified_by = claims.actor_id().to_string();
 
     let message = state
         .device_service
-        .update_many_devices(user_id, modified_by, payload)
+        .update_many_devices(&claims, user_id, modified_by, payload)
         .await?;
 
     Ok(RestApiResponse::success_with_message(message, ()))
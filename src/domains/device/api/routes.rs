@@ -1,9 +1,13 @@
 use super::handlers::*;
 use crate::{
-    common::app_state::AppState,
+    common::{
+        app_state::AppState,
+        rbac::{require_permission, DEVICE_CREATE, DEVICE_DELETE, DEVICE_READ, DEVICE_UPDATE},
+    },
     domains::device::dto::device_dto::{CreateDeviceDto, DeviceDto, UpdateDeviceDto},
 };
 use axum::{
+    middleware,
     routing::{delete, get, post, put},
     Router,
 };
@@ -53,12 +57,33 @@ impl utoipa::Modify for DeviceApiDoc {
 
 /// This function creates a router for the device routes.
 /// It defines the routes and their corresponding handlers.
+/// Each route requires the permission documented on its handler.
 pub fn device_routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(get_devices))
-        .route("/", post(create_device))
-        .route("/{id}", get(get_device_by_id))
-        .route("/{id}", put(update_device))
-        .route("/{id}", delete(delete_device))
-        .route("/batch/{user_id}", put(update_many_devices))
+        .route(
+            "/",
+            get(get_devices).route_layer(middleware::from_fn(require_permission(DEVICE_READ))),
+        )
+        .route(
+            "/",
+            post(create_device).route_layer(middleware::from_fn(require_permission(DEVICE_CREATE))),
+        )
+        .route(
+            "/{id}",
+            get(get_device_by_id).route_layer(middleware::from_fn(require_permission(DEVICE_READ))),
+        )
+        .route(
+            "/{id}",
+            put(update_device).route_layer(middleware::from_fn(require_permission(DEVICE_UPDATE))),
+        )
+        .route(
+            "/{id}",
+            delete(delete_device)
+                .route_layer(middleware::from_fn(require_permission(DEVICE_DELETE))),
+        )
+        .route(
+            "/batch/{user_id}",
+            put(update_many_devices)
+                .route_layer(middleware::from_fn(require_permission(DEVICE_UPDATE))),
+        )
 }
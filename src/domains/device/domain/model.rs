@@ -145,4 +145,5 @@ pub struct Device {
     pub created_at: Option<DateTime<Utc>>,
     pub modified_by: Option<String>,
     pub modified_at: Option<DateTime<Utc>>,
+    pub version: i64,
 }
@@ -17,9 +17,27 @@ pub trait DeviceRepository: Send + Sync {
     /// Retrieves all devices from the database.
     async fn find_all(&self, pool: PgPool) -> Result<Vec<Device>, sqlx::Error>;
 
+    /// Retrieves all devices owned by the user.
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<Device>, sqlx::Error>;
+
+    /// Retrieves the devices with the given identifiers.
+    async fn find_by_ids(&self, pool: PgPool, ids: Vec<String>)
+        -> Result<Vec<Device>, sqlx::Error>;
+
     /// Finds a device by its unique identifier.
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<Device>, sqlx::Error>;
 
+    /// Finds a device by its unique identifier and locks it until the transaction ends.
+    async fn find_by_id_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<Device>, sqlx::Error>;
+
     /// Creates a new device record in the database within the given transaction.
     async fn create(
         &self,
@@ -36,6 +54,7 @@ pub trait DeviceRepository: Send + Sync {
     ) -> Result<Option<Device>, sqlx::Error>;
 
     /// Updates multiple devices for a given user with the specified changes.
+    /// Existing devices owned by another user are left untouched.
     async fn update_many(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -6,7 +6,7 @@ use std::sync::Arc;
 use sqlx::PgPool;
 
 use crate::{
-    common::error::AppError,
+    common::{error::AppError, etag::IfMatch, jwt::Claims},
     domains::device::dto::device_dto::{
         CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
     },
@@ -16,6 +16,7 @@ use crate::{
 /// Trait defining the contract for device-related business operations.
 /// This includes creating, retrieving, updating, and deleting devices,
 /// as well as batch updates for user-associated devices.
+/// Users may only access their own devices; admins may access every device.
 pub trait DeviceServiceTrait: Send + Sync {
     /// constructor for the service.
     fn create_service(pool: PgPool) -> Arc<dyn DeviceServiceTrait>
@@ -23,27 +24,39 @@ pub trait DeviceServiceTrait: Send + Sync {
         Self: Sized;
 
     /// Retrieves a device by its unique ID.
-    async fn get_device_by_id(&self, id: String) -> Result<DeviceDto, AppError>;
+    async fn get_device_by_id(&self, claims: &Claims, id: String) -> Result<DeviceDto, AppError>;
 
-    /// Retrieves a list of all devices.
-    async fn get_devices(&self) -> Result<Vec<DeviceDto>, AppError>;
+    /// Retrieves a list of all devices visible to the user.
+    async fn get_devices(&self, claims: &Claims) -> Result<Vec<DeviceDto>, AppError>;
 
     /// Creates a new device from the provided payload.
-    async fn create_device(&self, payload: CreateDeviceDto) -> Result<DeviceDto, AppError>;
+    async fn create_device(
+        &self,
+        claims: &Claims,
+        payload: CreateDeviceDto,
+    ) -> Result<DeviceDto, AppError>;
 
-    /// Updates an existing device with new data.
+    /// Updates an existing device with new data, if it matches the precondition.
     async fn update_device(
         &self,
+        claims: &Claims,
         id: String,
         payload: UpdateDeviceDto,
+        if_match: IfMatch,
     ) -> Result<DeviceDto, AppError>;
 
-    /// Deletes a device by its ID.
-    async fn delete_device(&self, id: String) -> Result<String, AppError>;
+    /// Deletes a device by its ID, if it matches the precondition.
+    async fn delete_device(
+        &self,
+        claims: &Claims,
+        id: String,
+        if_match: IfMatch,
+    ) -> Result<String, AppError>;
 
     /// Applies updates to multiple devices owned by a user.
     async fn update_many_devices(
         &self,
+        claims: &Claims,
         user_id: String,
         modified_by: String,
         payload: UpdateManyDevicesDto,
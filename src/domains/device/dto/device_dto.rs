@@ -20,6 +20,8 @@ pub struct DeviceDto {
     pub modified_by: Option<String>,
     #[serde(with = "crate::common::ts_format::option")]
     pub modified_at: Option<DateTime<Utc>>,
+    /// Incremented on every change; sent as `ETag` and expected in `If-Match`.
+    pub version: i64,
 }
 
 impl From<Device> for DeviceDto {
@@ -35,6 +37,7 @@ impl From<Device> for DeviceDto {
             created_at: device.created_at,
             modified_by: device.modified_by,
             modified_at: device.modified_at,
+            version: device.version,
         }
     }
 }
@@ -22,7 +22,8 @@ const FIND_DEVICE_INFO_QUERY: &str = r#"
         created_by,
         created_at,
         modified_by,
-        modified_at
+        modified_at,
+        version
     from
         devices
     where
@@ -44,7 +45,8 @@ impl DeviceRepository for DeviceRepo {
                 created_by,
                 created_at,
                 modified_by,
-                modified_at
+                modified_at,
+                version
             from
                 devices
             "#,
@@ -55,6 +57,70 @@ impl DeviceRepository for DeviceRepo {
         Ok(devices)
     }
 
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<Device>, sqlx::Error> {
+        let devices = sqlx::query_as::<_, Device>(
+            r#"
+            select
+                id,
+                user_id,
+                name,
+                status,
+                device_os,
+                registered_at,
+                created_by,
+                created_at,
+                modified_by,
+                modified_at,
+                version
+            from
+                devices
+            where
+                user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(devices)
+    }
+
+    async fn find_by_ids(
+        &self,
+        pool: PgPool,
+        ids: Vec<String>,
+    ) -> Result<Vec<Device>, sqlx::Error> {
+        let devices = sqlx::query_as::<_, Device>(
+            r#"
+            select
+                id,
+                user_id,
+                name,
+                status,
+                device_os,
+                registered_at,
+                created_by,
+                created_at,
+                modified_by,
+                modified_at,
+                version
+            from
+                devices
+            where
+                id = ANY($1)
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(devices)
+    }
+
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<Device>, sqlx::Error> {
         let device = sqlx::query_as::<_, Device>(FIND_DEVICE_INFO_QUERY)
             .bind(id)
@@ -64,6 +130,19 @@ impl DeviceRepository for DeviceRepo {
         Ok(device)
     }
 
+    async fn find_by_id_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<Device>, sqlx::Error> {
+        let device = sqlx::query_as::<_, Device>(&format!("{FIND_DEVICE_INFO_QUERY} FOR UPDATE"))
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        Ok(device)
+    }
+
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -110,7 +189,7 @@ impl DeviceRepository for DeviceRepo {
         if existing.is_some() {
             let mut builder = QueryBuilder::<_>::new("UPDATE devices SET ");
 
-            builder.push(" modified_at = NOW()");
+            builder.push(" modified_at = NOW(), version = version + 1");
 
             if let Some(value) = device.user_id {
                 builder.push(", user_id = ").push_bind(value);
@@ -188,7 +267,9 @@ impl DeviceRepository for DeviceRepo {
             status = EXCLUDED.status,
             device_os = EXCLUDED.device_os,
             modified_by = EXCLUDED.modified_by,
-            modified_at = EXCLUDED.modified_at
+            modified_at = EXCLUDED.modified_at,
+            version = devices.version + 1
+            WHERE devices.user_id = EXCLUDED.user_id
             "#,
         );
 
@@ -1,14 +1,19 @@
 use crate::{
-    common::error::AppError,
+    common::{
+        error::AppError,
+        etag::IfMatch,
+        jwt::Claims,
+        rbac::{ensure_owner_or_admin, is_admin},
+    },
     domains::device::{
-        domain::{repository::DeviceRepository, service::DeviceServiceTrait},
+        domain::{model::Device, repository::DeviceRepository, service::DeviceServiceTrait},
         dto::device_dto::{CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto},
         infra::impl_repository::DeviceRepo,
     },
 };
 
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
 
 /// Service struct for handling device-related operations
@@ -32,20 +37,23 @@ impl DeviceServiceTrait for DeviceService {
     }
 
     /// get device by id
-    async fn get_device_by_id(&self, id: String) -> Result<DeviceDto, AppError> {
-        match self.repo.find_by_id(self.pool.clone(), id).await {
-            Ok(Some(device)) => Ok(DeviceDto::from(device)),
-            Ok(None) => Err(AppError::NotFound("Device not found".into())),
-            Err(err) => {
-                tracing::error!("Error fetching device: {err}");
-                Err(AppError::DatabaseError(err))
-            }
-        }
+    async fn get_device_by_id(&self, claims: &Claims, id: String) -> Result<DeviceDto, AppError> {
+        let device = self.find_owned_device(claims, id).await?;
+        Ok(DeviceDto::from(device))
     }
 
     /// get devices
-    async fn get_devices(&self) -> Result<Vec<DeviceDto>, AppError> {
-        match self.repo.find_all(self.pool.clone()).await {
+    /// Admins see every device, other users only their own.
+    async fn get_devices(&self, claims: &Claims) -> Result<Vec<DeviceDto>, AppError> {
+        let devices = if is_admin(claims) {
+            self.repo.find_all(self.pool.clone()).await
+        } else {
+            self.repo
+                .find_by_user_id(self.pool.clone(), claims.sub.clone())
+                .await
+        };
+
+        match devices {
             Ok(devices) => {
                 let device_dtos: Vec<DeviceDto> = devices.into_iter().map(Into::into).collect();
                 Ok(device_dtos)
@@ -58,7 +66,13 @@ impl DeviceServiceTrait for DeviceService {
     }
 
     /// create device
-    async fn create_device(&self, payload: CreateDeviceDto) -> Result<DeviceDto, AppError> {
+    async fn create_device(
+        &self,
+        claims: &Claims,
+        payload: CreateDeviceDto,
+    ) -> Result<DeviceDto, AppError> {
+        ensure_owner_or_admin(claims, &payload.user_id)?;
+
         let mut tx = self.pool.begin().await?;
         match self.repo.create(&mut tx, payload).await {
             Ok(device) => {
@@ -76,10 +90,19 @@ impl DeviceServiceTrait for DeviceService {
     /// update device
     async fn update_device(
         &self,
+        claims: &Claims,
         id: String,
         payload: UpdateDeviceDto,
+        if_match: IfMatch,
     ) -> Result<DeviceDto, AppError> {
         let mut tx = self.pool.begin().await?;
+        self.lock_owned_device(&mut tx, claims, id.clone(), &if_match)
+            .await?;
+        // Only admins may hand a device over to another user.
+        if let Some(user_id) = &payload.user_id {
+            ensure_owner_or_admin(claims, user_id)?;
+        }
+
         match self.repo.update(&mut tx, id, payload).await {
             Ok(Some(device)) => {
                 tx.commit().await?;
@@ -98,8 +121,16 @@ impl DeviceServiceTrait for DeviceService {
     }
 
     /// delete device
-    async fn delete_device(&self, id: String) -> Result<String, AppError> {
+    async fn delete_device(
+        &self,
+        claims: &Claims,
+        id: String,
+        if_match: IfMatch,
+    ) -> Result<String, AppError> {
         let mut tx = self.pool.begin().await?;
+        self.lock_owned_device(&mut tx, claims, id.clone(), &if_match)
+            .await?;
+
         match self.repo.delete(&mut tx, id).await {
             Ok(true) => {
                 tx.commit().await?;
@@ -120,10 +151,33 @@ impl DeviceServiceTrait for DeviceService {
     /// batch update device
     async fn update_many_devices(
         &self,
+        claims: &Claims,
         user_id: String,
         modified_by: String,
         payload: UpdateManyDevicesDto,
     ) -> Result<String, AppError> {
+        ensure_owner_or_admin(claims, &user_id)?;
+
+        // Existing devices in the batch must belong to the same user.
+        let ids: Vec<String> = payload
+            .devices
+            .iter()
+            .filter_map(|device| device.id.clone())
+            .collect();
+        if !ids.is_empty() {
+            let existing = self
+                .repo
+                .find_by_ids(self.pool.clone(), ids)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Error fetching devices: {err}");
+                    AppError::DatabaseError(err)
+                })?;
+            if existing.iter().any(|device| device.user_id != user_id) {
+                return Err(AppError::Forbidden);
+            }
+        }
+
         let mut tx = self.pool.begin().await?;
         match self
             .repo
@@ -142,3 +196,51 @@ impl DeviceServiceTrait for DeviceService {
         }
     }
 }
+
+/// Internal helper methods defined on `DeviceService`.
+impl DeviceService {
+    /// Loads a device and applies the ownership policy.
+    /// Returns `NotFound` if the device does not exist and `Forbidden`
+    /// if it belongs to another user and the caller is not an admin.
+    async fn find_owned_device(&self, claims: &Claims, id: String) -> Result<Device, AppError> {
+        let device = self
+            .repo
+            .find_by_id(self.pool.clone(), id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error fetching device: {err}");
+                AppError::DatabaseError(err)
+            })?
+            .ok_or_else(|| AppError::NotFound("Device not found".into()))?;
+
+        ensure_owner_or_admin(claims, &device.user_id)?;
+
+        Ok(device)
+    }
+
+    /// Loads a device for a change and locks it until the transaction ends.
+    /// Applies the ownership policy like `find_owned_device`, then the `If-Match`
+    /// precondition, which fails with `PreconditionFailed` if the device has changed.
+    async fn lock_owned_device(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        claims: &Claims,
+        id: String,
+        if_match: &IfMatch,
+    ) -> Result<Device, AppError> {
+        let device = self
+            .repo
+            .find_by_id_for_update(tx, id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error fetching device: {err}");
+                AppError::DatabaseError(err)
+            })?
+            .ok_or_else(|| AppError::NotFound("Device not found".into()))?;
+
+        ensure_owner_or_admin(claims, &device.user_id)?;
+        if_match.check(device.version)?;
+
+        Ok(device)
+    }
+}
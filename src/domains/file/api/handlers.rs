@@ -1,9 +1,13 @@
-use crate::common::{app_state::AppState, dto::RestApiResponse, error::AppError};
+use crate::{
+    common::{app_state::AppState, dto::RestApiResponse, error::AppError, jwt::Claims},
+    domains::file::dto::file_dto::UploadedFileDto,
+};
 use axum::{
     body::Body,
     extract::{Path, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
+    Extension,
 };
 
 use std::path::Path as FilePath;
@@ -15,16 +19,47 @@ use tokio_util::io::ReaderStream;
 #[utoipa::path(
     get,
     path = "/file/{file_id}",
-    responses((status = 200, description = "Serve protected file")),
+    responses(
+        (status = 200, description = "Serve protected file"),
+        (status = 403, description = "Missing `file:read` permission or not the file owner")
+    ),
+    security(("bearer_auth" = ["file:read"])),
     tag = "Files"
 )]
 /// Serve a protected file from the server's filesystem.
 pub async fn serve_protected_file(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(file_id): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let file_metadata = state.file_service.get_file_metadata(file_id).await?;
+    let file_metadata = state
+        .file_service
+        .get_file_metadata(&claims, file_id)
+        .await?;
+
+    file_response(&state, file_metadata).await
+}
+
+/// Serves a private asset by its path below `ASSETS_PRIVATE_URL`.
+/// Like `serve_protected_file`, only the owner of the file and admins may read it.
+pub async fn serve_private_asset(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(file_relative_path): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let file_metadata = state
+        .file_service
+        .get_file_metadata_by_path(&claims, file_relative_path)
+        .await?;
+
+    file_response(&state, file_metadata).await
+}
 
+/// Streams a stored file with the content type and name of its metadata.
+async fn file_response(
+    state: &AppState,
+    file_metadata: Option<UploadedFileDto>,
+) -> Result<Response, AppError> {
     // If the file is not found, return a 404.
     let file_metadata = file_metadata.ok_or_else(|| AppError::NotFound("File not found".into()))?;
 
@@ -72,14 +107,19 @@ pub async fn serve_protected_file(
 #[utoipa::path(
     delete,
     path = "/file/{file_id}",
-    responses((status = 200, description = "Delete file")),
+    responses(
+        (status = 200, description = "Delete file"),
+        (status = 403, description = "Missing `file:delete` permission or not the file owner")
+    ),
+    security(("bearer_auth" = ["file:delete"])),
     tag = "Files"
 )]
 /// Delete a file from the server's filesystem and database.
 pub async fn delete_file(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(file_id): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let message = state.file_service.delete_file(file_id).await?;
+    let message = state.file_service.delete_file(&claims, file_id).await?;
     Ok(RestApiResponse::success_with_message(message, ()))
 }
@@ -1,6 +1,13 @@
 use super::handlers::*;
-use crate::{common::app_state::AppState, domains::file::dto::file_dto::UploadedFileDto};
+use crate::{
+    common::{
+        app_state::AppState,
+        rbac::{require_permission, FILE_DELETE, FILE_READ},
+    },
+    domains::file::dto::file_dto::UploadedFileDto,
+};
 use axum::{
+    middleware,
     routing::{delete, get},
     Router,
 };
@@ -44,8 +51,26 @@ impl utoipa::Modify for FileApiDoc {
     }
 }
 
+/// This function creates a router for the file routes.
+/// Each route requires the permission documented on its handler.
 pub fn file_routes() -> Router<AppState> {
     Router::new()
-        .route("/{file_id}", get(serve_protected_file))
-        .route("/{file_id}", delete(delete_file))
+        .route(
+            "/{file_id}",
+            get(serve_protected_file)
+                .route_layer(middleware::from_fn(require_permission(FILE_READ))),
+        )
+        .route(
+            "/{file_id}",
+            delete(delete_file).route_layer(middleware::from_fn(require_permission(FILE_DELETE))),
+        )
+}
+
+/// This function creates a router for the private assets below `assets_private_url`.
+/// Like `GET /file/{file_id}`, they require `file:read` and are only served to their owner.
+pub fn private_asset_routes(assets_private_url: &str) -> Router<AppState> {
+    Router::new().route(
+        &format!("{assets_private_url}/{{*file_relative_path}}"),
+        get(serve_private_asset).route_layer(middleware::from_fn(require_permission(FILE_READ))),
+    )
 }
@@ -26,10 +26,18 @@ pub trait FileRepository {
         id: String,
     ) -> Result<Option<UploadedFile>, sqlx::Error>;
 
-    /// Finds a file record associated with a specific user ID.
-    async fn find_by_user_id(
+    /// Finds a file record by the path of the file relative to the private assets directory.
+    async fn find_by_relative_path(
         &self,
         pool: PgPool,
+        file_relative_path: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error>;
+
+    /// Deletes the profile picture record of a user using a transaction.
+    /// Returns the deleted record, or `None` if the user has no profile picture.
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
         user_id: String,
     ) -> Result<Option<UploadedFile>, sqlx::Error>;
 
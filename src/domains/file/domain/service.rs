@@ -7,7 +7,7 @@ use async_trait::async_trait;
 use sqlx::{PgPool, Postgres, Transaction};
 
 use crate::{
-    common::{config::Config, error::AppError},
+    common::{config::Config, error::AppError, jwt::Claims},
     domains::file::dto::file_dto::{UploadFileDto, UploadedFileDto},
 };
 
@@ -15,6 +15,7 @@ use crate::{
 /// Trait defining the contract for file-related operations.
 /// Used to abstract file handling logic such as uploading,
 /// retrieving metadata, and deleting files.
+/// Users may only access their own files; admins may access every file.
 pub trait FileServiceTrait: Send + Sync {
     /// constructor for the service.
     fn create_service(config: Config, pool: PgPool) -> Arc<dyn FileServiceTrait>
@@ -29,10 +30,33 @@ pub trait FileServiceTrait: Send + Sync {
         upload_file_dto: &UploadFileDto,
     ) -> Result<Option<UploadedFileDto>, AppError>;
 
+    /// Deletes the metadata of a user's profile picture within an active transaction.
+    /// Returns the deleted metadata, or `None` if the user has no profile picture.
+    /// The file itself must be removed with `remove_stored_files` once committed.
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<UploadedFileDto>, AppError>;
+
     /// Retrieves file metadata by its file ID.
-    async fn get_file_metadata(&self, file_id: String)
-        -> Result<Option<UploadedFileDto>, AppError>;
+    async fn get_file_metadata(
+        &self,
+        claims: &Claims,
+        file_id: String,
+    ) -> Result<Option<UploadedFileDto>, AppError>;
+
+    /// Retrieves file metadata by the path of the file relative to the private assets directory.
+    async fn get_file_metadata_by_path(
+        &self,
+        claims: &Claims,
+        file_relative_path: String,
+    ) -> Result<Option<UploadedFileDto>, AppError>;
 
     /// Deletes a file by its file ID and returns a confirmation message.
-    async fn delete_file(&self, file_id: String) -> Result<String, AppError>;
+    async fn delete_file(&self, claims: &Claims, file_id: String) -> Result<String, AppError>;
+
+    /// Removes stored files from the filesystem after their metadata has been deleted.
+    /// Failures are only logged, since the records are already gone.
+    fn remove_stored_files(&self, file_relative_paths: &[String]);
 }
@@ -56,25 +56,26 @@ impl FileRepository for FileRepo {
         Ok(inserted_file)
     }
 
-    async fn find_by_user_id(
+    async fn delete_profile_picture(
         &self,
-        pool: PgPool,
+        tx: &mut Transaction<'_, Postgres>,
         user_id: String,
     ) -> Result<Option<UploadedFile>, sqlx::Error> {
         let uploaded_file = sqlx::query_as!(
             UploadedFile,
             r#"
-            SELECT id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+            DELETE FROM uploaded_files
+            WHERE user_id = $1
+              AND file_type = 'profile_picture'
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
                 content_type, file_size, file_type, created_by,
                 created_at,
                 modified_by,
                 modified_at
-            FROM uploaded_files 
-            WHERE user_id = $1
             "#,
             user_id
         )
-        .fetch_optional(&pool)
+        .fetch_optional(&mut **tx)
         .await?;
 
         Ok(uploaded_file)
@@ -93,6 +94,30 @@ impl FileRepository for FileRepo {
         Ok(uploaded_file)
     }
 
+    async fn find_by_relative_path(
+        &self,
+        pool: PgPool,
+        file_relative_path: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error> {
+        let uploaded_file = sqlx::query_as!(
+            UploadedFile,
+            r#"
+            SELECT id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                content_type, file_size, file_type, created_by,
+                created_at,
+                modified_by,
+                modified_at
+            FROM uploaded_files
+            WHERE file_relative_path = $1
+            "#,
+            file_relative_path
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(uploaded_file)
+    }
+
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -1,4 +1,4 @@
-use crate::common::{config::Config, error::AppError};
+use crate::common::{config::Config, error::AppError, jwt::Claims, rbac::ensure_owner_or_admin};
 use crate::domains::file::domain::model::FileType;
 use crate::domains::file::domain::repository::FileRepository;
 use crate::domains::file::domain::service::FileServiceTrait;
@@ -35,6 +35,7 @@ impl FileServiceTrait for FileService {
 
     /// Uploads a profile picture for a user.
     /// Validates the file, writes it to disk, and stores its metadata in the database.
+    /// The file is removed from disk again if its metadata cannot be stored.
     /// Returns the uploaded file's metadata.
     async fn process_profile_picture_upload(
         &self,
@@ -48,21 +49,22 @@ impl FileServiceTrait for FileService {
             return Err(AppError::InvalidFileData);
         }
 
+        let Some(user_id) = upload_file_dto.user_id.clone() else {
+            return Err(AppError::ValidationError("User ID is missing".into()));
+        };
+
         let (unique_filename, file_relative_path, file_path) =
             self.build_file_path(&file_dto.original_filename);
 
         self.write_file_to_disk(&file_path, &file_dto.data)?;
 
-        let file_url = format!(
-            "{}/profile/{}",
-            self.config.assets_private_url, &unique_filename
-        );
+        let file_url = format!("{}/{}", self.config.assets_private_url, &file_relative_path);
 
         let create_file_dto = CreateFileDto {
-            user_id: upload_file_dto.user_id.clone(),
+            user_id: Some(user_id),
             file_name: unique_filename,
             origin_file_name: file_dto.original_filename.clone(),
-            file_relative_path,
+            file_relative_path: file_relative_path.clone(),
             file_url,
             content_type: file_dto.content_type.clone(),
             file_size: file_dto.data.len() as u32,
@@ -70,24 +72,43 @@ impl FileServiceTrait for FileService {
             modified_by: upload_file_dto.modified_by.clone(),
         };
 
-        self.repo
+        let uploaded_file = self
+            .repo
             .create_file(tx, create_file_dto)
             .await
             .map_err(|err| {
                 tracing::error!("Error uploading file: {}", err);
+                self.remove_stored_files(&[file_relative_path]);
                 AppError::DatabaseError(err)
             })?;
 
-        if let Some(user_id) = &upload_file_dto.user_id {
-            self.get_file_by_user(user_id.clone()).await
-        } else {
-            Err(AppError::ValidationError("User ID is missing".into()))
-        }
+        Ok(Some(UploadedFileDto::from(uploaded_file)))
+    }
+
+    /// Deletes the metadata of a user's profile picture within the transaction.
+    /// The file stays on disk, so that it is still served if the transaction is rolled back.
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<UploadedFileDto>, AppError> {
+        let uploaded_file = self
+            .repo
+            .delete_profile_picture(tx, user_id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error deleting profile picture: {}", err);
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(uploaded_file.map(UploadedFileDto::from))
     }
 
     /// Retrieves the metadata of a file by its id.
+    /// Returns `Forbidden` if the file belongs to another user and the caller is not an admin.
     async fn get_file_metadata(
         &self,
+        claims: &Claims,
         file_id: String,
     ) -> Result<Option<UploadedFileDto>, AppError> {
         let uploaded_file = self
@@ -100,16 +121,46 @@ impl FileServiceTrait for FileService {
             });
 
         match uploaded_file {
-            Ok(Some(file)) => Ok(Some(UploadedFileDto::from(file))),
+            Ok(Some(file)) => {
+                ensure_owner_or_admin(claims, &file.user_id)?;
+                Ok(Some(UploadedFileDto::from(file)))
+            }
             Ok(None) => Ok(None),
             Err(err) => Err(err),
         }
     }
 
+    /// Retrieves file metadata by its relative path, so that private assets are only
+    /// served to their owner.
+    /// Returns `Forbidden` if the file belongs to another user and the caller is not an admin.
+    async fn get_file_metadata_by_path(
+        &self,
+        claims: &Claims,
+        file_relative_path: String,
+    ) -> Result<Option<UploadedFileDto>, AppError> {
+        let uploaded_file = self
+            .repo
+            .find_by_relative_path(self.pool.clone(), file_relative_path)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving file: {}", err);
+                AppError::DatabaseError(err)
+            })?;
+
+        match uploaded_file {
+            Some(file) => {
+                ensure_owner_or_admin(claims, &file.user_id)?;
+                Ok(Some(UploadedFileDto::from(file)))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Deletes a file by its id.
     /// Removes the file from the filesystem and deletes its metadata from the database.
     /// Returns a success message if the deletion was successful.
-    async fn delete_file(&self, file_id: String) -> Result<String, AppError> {
+    /// Returns `Forbidden` if the file belongs to another user and the caller is not an admin.
+    async fn delete_file(&self, claims: &Claims, file_id: String) -> Result<String, AppError> {
         let mut tx = self.pool.begin().await?;
 
         let to_delete_file = self
@@ -121,9 +172,11 @@ impl FileServiceTrait for FileService {
                 AppError::DatabaseError(err)
             })?;
 
-        if to_delete_file.is_none() {
+        let Some(to_delete_file) = to_delete_file else {
             return Err(AppError::NotFound("File not found".into()));
-        }
+        };
+
+        ensure_owner_or_admin(claims, &to_delete_file.user_id)?;
 
         let deletion_result = self.repo.delete(&mut tx, file_id).await.map_err(|err| {
             tracing::error!("Error deleting file: {}", err);
@@ -135,7 +188,7 @@ impl FileServiceTrait for FileService {
         }
 
         let file_path = FilePath::new(self.config.assets_private_path.as_str())
-            .join(to_delete_file.unwrap().file_relative_path);
+            .join(to_delete_file.file_relative_path);
 
         if std::fs::remove_file(&file_path).is_err() {
             tracing::error!(
@@ -149,28 +202,24 @@ impl FileServiceTrait for FileService {
 
         Ok("File deleted successfully".into())
     }
-}
-
-/// Internal helper methods defined on `FileService`.
-impl FileService {
-    /// Retrieves file metadata associated with a given user ID from the repository.
-    async fn get_file_by_user(&self, user_id: String) -> Result<Option<UploadedFileDto>, AppError> {
-        let uploaded_file = self
-            .repo
-            .find_by_user_id(self.pool.clone(), user_id)
-            .await
-            .map_err(|err| {
-                tracing::error!("Error retrieving file: {}", err);
-                AppError::DatabaseError(err)
-            });
 
-        match uploaded_file {
-            Ok(Some(file)) => Ok(Some(UploadedFileDto::from(file))),
-            Ok(None) => Ok(None),
-            Err(err) => Err(err),
+    /// Removes stored files from the filesystem, logging the files that cannot be removed.
+    fn remove_stored_files(&self, file_relative_paths: &[String]) {
+        let base_dir = FilePath::new(self.config.assets_private_path.as_str());
+        for file_relative_path in file_relative_paths {
+            let file_path = base_dir.join(file_relative_path);
+            if let Err(err) = std::fs::remove_file(&file_path) {
+                tracing::error!(
+                    "Error deleting file {} from filesystem: {err}",
+                    file_path.display()
+                );
+            }
         }
     }
+}
 
+/// Internal helper methods defined on `FileService`.
+impl FileService {
     /// Ensures the generated filename is unique within the given directory.
     fn generate_unique_filename(original: &str, base_dir: &str) -> String {
         let path = FilePath::new(original);
@@ -1,16 +1,23 @@
 use crate::{
     common::{
-        app_state::AppState, dto::RestApiResponse, error::AppError, jwt::Claims,
+        app_state::AppState,
+        dto::{PageDto, RestApiResponse},
+        error::AppError,
+        etag::{etag_header, IfMatch},
+        jwt::Claims,
         multipart_helper::parse_multipart_to_maps,
     },
     domains::{
         file::dto::file_dto::UploadFileDto,
-        user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+        user::dto::user_dto::{
+            CreateUserMultipartDto, PatchUserDto, ProfilePictureMultipartDto, SearchUserDto,
+            UpdateUserDto, UserDto,
+        },
     },
 };
 
 use axum::{
-    extract::{Multipart, State},
+    extract::{rejection::QueryRejection, Multipart, Query, State},
     response::IntoResponse,
     Extension, Json,
 };
@@ -20,7 +27,12 @@ use validator::Validate;
 #[utoipa::path(
     get,
     path = "/user/{id}",
-    responses((status = 200, description = "Get user by ID", body = UserDto)),
+    responses(
+        (status = 200, description = "Get user by ID", body = UserDto,
+            headers(("ETag" = String, description = "Version of the user, for `If-Match`"))),
+        (status = 403, description = "Missing `user:read` permission")
+    ),
+    security(("bearer_auth" = ["user:read"])),
     tag = "Users"
 )]
 pub async fn get_user_by_id(
@@ -28,20 +40,30 @@ pub async fn get_user_by_id(
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     let user = state.user_service.get_user_by_id(id).await?;
-    Ok(RestApiResponse::success(user))
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
 }
 
 #[utoipa::path(
     post,
     path = "/user/list",
     request_body = SearchUserDto,
-    responses((status = 200, description = "List users by condition", body = [UserDto])),
+    responses(
+        (status = 200, description = "List users by condition", body = PageDto<UserDto>),
+        (status = 400, description = "Invalid search criteria"),
+        (status = 403, description = "Missing `user:read` permission")
+    ),
+    security(("bearer_auth" = ["user:read"])),
     tag = "Users"
 )]
 pub async fn get_user_list(
     State(state): State<AppState>,
     Json(payload): Json<SearchUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::InvalidFields(err)
+    })?;
+
     let users = state.user_service.get_user_list(payload).await?;
     Ok(RestApiResponse::success(users))
 }
@@ -49,11 +71,29 @@ pub async fn get_user_list(
 #[utoipa::path(
     get,
     path = "/user",
-    responses((status = 200, description = "List all users", body = [UserDto])),
+    params(SearchUserDto),
+    responses(
+        (status = 200, description = "List users", body = PageDto<UserDto>),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 403, description = "Missing `user:read` permission")
+    ),
+    security(("bearer_auth" = ["user:read"])),
     tag = "Users"
 )]
-pub async fn get_users(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let users = state.user_service.get_users().await?;
+pub async fn get_users(
+    State(state): State<AppState>,
+    query: Result<Query<SearchUserDto>, QueryRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(query) = query.map_err(|err| {
+        tracing::error!("Invalid query: {err}");
+        AppError::ValidationError(err.body_text())
+    })?;
+    query.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::InvalidFields(err)
+    })?;
+
+    let users = state.user_service.get_user_list(query).await?;
     Ok(RestApiResponse::success(users))
 }
 
@@ -65,7 +105,11 @@ pub async fn get_users(State(state): State<AppState>) -> Result<impl IntoRespons
         content_type = "multipart/form-data",
         description = "User creation with optional profile picture upload"
     ),
-    responses((status = 200, description = "Create a new user", body = UserDto)),
+    responses(
+        (status = 200, description = "Create a new user", body = UserDto),
+        (status = 403, description = "Missing `user:create` permission")
+    ),
+    security(("bearer_auth" = ["user:create"])),
     tag = "Users"
 )]
 pub async fn create_user(
@@ -73,7 +117,7 @@ pub async fn create_user(
     Extension(claims): Extension<Claims>,
     multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
-    let modified_by = claims.sub.clone().to_string();
+    let modified_by = claims.actor_id().to_string();
 
     let (mut fields, mut files) =
         parse_multipart_to_maps(multipart, &state.config.asset_allowed_extensions_pattern).await?;
@@ -128,13 +172,21 @@ pub async fn create_user(
     put,
     path = "/user/{id}",
     request_body = UpdateUserDto,
-    responses((status = 200, description = "Update user", body = UserDto)),
+    responses(
+        (status = 200, description = "Update user", body = UserDto,
+            headers(("ETag" = String, description = "New version of the user"))),
+        (status = 403, description = "Missing `user:update` permission"),
+        (status = 412, description = "User was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["user:update"])),
     tag = "Users"
 )]
 pub async fn update_user(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
     Json(payload): Json<UpdateUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
     payload.validate().map_err(|err| {
@@ -142,24 +194,201 @@ pub async fn update_user(
         AppError::ValidationError(format!("Invalid input: {}", err))
     })?;
 
-    // Set the modified_by field to the current user's ID.
+    // Set the modified_by field to the acting user's ID (the admin when impersonating).
     let mut payload = payload;
-    payload.modified_by = claims.sub.clone().to_string();
+    payload.modified_by = claims.actor_id().to_string();
 
-    let user = state.user_service.update_user(id, payload).await?;
-    Ok(RestApiResponse::success(user))
+    let user = state
+        .user_service
+        .update_user(id, payload, if_match)
+        .await?;
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/user/{id}",
+    request_body(
+        content = PatchUserDto,
+        content_type = "application/merge-patch+json",
+        description = "JSON merge patch (RFC 7396) with the fields to change"
+    ),
+    responses(
+        (status = 200, description = "Partially update user", body = UserDto,
+            headers(("ETag" = String, description = "New version of the user"))),
+        (status = 400, description = "Invalid patch"),
+        (status = 403, description = "Missing `user:update` permission"),
+        (status = 404, description = "User not found"),
+        (status = 412, description = "User was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["user:update"])),
+    tag = "Users"
+)]
+pub async fn patch_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+    Json(payload): Json<PatchUserDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate().map_err(|err| {
+        tracing::error!("Validation error: {err}");
+        AppError::ValidationError(format!("Invalid input: {}", err))
+    })?;
+
+    // Set the modified_by field to the acting user's ID (the admin when impersonating).
+    let mut payload = payload;
+    payload.modified_by = claims.actor_id().to_string();
+
+    let user = state.user_service.patch_user(id, payload, if_match).await?;
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/{id}/profile-picture",
+    request_body(
+        content = ProfilePictureMultipartDto,
+        content_type = "multipart/form-data",
+        description = "The new profile picture"
+    ),
+    responses(
+        (status = 200, description = "Replace the user's profile picture", body = UserDto,
+            headers(("ETag" = String, description = "New version of the user"))),
+        (status = 400, description = "Missing or invalid profile picture"),
+        (status = 403, description = "Missing `user:update` permission"),
+        (status = 404, description = "User not found"),
+        (status = 412, description = "User was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["user:update"])),
+    tag = "Users"
+)]
+pub async fn update_profile_picture(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let (_, mut files) =
+        parse_multipart_to_maps(multipart, &state.config.asset_allowed_extensions_pattern).await?;
+
+    let file = files
+        .remove("profile_picture")
+        .and_then(|profile_files| profile_files.into_iter().next())
+        .ok_or(AppError::ValidationError("Missing profile picture".into()))?;
+
+    let upload_file_dto = UploadFileDto {
+        file,
+        user_id: None,
+        modified_by: claims.actor_id().to_string(),
+    };
+
+    let user = state
+        .user_service
+        .update_profile_picture(id, upload_file_dto, if_match)
+        .await?;
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/{id}/profile-picture",
+    responses(
+        (status = 200, description = "Profile picture deleted", body = UserDto,
+            headers(("ETag" = String, description = "New version of the user"))),
+        (status = 403, description = "Missing `user:update` permission"),
+        (status = 404, description = "User or profile picture not found"),
+        (status = 412, description = "User was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["user:update"])),
+    tag = "Users"
+)]
+pub async fn delete_profile_picture(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state
+        .user_service
+        .delete_profile_picture(id, claims.actor_id().to_string(), if_match)
+        .await?;
+    Ok((
+        etag_header(user.version),
+        RestApiResponse::success_with_message("Profile picture deleted", user),
+    ))
 }
 
 #[utoipa::path(
     delete,
     path = "/user/{id}",
-    responses((status = 200, description = "User deleted")),
+    responses(
+        (status = 200, description = "User soft-deleted and signed out"),
+        (status = 403, description = "Missing `user:delete` permission"),
+        (status = 404, description = "User not found"),
+        (status = 412, description = "User was modified since the `If-Match` version"),
+        (status = 428, description = "Missing `If-Match` header")
+    ),
+    security(("bearer_auth" = ["user:delete"])),
     tag = "Users"
 )]
 pub async fn delete_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+) -> Result<impl IntoResponse, AppError> {
+    let message = state
+        .user_service
+        .delete_user(id, claims.actor_id().to_string(), if_match)
+        .await?;
+    Ok(RestApiResponse::success_with_message(message, ()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/{id}/restore",
+    responses(
+        (status = 200, description = "Restore a deleted user", body = UserDto),
+        (status = 400, description = "User is not deleted"),
+        (status = 403, description = "Missing `user:delete` permission"),
+        (status = 404, description = "User not found")
+    ),
+    security(("bearer_auth" = ["user:delete"])),
+    tag = "Users"
+)]
+pub async fn restore_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state
+        .user_service
+        .restore_user(id, claims.actor_id().to_string())
+        .await?;
+    Ok(RestApiResponse::success(user))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/{id}/purge",
+    responses(
+        (status = 200, description = "Deleted user permanently removed with its devices, files and credentials"),
+        (status = 400, description = "User is not deleted"),
+        (status = 403, description = "Missing `user:delete` permission"),
+        (status = 404, description = "User not found")
+    ),
+    security(("bearer_auth" = ["user:delete"])),
+    tag = "Users"
+)]
+pub async fn purge_user(
     State(state): State<AppState>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let message = state.user_service.delete_user(id).await?;
+    let message = state.user_service.purge_user(id).await?;
     Ok(RestApiResponse::success_with_message(message, ()))
 }
@@ -1,11 +1,19 @@
 use super::handlers::*;
 use crate::{
-    common::app_state::AppState,
-    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    common::{
+        app_state::AppState,
+        dto::SortOrder,
+        rbac::{require_permission, USER_CREATE, USER_DELETE, USER_READ, USER_UPDATE},
+    },
+    domains::user::dto::user_dto::{
+        CreateUserMultipartDto, PatchUserDto, ProfilePictureMultipartDto, SearchUserDto,
+        UpdateUserDto, UserDto, UserSortField,
+    },
 };
 
 use axum::{
-    routing::{delete, get, post, put},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
@@ -22,9 +30,23 @@ use utoipa::{
         get_user_list,
         create_user,
         update_user,
+        patch_user,
+        update_profile_picture,
+        delete_profile_picture,
         delete_user,
+        restore_user,
+        purge_user,
     ),
-    components(schemas(UserDto, SearchUserDto, CreateUserMultipartDto, UpdateUserDto)),
+    components(schemas(
+        UserDto,
+        SearchUserDto,
+        UserSortField,
+        SortOrder,
+        CreateUserMultipartDto,
+        PatchUserDto,
+        ProfilePictureMultipartDto,
+        UpdateUserDto
+    )),
     tags(
         (name = "Users", description = "User management endpoints")
     ),
@@ -52,12 +74,54 @@ impl utoipa::Modify for UserApiDoc {
     }
 }
 
+/// This function creates a router for the user routes.
+/// Each route requires the permission documented on its handler.
 pub fn user_routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(get_users))
-        .route("/", post(create_user))
-        .route("/list", post(get_user_list))
-        .route("/{id}", get(get_user_by_id))
-        .route("/{id}", put(update_user))
-        .route("/{id}", delete(delete_user))
+        .route(
+            "/",
+            get(get_users).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/",
+            post(create_user).route_layer(middleware::from_fn(require_permission(USER_CREATE))),
+        )
+        .route(
+            "/list",
+            post(get_user_list).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/{id}",
+            get(get_user_by_id).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/{id}",
+            put(update_user).route_layer(middleware::from_fn(require_permission(USER_UPDATE))),
+        )
+        .route(
+            "/{id}",
+            patch(patch_user).route_layer(middleware::from_fn(require_permission(USER_UPDATE))),
+        )
+        .route(
+            "/{id}",
+            delete(delete_user).route_layer(middleware::from_fn(require_permission(USER_DELETE))),
+        )
+        .route(
+            "/{id}/profile-picture",
+            put(update_profile_picture)
+                .route_layer(middleware::from_fn(require_permission(USER_UPDATE))),
+        )
+        .route(
+            "/{id}/profile-picture",
+            delete(delete_profile_picture)
+                .route_layer(middleware::from_fn(require_permission(USER_UPDATE))),
+        )
+        .route(
+            "/{id}/restore",
+            post(restore_user).route_layer(middleware::from_fn(require_permission(USER_DELETE))),
+        )
+        .route(
+            "/{id}/purge",
+            delete(purge_user).route_layer(middleware::from_fn(require_permission(USER_DELETE))),
+        )
 }
@@ -6,10 +6,12 @@ pub struct User {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_by: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub modified_by: Option<String>,
     pub modified_at: Option<DateTime<Utc>>,
+    pub version: i64,
     pub file_id: Option<String>,
     pub origin_file_name: Option<String>,
 }
@@ -12,19 +12,30 @@ use sqlx::{PgPool, Postgres, Transaction};
 /// Trait representing repository-level operations for user entities.
 /// Provides methods for creating, retrieving, updating, and deleting users in the database.
 pub trait UserRepository: Send + Sync {
-    /// Retrieves all users from the database.
-    async fn find_all(&self, pool: PgPool) -> Result<Vec<User>, sqlx::Error>;
-
     /// Finds a user by their unique identifier.
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<User>, sqlx::Error>;
 
-    /// Finds user list by condition
+    /// Finds a user by their unique identifier and locks its row for the rest of the
+    /// transaction, so that it cannot change between reading and updating it.
+    async fn find_by_id_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    /// Returns a page of the users matching the criteria, in the requested order.
     async fn find_list(
         &self,
         pool: PgPool,
         search_user_dto: SearchUserDto,
+        limit: i64,
+        offset: i64,
     ) -> Result<Vec<User>, sqlx::Error>;
 
+    /// Counts the users matching the criteria.
+    async fn count(&self, pool: PgPool, search_user_dto: SearchUserDto)
+        -> Result<i64, sqlx::Error>;
+
     /// Creates a new user record using the provided data within an active transaction.
     async fn create(
         &self,
@@ -33,6 +44,7 @@ pub trait UserRepository: Send + Sync {
     ) -> Result<String, sqlx::Error>;
 
     /// Updates an existing user record using the provided data.
+    /// Returns `None` if there is no such user or it is deleted.
     async fn update(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -40,10 +52,43 @@ pub trait UserRepository: Send + Sync {
         user: UpdateUserDto,
     ) -> Result<Option<User>, sqlx::Error>;
 
-    /// Deletes a user by their unique identifier within an active transaction.
+    /// Records that a user was modified, locking its row for the rest of the transaction.
+    /// Returns `false` if there is no such user.
+    async fn mark_modified(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Soft-deletes a user by their unique identifier within an active transaction.
+    /// Returns `false` if there is no such user or it is already deleted.
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Restores a soft-deleted user within an active transaction.
+    /// Returns `false` if there is no such deleted user.
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
     ) -> Result<bool, sqlx::Error>;
+
+    /// Checks whether a user exists, including soft-deleted users.
+    async fn exists(&self, pool: PgPool, id: String) -> Result<bool, sqlx::Error>;
+
+    /// Permanently removes a soft-deleted user together with its devices, uploaded files
+    /// and credentials within an active transaction.
+    /// Returns the relative paths of the removed files, or `None` if there is no such
+    /// deleted user.
+    async fn purge(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<Vec<String>>, sqlx::Error>;
 }
@@ -2,12 +2,14 @@
 //! It abstracts operations such as user creation, retrieval, update, and deletion.
 
 use crate::{
-    common::error::AppError,
+    common::{dto::PageDto, error::AppError, etag::IfMatch},
     domains::file::dto::file_dto::UploadFileDto,
-    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    domains::user::dto::user_dto::{
+        CreateUserMultipartDto, PatchUserDto, SearchUserDto, UpdateUserDto, UserDto,
+    },
 };
 
-use crate::domains::file::FileServiceTrait;
+use crate::domains::{auth::AuthServiceTrait, file::FileServiceTrait};
 use async_trait::async_trait;
 use sqlx::PgPool;
 use std::sync::Arc;
@@ -20,6 +22,7 @@ pub trait UserServiceTrait: Send + Sync {
     fn create_service(
         pool: PgPool,
         file_service: Arc<dyn FileServiceTrait>,
+        auth_service: Arc<dyn AuthServiceTrait>,
     ) -> Arc<dyn UserServiceTrait>
     where
         Self: Sized;
@@ -27,23 +30,68 @@ pub trait UserServiceTrait: Send + Sync {
     /// Retrieves a user by their unique identifier.
     async fn get_user_by_id(&self, id: String) -> Result<UserDto, AppError>;
 
-    /// Retrieves user list by condition
-    async fn get_user_list(&self, search_user_dto: SearchUserDto)
-        -> Result<Vec<UserDto>, AppError>;
-
-    /// Retrieves all users.
-    async fn get_users(&self) -> Result<Vec<UserDto>, AppError>;
+    /// Retrieves a page of the users matching the criteria, with the total count.
+    async fn get_user_list(
+        &self,
+        search_user_dto: SearchUserDto,
+    ) -> Result<PageDto<UserDto>, AppError>;
 
     /// Creates a new user with optional profile picture upload.
+    /// An email verification token is mailed to the user's address.
     async fn create_user(
         &self,
         create_user: CreateUserMultipartDto,
         upload_file_dto: Option<&mut UploadFileDto>,
     ) -> Result<UserDto, AppError>;
 
-    /// Updates an existing user with the given payload.
-    async fn update_user(&self, id: String, payload: UpdateUserDto) -> Result<UserDto, AppError>;
+    /// Updates an existing user with the given payload, if it matches the precondition.
+    /// Changing the email address marks it unverified and mails a new verification token.
+    async fn update_user(
+        &self,
+        id: String,
+        payload: UpdateUserDto,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Applies a JSON merge patch to a user: only the members present in the patch change.
+    async fn patch_user(
+        &self,
+        id: String,
+        patch: PatchUserDto,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Replaces the profile picture of a user, or adds one if the user has none,
+    /// if the user matches the precondition.
+    async fn update_profile_picture(
+        &self,
+        id: String,
+        upload_file_dto: UploadFileDto,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Removes the profile picture of a user, if the user matches the precondition.
+    /// Returns the updated user.
+    async fn delete_profile_picture(
+        &self,
+        id: String,
+        modified_by: String,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Soft-deletes a user by their unique identifier and revokes all of its sessions.
+    /// The user is hidden from every query and can no longer log in until restored.
+    async fn delete_user(
+        &self,
+        id: String,
+        deleted_by: String,
+        if_match: IfMatch,
+    ) -> Result<String, AppError>;
+
+    /// Restores a soft-deleted user.
+    async fn restore_user(&self, id: String, modified_by: String) -> Result<UserDto, AppError>;
 
-    /// Deletes a user by their unique identifier.
-    async fn delete_user(&self, id: String) -> Result<String, AppError>;
+    /// Permanently removes a soft-deleted user together with its devices, uploaded files
+    /// and credentials.
+    async fn purge_user(&self, id: String) -> Result<String, AppError>;
 }
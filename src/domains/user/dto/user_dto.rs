@@ -1,21 +1,28 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
-use crate::domains::user::domain::model::User;
+use crate::{
+    common::dto::{patch_field, SortOrder},
+    domains::user::domain::model::User,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UserDto {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_by: Option<String>,
     #[serde(with = "crate::common::ts_format::option")]
     pub created_at: Option<DateTime<Utc>>,
     pub modified_by: Option<String>,
     #[serde(with = "crate::common::ts_format::option")]
     pub modified_at: Option<DateTime<Utc>>,
+    /// Incremented on every change; sent as `ETag` and expected in `If-Match`.
+    pub version: i64,
     pub file_id: Option<String>,
     pub origin_file_name: Option<String>,
 }
@@ -26,21 +33,63 @@ impl From<User> for UserDto {
             id: user.id,
             username: user.username,
             email: user.email,
+            email_verified_at: user.email_verified_at,
             created_by: user.created_by,
             created_at: user.created_at,
             modified_by: user.modified_by,
             modified_at: user.modified_at,
+            version: user.version,
             file_id: user.file_id,
             origin_file_name: user.origin_file_name,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+/// Criteria, sort order and page of a user listing, sent as query parameters to
+/// `GET /user` or as body to `POST /user/list`. Unset criteria match every user.
+/// `username` and `email` match case-insensitively on a part of the value, and `search`
+/// on a part of either. `created_from` (inclusive) and `created_to` (exclusive) are
+/// RFC 3339 timestamps; pages start at 1.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
 pub struct SearchUserDto {
     pub id: Option<String>,
     pub username: Option<String>,
     pub email: Option<String>,
+    pub search: Option<String>,
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub created_from: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub created_to: Option<DateTime<Utc>>,
+    pub sort_by: Option<UserSortField>,
+    pub order: Option<SortOrder>,
+    #[validate(range(min = 1, message = "Page must be at least 1"))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100, message = "Page size must be 1 to 100"))]
+    pub page_size: Option<i64>,
+}
+
+/// Column a user listing can be sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortField {
+    Username,
+    Email,
+    #[default]
+    CreatedAt,
+    ModifiedAt,
+}
+
+impl UserSortField {
+    /// Returns the column of the user listing query.
+    pub fn column(&self) -> &'static str {
+        match self {
+            UserSortField::Username => "u.username",
+            UserSortField::Email => "u.email",
+            UserSortField::CreatedAt => "u.created_at",
+            UserSortField::ModifiedAt => "u.modified_at",
+        }
+    }
 }
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateUserMultipartDto {
@@ -55,6 +104,32 @@ pub struct CreateUserMultipartDto {
     pub profile_picture: Option<String>,
 }
 
+/// Multipart body replacing a user's profile picture.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProfilePictureMultipartDto {
+    #[allow(dead_code)]
+    #[schema(value_type = String, format = "binary", example = "profile_picture.png")]
+    pub profile_picture: String,
+}
+
+/// Partial update of a user as a JSON merge patch (RFC 7396).
+/// Absent members are left unchanged and `null` clears a nullable field;
+/// `username` and `email` are required, so they cannot be cleared.
+#[derive(Debug, Default, Deserialize, ToSchema, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct PatchUserDto {
+    #[serde(default, deserialize_with = "patch_field")]
+    #[schema(value_type = Option<String>, nullable)]
+    #[validate(length(max = 64, message = "Username cannot exceed 64 characters"))]
+    pub username: Option<Option<String>>,
+    #[serde(default, deserialize_with = "patch_field")]
+    #[schema(value_type = Option<String>, nullable)]
+    #[validate(email(message = "Invalid email format"))]
+    pub email: Option<Option<String>>,
+    #[serde(skip)]
+    pub modified_by: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct UpdateUserDto {
     #[validate(length(max = 64, message = "Username cannot exceed 64 characters"))]
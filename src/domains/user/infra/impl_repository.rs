@@ -9,21 +9,29 @@ use uuid::Uuid;
 
 pub struct UserRepo;
 
+const COUNT_USER_QUERY: &str = r#"
+    SELECT COUNT(*)
+    FROM users u
+    WHERE u.deleted_at IS NULL
+    "#;
+
 const FIND_USER_QUERY: &str = r#"
     SELECT
         u.id,
         u.username,
         u.email,
+        u.email_verified_at,
         u.created_by,
         u.created_at,
         u.modified_by,
         u.modified_at,
+        u.version,
         uf.id as file_id,
         uf.origin_file_name
     FROM users u
     LEFT JOIN uploaded_files uf 
             ON uf.user_id = u.id and uf.file_type = 'profile_picture'
-    WHERE 1=1
+    WHERE u.deleted_at IS NULL
     "#;
 
 const FIND_USER_INFO_QUERY: &str = r#"
@@ -31,57 +39,64 @@ const FIND_USER_INFO_QUERY: &str = r#"
         u.id,
         u.username,
         u.email,
+        u.email_verified_at,
         u.created_by,
         u.created_at,
         u.modified_by,
         u.modified_at,
+        u.version,
         uf.id as file_id,
         uf.origin_file_name
     FROM users u
     LEFT JOIN uploaded_files uf 
            ON uf.user_id = u.id and uf.file_type = 'profile_picture'
     WHERE u.id = $1
+      AND u.deleted_at IS NULL
     "#;
 
 #[async_trait]
 impl UserRepository for UserRepo {
-    async fn find_all(&self, pool: PgPool) -> Result<Vec<User>, sqlx::Error> {
-        let users = sqlx::query_as::<_, User>(FIND_USER_QUERY)
-            .fetch_all(&pool)
-            .await?;
-        Ok(users)
-    }
-
     async fn find_list(
         &self,
         pool: PgPool,
         search_user_dto: SearchUserDto,
+        limit: i64,
+        offset: i64,
     ) -> Result<Vec<User>, sqlx::Error> {
-        let mut builder = QueryBuilder::<_>::new(FIND_USER_QUERY);
+        let mut builder = QueryBuilder::<Postgres>::new(FIND_USER_QUERY);
+        push_search_conditions(&mut builder, &search_user_dto);
 
-        if let Some(s) = search_user_dto
-            .id
-            .as_deref()
-            .filter(|s| !s.trim().is_empty())
-        {
-            builder.push(" AND u.id = ");
-            builder.push_bind(s);
-        }
-
-        if let Some(s) = search_user_dto
-            .username
-            .as_deref()
-            .filter(|s| !s.trim().is_empty())
-        {
-            builder.push(" AND u.username like ");
-            builder.push_bind(format!("%{}%", s));
-        }
+        // The sort column is taken from a whitelist; the ID keeps pages stable.
+        let sort_by = search_user_dto.sort_by.unwrap_or_default();
+        let order = search_user_dto.order.unwrap_or_default();
+        builder.push(format!(
+            " ORDER BY {} {}, u.id {}",
+            sort_by.column(),
+            order.as_sql(),
+            order.as_sql()
+        ));
+        builder.push(" LIMIT ");
+        builder.push_bind(limit);
+        builder.push(" OFFSET ");
+        builder.push_bind(offset);
 
         let query = builder.build_query_as::<User>();
         let users = query.fetch_all(&pool).await?;
         Ok(users)
     }
 
+    async fn count(
+        &self,
+        pool: PgPool,
+        search_user_dto: SearchUserDto,
+    ) -> Result<i64, sqlx::Error> {
+        let mut builder = QueryBuilder::<Postgres>::new(COUNT_USER_QUERY);
+        push_search_conditions(&mut builder, &search_user_dto);
+
+        let count = builder.build_query_scalar::<i64>().fetch_one(&pool).await?;
+        Ok(count)
+    }
+
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<User>, sqlx::Error> {
         let user = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
             .bind(id)
@@ -90,6 +105,18 @@ impl UserRepository for UserRepo {
         Ok(user)
     }
 
+    async fn find_by_id_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(&format!("{FIND_USER_INFO_QUERY} FOR UPDATE OF u"))
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await?;
+        Ok(user)
+    }
+
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -120,47 +147,202 @@ impl UserRepository for UserRepo {
         id: String,
         user: UpdateUserDto,
     ) -> Result<Option<User>, sqlx::Error> {
-        let existing = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
-            .bind(id.clone())
-            .fetch_optional(&mut **tx)
-            .await?;
+        let res = sqlx::query!(
+            r#"
+            UPDATE users 
+            SET username = $1,
+                email = $2,
+                email_verified_at = CASE WHEN email = $2::VARCHAR THEN email_verified_at END,
+                modified_by = $3, 
+                modified_at = NOW(),
+                version = version + 1
+            WHERE id = $4
+              AND deleted_at IS NULL
+            "#,
+            user.username.clone(),
+            user.email.clone(),
+            user.modified_by.clone(),
+            id.clone()
+        )
+        .execute(&mut **tx)
+        .await?;
 
-        if existing.is_some() {
-            sqlx::query!(
-                r#"
-                UPDATE users 
-                SET username = $1,
-                    email = $2, 
-                    modified_by = $3, 
-                    modified_at = NOW() 
-                WHERE id = $4
-                "#,
-                user.username.clone(),
-                user.email.clone(),
-                user.modified_by.clone(),
-                id.clone()
-            )
-            .execute(&mut **tx)
+        // Deleted users are not updated.
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let updated_user = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
+            .bind(id)
+            .fetch_one(&mut **tx)
             .await?;
 
-            let updated_user = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
-                .bind(id)
-                .fetch_one(&mut **tx)
-                .await?;
+        Ok(Some(updated_user))
+    }
 
-            return Ok(Some(updated_user));
-        }
-        Ok(None)
+    async fn mark_modified(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE users
+               SET modified_by = $1,
+                   modified_at = NOW(),
+                   version = version + 1
+             WHERE id = $2
+               AND deleted_at IS NULL
+            "#,
+            modified_by,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected() > 0)
     }
 
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE users
+               SET deleted_at = NOW(),
+                   deleted_by = $1,
+                   version = version + 1
+             WHERE id = $2
+               AND deleted_at IS NULL
+            "#,
+            deleted_by,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
     ) -> Result<bool, sqlx::Error> {
-        let res = sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, id)
+        let res = sqlx::query!(
+            r#"
+            UPDATE users
+               SET deleted_at = NULL,
+                   deleted_by = NULL,
+                   modified_by = $1,
+                   modified_at = NOW(),
+                   version = version + 1
+             WHERE id = $2
+               AND deleted_at IS NOT NULL
+            "#,
+            modified_by,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn exists(&self, pool: PgPool, id: String) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM users WHERE id = $1) AS "exists!""#,
+            id
+        )
+        .fetch_one(&pool)
+        .await?;
+        Ok(exists)
+    }
+
+    async fn purge(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<Vec<String>>, sqlx::Error> {
+        let deleted = sqlx::query_scalar!(
+            r#"SELECT id FROM users WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE"#,
+            id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        if deleted.is_none() {
+            return Ok(None);
+        }
+
+        // Devices, files and credentials are not deleted together with the user by the
+        // database; every other table referencing the user cascades.
+        sqlx::query!(r#"DELETE FROM devices WHERE user_id = $1"#, id)
             .execute(&mut **tx)
             .await?;
-        Ok(res.rows_affected() > 0)
+        let file_paths = sqlx::query_scalar!(
+            r#"DELETE FROM uploaded_files WHERE user_id = $1 RETURNING file_relative_path"#,
+            id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        sqlx::query!(r#"DELETE FROM user_auth WHERE user_id = $1"#, id)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(Some(file_paths))
     }
 }
+
+/// Appends the criteria of a user listing to a query selecting from `users u`.
+fn push_search_conditions(builder: &mut QueryBuilder<'_, Postgres>, search: &SearchUserDto) {
+    let non_empty = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+
+    if let Some(id) = non_empty(&search.id) {
+        builder.push(" AND u.id = ");
+        builder.push_bind(id);
+    }
+    if let Some(username) = non_empty(&search.username) {
+        builder.push(" AND u.username ILIKE ");
+        builder.push_bind(contains_pattern(&username));
+    }
+    if let Some(email) = non_empty(&search.email) {
+        builder.push(" AND u.email ILIKE ");
+        builder.push_bind(contains_pattern(&email));
+    }
+    if let Some(term) = non_empty(&search.search) {
+        let pattern = contains_pattern(&term);
+        builder.push(" AND (u.username ILIKE ");
+        builder.push_bind(pattern.clone());
+        builder.push(" OR u.email ILIKE ");
+        builder.push_bind(pattern);
+        builder.push(")");
+    }
+    if let Some(from) = search.created_from {
+        builder.push(" AND u.created_at >= ");
+        builder.push_bind(from);
+    }
+    if let Some(to) = search.created_to {
+        builder.push(" AND u.created_at < ");
+        builder.push_bind(to);
+    }
+}
+
+/// Builds a `LIKE` pattern matching values that contain `value` literally.
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
@@ -1,18 +1,24 @@
 use crate::{
-    common::error::AppError,
+    common::{dto::PageDto, error::AppError, etag::IfMatch},
     domains::{
+        auth::AuthServiceTrait,
         file::{dto::file_dto::UploadFileDto, FileServiceTrait},
         user::{
-            domain::{repository::UserRepository, service::UserServiceTrait},
-            dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+            domain::{model::User, repository::UserRepository, service::UserServiceTrait},
+            dto::user_dto::{
+                CreateUserMultipartDto, PatchUserDto, SearchUserDto, UpdateUserDto, UserDto,
+            },
             infra::impl_repository::UserRepo,
         },
     },
 };
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
 
+/// Number of users per page if the search does not set `page_size`.
+const DEFAULT_PAGE_SIZE: i64 = 20;
+
 /// Service struct for handling user-related operations
 /// such as creating, updating, deleting, and fetching users.
 /// It uses a repository pattern to abstract the data access layer.
@@ -21,6 +27,7 @@ pub struct UserService {
     pub pool: PgPool,
     pub repo: Arc<dyn UserRepository + Send + Sync>,
     pub file_service: Arc<dyn FileServiceTrait>,
+    pub auth_service: Arc<dyn AuthServiceTrait>,
 }
 
 #[async_trait]
@@ -29,11 +36,13 @@ impl UserServiceTrait for UserService {
     fn create_service(
         pool: PgPool,
         file_service: Arc<dyn FileServiceTrait>,
+        auth_service: Arc<dyn AuthServiceTrait>,
     ) -> Arc<dyn UserServiceTrait> {
         Arc::new(Self {
             pool,
             repo: Arc::new(UserRepo {}),
             file_service,
+            auth_service,
         })
     }
 
@@ -49,41 +58,43 @@ impl UserServiceTrait for UserService {
         }
     }
 
-    /// Retrieves user list by condition
-    /// Returns a vector of UserDto objects.
+    /// Retrieves a page of the users matching the criteria.
+    /// Returns the UserDto objects of the page together with the total count.
     async fn get_user_list(
         &self,
         search_user_dto: SearchUserDto,
-    ) -> Result<Vec<UserDto>, AppError> {
-        match self
+    ) -> Result<PageDto<UserDto>, AppError> {
+        let page = search_user_dto.page.unwrap_or(1);
+        let page_size = search_user_dto.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        let total = self
             .repo
-            .find_list(self.pool.clone(), search_user_dto)
+            .count(self.pool.clone(), search_user_dto.clone())
             .await
-        {
-            Ok(users) => {
-                let user_dtos: Vec<UserDto> = users.into_iter().map(Into::into).collect();
-                Ok(user_dtos)
-            }
-            Err(err) => {
+            .map_err(|err| {
+                tracing::error!("Error counting users: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        let users = self
+            .repo
+            .find_list(
+                self.pool.clone(),
+                search_user_dto,
+                page_size,
+                (page - 1).saturating_mul(page_size),
+            )
+            .await
+            .map_err(|err| {
                 tracing::error!("Error fetching users: {err}");
-                Err(AppError::DatabaseError(err))
-            }
-        }
-    }
+                AppError::DatabaseError(err)
+            })?;
 
-    /// Retrieves all users.
-    /// Returns a vector of UserDto objects.
-    async fn get_users(&self) -> Result<Vec<UserDto>, AppError> {
-        match self.repo.find_all(self.pool.clone()).await {
-            Ok(users) => {
-                let user_dtos: Vec<UserDto> = users.into_iter().map(Into::into).collect();
-                Ok(user_dtos)
-            }
-            Err(err) => {
-                tracing::error!("Error fetching users: {err}");
-                Err(AppError::DatabaseError(err))
-            }
-        }
+        Ok(PageDto {
+            items: users.into_iter().map(Into::into).collect(),
+            page,
+            page_size,
+            total,
+        })
     }
     /// Creates a new user.
     /// Takes a CreateUserMultipartDto object and an optional UploadFileDto object.
@@ -92,6 +103,7 @@ impl UserServiceTrait for UserService {
         create_user: CreateUserMultipartDto,
         upload_file_dto: Option<&mut UploadFileDto>,
     ) -> Result<UserDto, AppError> {
+        let email = create_user.email.clone();
         let mut tx = self.pool.begin().await?;
 
         let user_id = match self.repo.create(&mut tx, create_user).await {
@@ -111,6 +123,7 @@ impl UserServiceTrait for UserService {
         }
 
         tx.commit().await?;
+        self.send_email_verification(&user_id, email).await;
 
         match self.repo.find_by_id(self.pool.clone(), user_id).await {
             Ok(Some(user)) => Ok(UserDto::from(user)),
@@ -123,44 +136,312 @@ impl UserServiceTrait for UserService {
     }
 
     /// Updates an existing user.
-    async fn update_user(&self, id: String, payload: UpdateUserDto) -> Result<UserDto, AppError> {
+    /// Fails with `PreconditionFailed` if the user does not match the `If-Match` precondition.
+    async fn update_user(
+        &self,
+        id: String,
+        payload: UpdateUserDto,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError> {
+        self.save_user(id, &if_match, |_| Ok(payload)).await
+    }
+
+    /// Applies a JSON merge patch to a user.
+    /// The patch is merged into the current values, which are then saved like `update_user`;
+    /// an empty patch leaves the user unchanged.
+    async fn patch_user(
+        &self,
+        id: String,
+        patch: PatchUserDto,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError> {
+        if patch.username.is_none() && patch.email.is_none() {
+            let user = self.get_user_by_id(id).await?;
+            if_match.check(user.version)?;
+            return Ok(user);
+        }
+
+        self.save_user(id, &if_match, |user| {
+            Ok(UpdateUserDto {
+                username: merge_required("username", patch.username, user.username)?,
+                email: merge_required("email", patch.email, user.email.unwrap_or_default())?,
+                modified_by: patch.modified_by,
+            })
+        })
+        .await
+    }
+
+    /// Replaces the profile picture of a user.
+    /// The user row is locked while the old record is swapped for the new one, so that the
+    /// user keeps exactly one picture; the old file is removed from disk after the commit.
+    async fn update_profile_picture(
+        &self,
+        id: String,
+        mut upload_file_dto: UploadFileDto,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError> {
         let mut tx = self.pool.begin().await?;
 
-        match self.repo.update(&mut tx, id.to_string(), payload).await {
-            Ok(Some(user)) => {
+        self.lock_modified_user(&mut tx, &id, &upload_file_dto.modified_by, &if_match)
+            .await?;
+        let previous = self
+            .file_service
+            .delete_profile_picture(&mut tx, id.clone())
+            .await?;
+
+        upload_file_dto.user_id = Some(id.clone());
+        let uploaded = self
+            .file_service
+            .process_profile_picture_upload(&mut tx, &upload_file_dto)
+            .await?;
+
+        if let Err(err) = tx.commit().await {
+            tracing::error!("Error replacing profile picture: {err}");
+            let uploaded_paths: Vec<String> = uploaded
+                .into_iter()
+                .map(|file| file.file_relative_path)
+                .collect();
+            self.file_service.remove_stored_files(&uploaded_paths);
+            return Err(AppError::DatabaseError(err));
+        }
+        if let Some(previous) = previous {
+            self.file_service
+                .remove_stored_files(&[previous.file_relative_path]);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    /// Removes the profile picture of a user; the file is removed from disk after the commit.
+    async fn delete_profile_picture(
+        &self,
+        id: String,
+        modified_by: String,
+        if_match: IfMatch,
+    ) -> Result<UserDto, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        self.lock_modified_user(&mut tx, &id, &modified_by, &if_match)
+            .await?;
+        let Some(previous) = self
+            .file_service
+            .delete_profile_picture(&mut tx, id.clone())
+            .await?
+        else {
+            tx.rollback().await?;
+            return Err(AppError::NotFound("Profile picture not found".into()));
+        };
+
+        tx.commit().await?;
+        self.file_service
+            .remove_stored_files(&[previous.file_relative_path]);
+
+        self.get_user_by_id(id).await
+    }
+
+    /// Soft-deletes a user by their ID.
+    /// The user's sessions and tokens are revoked, so that it is signed out immediately.
+    /// Fails with `PreconditionFailed` if the user does not match the `If-Match` precondition.
+    async fn delete_user(
+        &self,
+        id: String,
+        deleted_by: String,
+        if_match: IfMatch,
+    ) -> Result<String, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = self.find_user_for_update(&mut tx, &id).await?;
+        if_match.check(user.version)?;
+
+        match self.repo.delete(&mut tx, id.to_string(), deleted_by).await {
+            Ok(true) => {
                 tx.commit().await?;
-                Ok(UserDto::from(user))
+                self.auth_service.revoke_all_sessions(id).await?;
+                Ok("User deleted".into())
             }
-            Ok(None) => {
+            Ok(false) => {
                 tx.rollback().await?;
                 Err(AppError::NotFound("User not found".into()))
             }
             Err(err) => {
-                tracing::error!("Error updating user: {err}");
+                tracing::error!("Error deleting user: {err}");
                 tx.rollback().await?;
                 Err(AppError::DatabaseError(err))
             }
         }
     }
 
-    /// Deletes a user by their ID.
-    async fn delete_user(&self, id: String) -> Result<String, AppError> {
+    /// Restores a soft-deleted user by their ID.
+    /// Returns a validation error if the user is not deleted.
+    async fn restore_user(&self, id: String, modified_by: String) -> Result<UserDto, AppError> {
         let mut tx = self.pool.begin().await?;
 
-        match self.repo.delete(&mut tx, id.to_string()).await {
-            Ok(true) => {
+        match self.repo.restore(&mut tx, id.clone(), modified_by).await {
+            Ok(true) => tx.commit().await?,
+            Ok(false) => {
+                tx.rollback().await?;
+                return Err(self.not_deleted_error(&id, "restored").await);
+            }
+            Err(err) => {
+                tracing::error!("Error restoring user: {err}");
+                tx.rollback().await?;
+                return Err(AppError::DatabaseError(err));
+            }
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    /// Permanently removes a soft-deleted user by their ID.
+    /// Devices, file records and credentials are deleted in one transaction; the files are
+    /// removed from disk once it has been committed.
+    /// Returns a validation error if the user has not been soft-deleted first.
+    async fn purge_user(&self, id: String) -> Result<String, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let file_paths = match self.repo.purge(&mut tx, id.clone()).await {
+            Ok(Some(file_paths)) => file_paths,
+            Ok(None) => {
+                tx.rollback().await?;
+                return Err(self.not_deleted_error(&id, "purged").await);
+            }
+            Err(err) => {
+                tracing::error!("Error purging user: {err}");
+                tx.rollback().await?;
+                return Err(AppError::DatabaseError(err));
+            }
+        };
+
+        tx.commit().await?;
+        self.file_service.remove_stored_files(&file_paths);
+
+        Ok("User purged".into())
+    }
+}
+
+/// Internal helper methods defined on `UserService`.
+impl UserService {
+    /// Locks the user, checks the `If-Match` precondition and saves the values built from
+    /// the current user by `build`. A changed email address is mailed a verification token.
+    async fn save_user<F>(
+        &self,
+        id: String,
+        if_match: &IfMatch,
+        build: F,
+    ) -> Result<UserDto, AppError>
+    where
+        F: FnOnce(User) -> Result<UpdateUserDto, AppError> + Send,
+    {
+        let mut tx = self.pool.begin().await?;
+
+        let user = self.find_user_for_update(&mut tx, &id).await?;
+        if_match.check(user.version)?;
+        let previous_email = user.email.clone();
+        let payload = build(user)?;
+
+        match self.repo.update(&mut tx, id, payload).await {
+            Ok(Some(user)) => {
                 tx.commit().await?;
-                Ok("User deleted".into())
+                if let Some(email) = user
+                    .email
+                    .clone()
+                    .filter(|email| previous_email.as_ref() != Some(email))
+                {
+                    self.send_email_verification(&user.id, email).await;
+                }
+                Ok(UserDto::from(user))
             }
-            Ok(false) => {
+            Ok(None) => {
                 tx.rollback().await?;
                 Err(AppError::NotFound("User not found".into()))
             }
             Err(err) => {
-                tracing::error!("Error deleting user: {err}");
+                tracing::error!("Error updating user: {err}");
                 tx.rollback().await?;
                 Err(AppError::DatabaseError(err))
             }
         }
     }
+
+    /// Loads a user and locks its row for the rest of the transaction.
+    /// Returns `NotFound` if there is no such user.
+    async fn find_user_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+    ) -> Result<User, AppError> {
+        self.repo
+            .find_by_id_for_update(tx, id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error retrieving user: {err}");
+                AppError::DatabaseError(err)
+            })?
+            .ok_or_else(|| AppError::NotFound("User not found".into()))
+    }
+
+    /// Locks the user, checks the `If-Match` precondition and records the modification
+    /// within the transaction. Returns `NotFound` if there is no such user.
+    async fn lock_modified_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+        modified_by: &str,
+        if_match: &IfMatch,
+    ) -> Result<(), AppError> {
+        let user = self.find_user_for_update(tx, id).await?;
+        if_match.check(user.version)?;
+
+        match self
+            .repo
+            .mark_modified(tx, id.to_string(), modified_by.to_string())
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AppError::NotFound("User not found".into())),
+            Err(err) => {
+                tracing::error!("Error updating user: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Returns the error for a user that is not soft-deleted: `NotFound` if there is no
+    /// such user at all, otherwise a validation error naming the refused `action`.
+    async fn not_deleted_error(&self, id: &str, action: &str) -> AppError {
+        match self.repo.exists(self.pool.clone(), id.to_string()).await {
+            Ok(true) => AppError::ValidationError(format!("Only deleted users can be {action}")),
+            Ok(false) => AppError::NotFound("User not found".into()),
+            Err(err) => {
+                tracing::error!("Error retrieving user: {err}");
+                AppError::DatabaseError(err)
+            }
+        }
+    }
+
+    /// Mails a verification token for the user's email address.
+    /// The user has already been saved, so a failure is only logged.
+    async fn send_email_verification(&self, user_id: &str, email: String) {
+        if let Err(err) = self
+            .auth_service
+            .send_email_verification(user_id.to_string(), email)
+            .await
+        {
+            tracing::error!("Error sending email verification: {err}");
+        }
+    }
+}
+
+/// Merges a member of a merge patch into a required field: an absent member keeps the
+/// current value, while `null` is rejected since the field cannot be cleared.
+fn merge_required(
+    field: &str,
+    patch: Option<Option<String>>,
+    current: String,
+) -> Result<String, AppError> {
+    match patch {
+        None => Ok(current),
+        Some(Some(value)) => Ok(value),
+        Some(None) => Err(AppError::ValidationError(format!("{field} cannot be null"))),
+    }
 }
@@ -3,6 +3,7 @@ use common::{
     bootstrap::{build_app_state, shutdown_signal},
     config::{setup_database, Config},
 };
+use std::net::SocketAddr;
 use tracing::info;
 
 #[cfg(not(feature = "opentelemetry"))]
@@ -34,6 +35,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     };
 
     let config = Config::from_env()?;
+    // Load the JWT keys eagerly so that an invalid key ring fails at startup.
+    std::sync::LazyLock::force(&common::jwt::KEYS);
     let pool = setup_database(&config).await?;
     let state = build_app_state(pool, config.clone());
     let app = create_router(state);
@@ -44,9 +47,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Connection info provides the client IP used to throttle logins.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
     #[cfg(feature = "opentelemetry")]
     shutdown_opentelemetry(opentelemetry_tracer_provider)?;
@@ -2,7 +2,45 @@ use axum::http::{Method, StatusCode};
 
 mod test_helpers;
 
-use test_helpers::{request, request_with_auth};
+use test_helpers::{
+    create_user_with_credentials, login, request, request_with_auth, request_with_token,
+    setup_test_db, test_config,
+};
+
+/// Stores a copy of the public test image as a private profile picture owned by the user.
+/// Returns the URL of the stored file below `ASSETS_PRIVATE_URL` and its path on disk.
+async fn create_private_asset(user_id: &str) -> (String, std::path::PathBuf) {
+    let config = test_config();
+    let file_name = format!("test-{}.jpeg", uuid::Uuid::new_v4());
+    let file_relative_path = format!("profile_picture/{}", file_name);
+    let file_url = format!("{}/{}", config.assets_private_url, file_relative_path);
+
+    let file_path = std::path::Path::new(&config.assets_private_path).join(&file_relative_path);
+    std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+    std::fs::copy(
+        std::path::Path::new(&config.assets_public_path).join("images.jpeg"),
+        &file_path,
+    )
+    .unwrap();
+
+    let pool = setup_test_db().await.unwrap();
+    sqlx::query(
+        "INSERT INTO uploaded_files (id, user_id, file_name, origin_file_name, file_relative_path,
+                file_url, content_type, file_size, file_type, created_by, modified_by)
+         VALUES ($1, $2, $3, 'images.jpeg', $4, $5, 'image/jpeg', $6, 'profile_picture', $2, $2)",
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(user_id)
+    .bind(&file_name)
+    .bind(&file_relative_path)
+    .bind(&file_url)
+    .bind(std::fs::metadata(&file_path).unwrap().len() as i64)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    (file_url, file_path)
+}
 
 #[tokio::test]
 async fn test_public_assets() {
@@ -24,9 +62,28 @@ async fn test_private_assets_without_auth() {
 
 #[tokio::test]
 async fn test_private_assets_with_auth() {
-    let response = request_with_auth(Method::GET, "/assets/private/profile_picture/images.jpeg");
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let (file_url, file_path) = create_private_asset(&user_id).await;
+
+    // The owner and admins can read the file.
+    let owner = login(&username, &password).await;
+    let response = request_with_token(Method::GET, &file_url, &owner.access_token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
 
+    let response = request_with_auth(Method::GET, &file_url).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Other users cannot, even though they are authenticated.
+    let (_, other_username, other_password) = create_user_with_credentials().await;
+    let other = login(&other_username, &other_password).await;
+    let response = request_with_token(Method::GET, &file_url, &other.access_token).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Files without a record are not served.
+    let response = request_with_auth(Method::GET, "/assets/private/profile_picture/images.jpeg");
     let (parts, _) = response.await.into_parts();
-    // println!("parts.status: {:?}", parts.status);
-    assert_eq!(parts.status, StatusCode::OK);
+    assert_eq!(parts.status, StatusCode::NOT_FOUND);
+
+    std::fs::remove_file(file_path).unwrap();
 }
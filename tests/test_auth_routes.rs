@@ -1,10 +1,51 @@
-use axum::http::{Method, StatusCode};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    http::{
+        header::{AUTHORIZATION, LOCATION, RETRY_AFTER, SET_COOKIE, WWW_AUTHENTICATE},
+        HeaderMap, Method, StatusCode,
+    },
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use sha2::{Digest, Sha256};
 
-use clean_axum_demo::common::{
-    dto::RestApiResponse,
-    jwt::{AuthBody, AuthPayload},
+use clean_axum_demo::{
+    common::{
+        config::Config,
+        dto::{PageDto, RestApiResponse},
+        jwt::{make_validation, AuthBody, AuthPayload, Claims, KEYS},
+        jwt_keys::KeyRing,
+        totp,
+    },
+    domains::auth::dto::auth_dto::{
+        ApiKeyDto, AuthEventDto, AuthUserDto, ChangePasswordDto, CreateApiKeyDto,
+        CreateOAuthClientDto, CreatedApiKeyDto, CreatedOAuthClientDto, ForgotPasswordDto,
+        IntrospectionDto, LogoutDto, MfaChallengeDto, MfaLoginDto, OAuthTokenDto, RecoveryCodesDto,
+        RefreshTokenDto, ResetPasswordDto, SessionDto, TotpCodeDto, TotpEnrollmentDto,
+        VerifyEmailDto,
+    },
+    domains::device::{
+        dto::device_dto::{CreateDeviceDto, DeviceDto},
+        DeviceOS, DeviceStatus,
+    },
+    domains::user::dto::user_dto::UserDto,
+};
+use test_helpers::{
+    create_user_with_credentials, deserialize_json_body, login, preflight, read_mailed_token,
+    request, request_from_ip_with_body, request_with_api_key, request_with_auth,
+    request_with_auth_and_body, request_with_body, request_with_config,
+    request_with_config_and_body, request_with_config_headers_and_body, request_with_form,
+    request_with_headers_and_body, request_with_token, request_with_token_and_body, setup_test_db,
+    test_config, TEST_CLIENT_ID, TEST_CLIENT_SECRET, TEST_USER_ID,
 };
-use test_helpers::{deserialize_json_body, request_with_body, TEST_CLIENT_ID, TEST_CLIENT_SECRET};
 
 mod test_helpers;
 
@@ -29,6 +70,323 @@ async fn test_login_user() {
 
     assert_eq!(auth_body.token_type, "Bearer");
     assert!(!auth_body.access_token.is_empty());
+    assert!(!auth_body.refresh_token.unwrap_or_default().is_empty());
+}
+
+#[tokio::test]
+async fn test_register_user() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let url = format!("/user/{}", user_id);
+    let response = request_with_token(Method::GET, url.as_str(), &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_register_duplicate_username() {
+    let (_, username, _) = create_user_with_credentials().await;
+
+    let payload = AuthUserDto {
+        username: username.clone(),
+        email: "duplicate@test.com".to_string(),
+        password: uuid::Uuid::new_v4().to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.message.contains("Username already exists"));
+}
+
+#[tokio::test]
+async fn test_register_invalid_input() {
+    let payload = AuthUserDto {
+        username: format!("testuser-{}", uuid::Uuid::new_v4()),
+        email: "not-an-email".to_string(),
+        password: "short".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    // Nothing was created, so the credentials cannot be used.
+    let payload = AuthPayload {
+        client_id: payload.username,
+        client_secret: payload.password,
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_register_password_policy() {
+    let username = format!("testuser-{}", uuid::Uuid::new_v4());
+    let cases = [
+        ("abc", "Password must be at least 8 characters"),
+        ("abcdefghijkl", "Password must contain at least 2 of"),
+        ("Password123", "Password is too common"),
+        (username.as_str(), "Password must not contain the username"),
+    ];
+
+    for (password, expected) in cases {
+        let payload = AuthUserDto {
+            username: username.clone(),
+            email: format!("{}@test.com", username),
+            password: password.to_string(),
+        };
+        let response = request_with_body(Method::POST, "/auth/register", &payload);
+        let (parts, body) = response.await.into_parts();
+        assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+
+        let response_body: RestApiResponse<HashMap<String, Vec<String>>> =
+            deserialize_json_body(body).await.unwrap();
+        let errors = response_body.0.data.unwrap();
+        assert!(
+            errors["password"].iter().any(|m| m.starts_with(expected)),
+            "{password}: {errors:?}"
+        );
+    }
+}
+
+/// Attempts a login with the given configuration and returns the status code.
+async fn try_login(config: &Config, username: &str, password: &str) -> StatusCode {
+    let payload = AuthPayload {
+        client_id: username.to_string(),
+        client_secret: password.to_string(),
+    };
+    request_with_config_and_body(config.clone(), Method::POST, "/auth/login", &payload)
+        .await
+        .status()
+}
+
+#[tokio::test]
+async fn test_login_unknown_user() {
+    let (_, username, _) = create_user_with_credentials().await;
+
+    let payload = AuthPayload {
+        client_id: format!("unknown-{}", uuid::Uuid::new_v4()),
+        client_secret: "wrong_password".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    let (unknown_parts, unknown_body) = response.await.into_parts();
+
+    let payload = AuthPayload {
+        client_id: username,
+        client_secret: "wrong_password".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    let (wrong_parts, wrong_body) = response.await.into_parts();
+
+    // Unknown users cannot be told apart from wrong passwords.
+    assert_eq!(unknown_parts.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong_parts.status, StatusCode::UNAUTHORIZED);
+    let unknown_body: RestApiResponse<()> = deserialize_json_body(unknown_body).await.unwrap();
+    let wrong_body: RestApiResponse<()> = deserialize_json_body(wrong_body).await.unwrap();
+    assert_eq!(unknown_body.0.message, wrong_body.0.message);
+}
+
+#[tokio::test]
+async fn test_login_backoff() {
+    let config = Config {
+        login_user_backoff_after: 2,
+        login_backoff_base_seconds: 60,
+        ..test_config()
+    };
+    let (_, username, password) = create_user_with_credentials().await;
+
+    assert_eq!(
+        try_login(&config, &username, "wrong_password").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        try_login(&config, &username, "wrong_password").await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Even the right password is rejected until the backoff has passed.
+    assert_eq!(
+        try_login(&config, &username, &password).await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn test_login_success_resets_failed_attempts() {
+    let config = Config {
+        login_user_backoff_after: 3,
+        login_backoff_base_seconds: 60,
+        ..test_config()
+    };
+    let (_, username, password) = create_user_with_credentials().await;
+
+    for _ in 0..2 {
+        for _ in 0..2 {
+            assert_eq!(
+                try_login(&config, &username, "wrong_password").await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(
+            try_login(&config, &username, &password).await,
+            StatusCode::OK
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_login_lockout() {
+    let config = Config {
+        login_user_lockout_after: 3,
+        ..test_config()
+    };
+    let (_, username, password) = create_user_with_credentials().await;
+
+    for _ in 0..3 {
+        assert_eq!(
+            try_login(&config, &username, "wrong_password").await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    let payload = AuthPayload {
+        client_id: username.clone(),
+        client_secret: password,
+    };
+    let response = request_with_config_and_body(config, Method::POST, "/auth/login", &payload);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(parts.headers.contains_key(RETRY_AFTER));
+
+    // The lockout is recorded.
+    let pool = setup_test_db().await.unwrap();
+    let lockouts: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM login_lockouts WHERE scope = 'username' AND key = $1",
+    )
+    .bind(&username)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(lockouts, 1);
+    let events: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT event_type, detail FROM auth_events WHERE username = $1 ORDER BY created_at",
+    )
+    .bind(&username)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let expected = [
+        ("login_failure", "wrong_credentials"),
+        ("login_failure", "wrong_credentials"),
+        ("login_failure", "wrong_credentials"),
+        ("login_lockout", "username"),
+        ("login_failure", "throttled"),
+    ]
+    .map(|(event_type, detail)| (event_type.to_string(), Some(detail.to_string())));
+    assert_eq!(events, expected);
+}
+
+#[tokio::test]
+async fn test_login_ip_lockout() {
+    let config = Config {
+        trust_forwarded_for: true,
+        login_ip_lockout_after: 3,
+        ..test_config()
+    };
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let client_ip = format!("10.{}.{}.{}", bytes[0], bytes[1], bytes[2]);
+
+    // Failed attempts against different users from the same IP add up.
+    for _ in 0..3 {
+        let payload = AuthPayload {
+            client_id: format!("unknown-{}", uuid::Uuid::new_v4()),
+            client_secret: "wrong_password".to_string(),
+        };
+        let response = request_from_ip_with_body(
+            config.clone(),
+            &client_ip,
+            Method::POST,
+            "/auth/login",
+            &payload,
+        );
+        assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let (_, username, password) = create_user_with_credentials().await;
+    let payload = AuthPayload {
+        client_id: username,
+        client_secret: password,
+    };
+    let response =
+        request_from_ip_with_body(config, &client_ip, Method::POST, "/auth/login", &payload);
+    assert_eq!(response.await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+async fn login_test_client() -> AuthBody {
+    let payload = AuthPayload {
+        client_id: TEST_CLIENT_ID.to_string(),
+        client_secret: TEST_CLIENT_SECRET.to_string(),
+    };
+
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+async fn refresh(refresh_token: &str) -> (StatusCode, Option<AuthBody>) {
+    let payload = RefreshTokenDto {
+        refresh_token: refresh_token.to_string(),
+    };
+
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    let (parts, body) = response.await.into_parts();
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    (parts.status, response_body.0.data)
+}
+
+#[tokio::test]
+async fn test_refresh_token() {
+    let auth_body = login_test_client().await;
+    let refresh_token = auth_body.refresh_token.unwrap();
+
+    let (status, refreshed) = refresh(&refresh_token).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let refreshed = refreshed.unwrap();
+    assert_eq!(refreshed.token_type, "Bearer");
+    assert!(!refreshed.access_token.is_empty());
+    assert_ne!(refreshed.refresh_token, Some(refresh_token));
+}
+
+#[tokio::test]
+async fn test_refresh_token_reuse_revokes_family() {
+    let auth_body = login_test_client().await;
+    let first_token = auth_body.refresh_token.unwrap();
+
+    let (status, refreshed) = refresh(&first_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let second_token = refreshed.unwrap().refresh_token.unwrap();
+
+    // Replaying the rotated token is treated as theft.
+    let (status, _) = refresh(&first_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The whole family is revoked, including the latest token.
+    let (status, _) = refresh(&second_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_refresh_token_invalid() {
+    let (status, _) = refresh(&uuid::Uuid::new_v4().to_string()).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
 }
 
 #[tokio::test]
@@ -64,11 +422,1824 @@ async fn test_login_user_not_found() {
 
     let (parts, body) = response.await.into_parts();
 
-    assert_eq!(parts.status, StatusCode::NOT_FOUND);
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
 
     let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
 
-    assert_eq!(response_body.0.status, StatusCode::NOT_FOUND);
+    assert_eq!(response_body.0.status, StatusCode::UNAUTHORIZED);
     println!("response_body.0.status: {:?}", response_body.0.status);
     println!("response_body.0.message: {:?}", response_body.0.message);
 }
+
+#[tokio::test]
+async fn test_logout() {
+    let auth_body = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let refresh_token = auth_body.refresh_token.clone().unwrap();
+
+    let payload = LogoutDto {
+        refresh_token: Some(refresh_token.clone()),
+    };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/logout",
+        &auth_body.access_token,
+        &payload,
+    );
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // The access token is on the revocation list now.
+    let response = request_with_token(Method::GET, "/user", &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    // The refresh token family is revoked as well.
+    let (status, _) = refresh(&refresh_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+/// Logs in with the given `User-Agent` and `X-Device-Id` headers.
+async fn login_from(username: &str, password: &str, headers: &[(&'static str, &str)]) -> AuthBody {
+    let payload = AuthPayload {
+        client_id: username.to_string(),
+        client_secret: password.to_string(),
+    };
+    let response = request_with_headers_and_body(Method::POST, "/auth/login", headers, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+async fn list_sessions(access_token: &str) -> Vec<SessionDto> {
+    let response = request_with_token(Method::GET, "/auth/sessions", access_token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Vec<SessionDto>> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+#[tokio::test]
+async fn test_sessions() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let pool = setup_test_db().await.unwrap();
+    let device_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO devices (id, user_id, name, status, device_os) VALUES ($1, $2, 'phone', 'active', 'iOS')",
+    )
+    .bind(&device_id)
+    .bind(&user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let laptop = login_from(&username, &password, &[("user-agent", "laptop-browser")]).await;
+    let phone = login_from(
+        &username,
+        &password,
+        &[("user-agent", "phone-app"), ("x-device-id", &device_id)],
+    )
+    .await;
+
+    let sessions = list_sessions(&laptop.access_token).await;
+    assert_eq!(sessions.len(), 2);
+    let laptop_session = sessions.iter().find(|s| s.current).unwrap();
+    assert_eq!(laptop_session.user_agent.as_deref(), Some("laptop-browser"));
+    assert_eq!(laptop_session.device_id, None);
+    let phone_session = sessions.iter().find(|s| !s.current).unwrap();
+    assert_eq!(phone_session.user_agent.as_deref(), Some("phone-app"));
+    assert_eq!(phone_session.device_id.as_deref(), Some(device_id.as_str()));
+
+    let url = format!("/auth/sessions/{}", phone_session.id);
+    let response = request_with_token(Method::DELETE, &url, &laptop.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Access and refresh tokens of the revoked session are rejected.
+    let response = request_with_token(Method::GET, "/device", &phone.access_token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    let (status, _) = refresh(&phone.refresh_token.unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let response = request_with_token(Method::DELETE, &url, &laptop.access_token);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    // The remaining session keeps working after a refresh, which stays bound to it.
+    let (status, refreshed) = refresh(&laptop.refresh_token.unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    let sessions = list_sessions(&refreshed.unwrap().access_token).await;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[tokio::test]
+async fn test_revoke_session_of_other_user() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let session_id = list_sessions(&auth_body.access_token).await[0].id.clone();
+
+    let (_, other_username, other_password) = create_user_with_credentials().await;
+    let other = login(&other_username, &other_password).await;
+
+    let url = format!("/auth/sessions/{session_id}");
+    let response = request_with_token(Method::DELETE, &url, &other.access_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/device", &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+fn cookie_config() -> Config {
+    Config {
+        auth_cookie_enabled: true,
+        ..test_config()
+    }
+}
+
+/// Logs in with cookie authentication enabled and returns the `Set-Cookie` headers by cookie name.
+async fn login_with_cookies(username: &str, password: &str) -> HashMap<String, String> {
+    let payload = AuthPayload {
+        client_id: username.to_string(),
+        client_secret: password.to_string(),
+    };
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::POST,
+        "/auth/login",
+        &[],
+        &payload,
+    )
+    .await;
+    let (parts, _) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    set_cookies(&parts.headers)
+}
+
+fn set_cookies(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|value| {
+            let value = value.to_str().unwrap();
+            let (name, _) = value.split_once('=').unwrap();
+            (name.to_string(), value.to_string())
+        })
+        .collect()
+}
+
+fn cookie_value(set_cookie: &str) -> &str {
+    let (_, value) = set_cookie
+        .split(';')
+        .next()
+        .unwrap()
+        .split_once('=')
+        .unwrap();
+    value
+}
+
+#[tokio::test]
+async fn test_cookie_auth_cors() {
+    let config = Config {
+        cors_allowed_origins: vec!["https://app.example.com".into()],
+        ..cookie_config()
+    };
+
+    // Configured origins may send the cookies and the CSRF header cross-origin.
+    let response = preflight(
+        config.clone(),
+        "https://app.example.com",
+        "/device",
+        Method::POST,
+        "x-csrf-token",
+    )
+    .await;
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    assert!(headers["access-control-allow-headers"]
+        .to_str()
+        .unwrap()
+        .contains("x-csrf-token"));
+
+    // Other origins are not allowed.
+    let response = preflight(
+        config,
+        "https://evil.example.com",
+        "/device",
+        Method::POST,
+        "x-csrf-token",
+    )
+    .await;
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+
+    // Without configured origins, credentials are never allowed.
+    let response = preflight(
+        cookie_config(),
+        "https://app.example.com",
+        "/device",
+        Method::POST,
+        "x-csrf-token",
+    )
+    .await;
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    assert!(response
+        .headers()
+        .get("access-control-allow-credentials")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_cookie_auth_disabled_by_default() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let payload = AuthPayload {
+        client_id: username,
+        client_secret: password,
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    assert!(parts.headers.get(SET_COOKIE).is_none());
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let cookie = format!(
+        "access_token={}",
+        response_body.0.data.unwrap().access_token
+    );
+    let response =
+        request_with_headers_and_body(Method::GET, "/device", &[("cookie", &cookie)], &()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_cookie_auth_with_csrf() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let cookies = login_with_cookies(&username, &password).await;
+
+    let access_cookie = &cookies["access_token"];
+    assert!(access_cookie.contains("; Path=/;"));
+    assert!(access_cookie.contains("; SameSite=Strict"));
+    assert!(access_cookie.contains("; HttpOnly"));
+    assert!(access_cookie.contains("; Secure"));
+    assert!(cookies["refresh_token"].contains("; Path=/auth;"));
+    assert!(cookies["refresh_token"].contains("; HttpOnly"));
+    // The frontend has to read the CSRF token.
+    assert!(!cookies["csrf_token"].contains("HttpOnly"));
+
+    let csrf_token = cookie_value(&cookies["csrf_token"]);
+    let cookie = format!(
+        "access_token={}; csrf_token={}",
+        cookie_value(access_cookie),
+        csrf_token
+    );
+
+    // Safe requests do not need the CSRF token.
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::GET,
+        "/device",
+        &[("cookie", &cookie)],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for headers in [
+        vec![("cookie", cookie.as_str())],
+        vec![("cookie", cookie.as_str()), ("x-csrf-token", "wrong")],
+    ] {
+        let response = request_with_config_headers_and_body(
+            cookie_config(),
+            Method::POST,
+            "/auth/logout",
+            &headers,
+            &LogoutDto::default(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::POST,
+        "/auth/logout",
+        &[("cookie", &cookie), ("x-csrf-token", csrf_token)],
+        &LogoutDto::default(),
+    )
+    .await;
+    let (parts, _) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let cleared = set_cookies(&parts.headers);
+    assert!(cleared["access_token"].starts_with("access_token=; Path=/; Max-Age=0;"));
+    assert!(cleared["refresh_token"].contains("Max-Age=0;"));
+
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::GET,
+        "/device",
+        &[("cookie", &cookie)],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_refresh_with_cookie() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let cookies = login_with_cookies(&username, &password).await;
+    let csrf_token = cookie_value(&cookies["csrf_token"]);
+    let cookie = format!(
+        "refresh_token={}; csrf_token={}",
+        cookie_value(&cookies["refresh_token"]),
+        csrf_token
+    );
+    let payload = serde_json::json!({});
+
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::POST,
+        "/auth/refresh",
+        &[("cookie", &cookie)],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::POST,
+        "/auth/refresh",
+        &[("cookie", &cookie), ("x-csrf-token", csrf_token)],
+        &payload,
+    )
+    .await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    // The tokens are only set as cookies, where scripts cannot read them.
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data, None);
+
+    let refreshed = set_cookies(&parts.headers);
+    assert_ne!(refreshed["refresh_token"], cookies["refresh_token"]);
+    let cookie = format!("access_token={}", cookie_value(&refreshed["access_token"]));
+    let response = request_with_config_headers_and_body(
+        cookie_config(),
+        Method::GET,
+        "/device",
+        &[("cookie", &cookie)],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_revoke_all_sessions() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let response = request_with_token(Method::GET, "/device", &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let url = format!("/auth/users/{}/sessions", user_id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, "/device", &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let (status, _) = refresh(&auth_body.refresh_token.unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+fn token_claims(access_token: &str) -> Claims {
+    let validation = make_validation(&test_config());
+    KEYS.decode::<Claims>(access_token, &validation)
+        .unwrap()
+        .claims
+}
+
+#[tokio::test]
+async fn test_impersonate_user() {
+    let (user_id, _, _) = create_user_with_credentials().await;
+
+    let url = format!("/auth/impersonate/{}", user_id);
+    let response = request_with_auth(Method::POST, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let auth_body = response_body.0.data.unwrap();
+    assert!(auth_body.refresh_token.is_none());
+
+    let admin_id = token_claims(&login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await.access_token).sub;
+    let claims = token_claims(&auth_body.access_token);
+    assert_eq!(claims.sub, user_id);
+    assert_eq!(claims.actor_id(), admin_id);
+    assert_eq!(claims.roles, ["user"]);
+
+    // The token acts as the user, but the admin is recorded as the modifier.
+    let payload = CreateDeviceDto {
+        name: format!("test-device-{}", uuid::Uuid::new_v4()),
+        user_id: user_id.clone(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(chrono::Utc::now()),
+        modified_by: user_id.clone(),
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/device", &auth_body.access_token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+    assert_eq!(device.user_id, user_id);
+
+    let pool = setup_test_db().await.unwrap();
+    let (created_by, modified_by): (Option<String>, Option<String>) =
+        sqlx::query_as("SELECT created_by, modified_by FROM devices WHERE id = $1")
+            .bind(&device.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(created_by, Some(admin_id.clone()));
+    assert_eq!(modified_by, Some(admin_id.clone()));
+
+    // The token only holds the permissions of the user.
+    let sessions_url = format!("/auth/users/{}/sessions", TEST_USER_ID);
+    let response = request_with_token(
+        Method::DELETE,
+        sessions_url.as_str(),
+        &auth_body.access_token,
+    );
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let audit: Vec<(String, String, String, i16)> = sqlx::query_as(
+        "SELECT actor_id, method, path, status FROM impersonation_audit WHERE token_id = $1 ORDER BY created_at",
+    )
+    .bind(&claims.jti)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let expected = [
+        ("POST", url.as_str(), 200),
+        ("POST", "/device", 200),
+        ("DELETE", sessions_url.as_str(), 403),
+    ]
+    .map(|(method, path, status)| {
+        (
+            admin_id.clone(),
+            method.to_string(),
+            path.to_string(),
+            status,
+        )
+    });
+    assert_eq!(audit, expected);
+}
+
+#[tokio::test]
+async fn test_impersonate_user_rejected() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    // Regular users lack the `user:impersonate` permission.
+    let url = format!("/auth/impersonate/{}", TEST_USER_ID);
+    let response = request_with_token(Method::POST, url.as_str(), &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Admins cannot impersonate themselves or unknown users.
+    let admin_id = token_claims(&login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await.access_token).sub;
+    let url = format!("/auth/impersonate/{}", admin_id);
+    let response = request_with_auth(Method::POST, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    let url = format!("/auth/impersonate/{}", uuid::Uuid::new_v4());
+    let response = request_with_auth(Method::POST, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_impersonate_user_without_credentials() {
+    // Users created by an admin have no password, but can still be impersonated.
+    let user_id = uuid::Uuid::new_v4().to_string();
+    let pool = setup_test_db().await.unwrap();
+    sqlx::query("INSERT INTO users (id, username, email) VALUES ($1, $1, $1 || '@test.com')")
+        .bind(&user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let url = format!("/auth/impersonate/{}", user_id);
+    let response = request_with_auth(Method::POST, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_impersonation_cannot_create_credentials() {
+    // The user may manage OAuth2 clients, but not while being impersonated.
+    let (user_id, _, _) = create_user_with_credentials().await;
+    let pool = setup_test_db().await.unwrap();
+    let role_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO roles (id, name) VALUES ($1, $1)")
+        .bind(&role_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO role_permissions (role_id, permission_id) \
+         SELECT $1, id FROM permissions WHERE name = 'client:manage'",
+    )
+    .bind(&role_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO user_roles (user_id, role_id) VALUES ($1, $2)")
+        .bind(&user_id)
+        .bind(&role_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let url = format!("/auth/impersonate/{}", user_id);
+    let response = request_with_auth(Method::POST, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let access_token = response_body.0.data.unwrap().access_token;
+
+    let (status, _) = create_api_key(&access_token, &["user:read"]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let payload = CreateOAuthClientDto {
+        name: "integration".to_string(),
+        scopes: vec!["user:read".to_string()],
+        grant_types: vec!["client_credentials".to_string()],
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/auth/oauth/clients", &access_token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+async fn list_auth_events(query: &str) -> (StatusCode, Option<PageDto<AuthEventDto>>) {
+    let url = format!("/auth/events?{query}");
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (parts, body) = response.await.into_parts();
+
+    let response_body: RestApiResponse<PageDto<AuthEventDto>> =
+        deserialize_json_body(body).await.unwrap();
+    (parts.status, response_body.0.data)
+}
+
+#[tokio::test]
+async fn test_auth_events() {
+    let config = Config {
+        trust_forwarded_for: true,
+        ..test_config()
+    };
+    let (user_id, username, password) = create_user_with_credentials().await;
+
+    let payload = AuthPayload {
+        client_id: username.clone(),
+        client_secret: "wrong_password".to_string(),
+    };
+    let response =
+        request_from_ip_with_body(config, "203.0.113.7", Method::POST, "/auth/login", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    let auth_body = login(&username, &password).await;
+    let (status, _) = refresh(&auth_body.refresh_token.unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, page) = list_auth_events(&format!("user_id={user_id}")).await;
+    assert_eq!(status, StatusCode::OK);
+    let page = page.unwrap();
+    assert_eq!(page.total, 3);
+    let event_types: Vec<_> = page.items.iter().map(|e| e.event_type.as_str()).collect();
+    assert_eq!(
+        event_types,
+        ["token_refresh", "login_success", "login_failure"]
+    );
+    let failure = &page.items[2];
+    assert_eq!(failure.username.as_deref(), Some(username.as_str()));
+    assert_eq!(failure.ip_address.as_deref(), Some("203.0.113.7"));
+    assert_eq!(failure.detail.as_deref(), Some("wrong_credentials"));
+
+    let (_, page) = list_auth_events(&format!("user_id={user_id}&event_type=login_success")).await;
+    let page = page.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].event_type, "login_success");
+
+    let (_, page) = list_auth_events(&format!("user_id={user_id}&page=2&page_size=2")).await;
+    let page = page.unwrap();
+    assert_eq!((page.total, page.page, page.page_size), (3, 2, 2));
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].event_type, "login_failure");
+
+    let hour_ago = (chrono::Utc::now() - chrono::Duration::hours(1)).format("%Y-%m-%dT%H:%M:%SZ");
+    let (_, page) = list_auth_events(&format!("user_id={user_id}&from={hour_ago}")).await;
+    assert_eq!(page.unwrap().total, 3);
+    let (_, page) = list_auth_events(&format!("user_id={user_id}&to={hour_ago}")).await;
+    assert_eq!(page.unwrap().total, 0);
+}
+
+#[tokio::test]
+async fn test_auth_events_invalid_query() {
+    for query in [
+        "page_size=1000",
+        "page=0",
+        "event_type=unknown",
+        "from=yesterday",
+    ] {
+        let url = format!("/auth/events?{query}");
+        let response = request_with_auth(Method::GET, url.as_str());
+        assert_eq!(response.await.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn test_auth_events_forbidden() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let response = request_with_token(Method::GET, "/auth/events", &auth_body.access_token);
+
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_revoke_all_sessions_forbidden() {
+    let (user_id, _, _) = create_user_with_credentials().await;
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let url = format!("/auth/users/{}/sessions", user_id);
+    let response = request_with_token(Method::DELETE, url.as_str(), &auth_body.access_token);
+
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_change_password() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let payload = ChangePasswordDto {
+        current_password: "wrong_password".to_string(),
+        new_password: new_password.clone(),
+    };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/password/change",
+        &auth_body.access_token,
+        &payload,
+    );
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let payload = ChangePasswordDto {
+        current_password: password.clone(),
+        new_password: format!("{username}-2"),
+    };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/password/change",
+        &auth_body.access_token,
+        &payload,
+    );
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+    let response_body: RestApiResponse<HashMap<String, Vec<String>>> =
+        deserialize_json_body(body).await.unwrap();
+    assert_eq!(
+        response_body.0.data.unwrap()["new_password"],
+        ["Password must not contain the username"]
+    );
+
+    let payload = ChangePasswordDto {
+        current_password: password.clone(),
+        new_password: new_password.clone(),
+    };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/password/change",
+        &auth_body.access_token,
+        &payload,
+    );
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let payload = AuthPayload {
+        client_id: username.clone(),
+        client_secret: password,
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    login(&username, &new_password).await;
+}
+
+#[tokio::test]
+async fn test_login_rehashes_outdated_password() {
+    let (user_id, username, _) = create_user_with_credentials().await;
+    let pool = setup_test_db().await.unwrap();
+
+    // An argon2i hash of "mySecretPassword" with other parameters, as written by older clients.
+    let legacy_hash = "$argon2i$v=19$m=65536,t=2,p=1$vNVL5PZ1hRwgLUlGmCQVTA$fg1d0/f8pdtMnzQTeh2YE6R0E8vfqMOQOs5k6Y22Qi0";
+    sqlx::query("UPDATE user_auth SET password_hash = $1 WHERE user_id = $2")
+        .bind(legacy_hash)
+        .bind(&user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    login(&username, "mySecretPassword").await;
+
+    let config = test_config();
+    let password_hash: String =
+        sqlx::query_scalar("SELECT password_hash FROM user_auth WHERE user_id = $1")
+            .bind(&user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(password_hash.starts_with(&format!(
+        "$argon2id$v=19$m={},t={},p={}$",
+        config.argon2_memory_kib, config.argon2_iterations, config.argon2_parallelism
+    )));
+
+    // The new hash still verifies the same password.
+    login(&username, "mySecretPassword").await;
+}
+
+#[tokio::test]
+async fn test_reset_password() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let payload = ForgotPasswordDto {
+        username: username.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/forgot", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let payload = ResetPasswordDto {
+        token: read_mailed_token(&format!("{username}@test.com"), "Reset token"),
+        new_password: uuid::Uuid::new_v4().to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/reset", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    login(&username, &payload.new_password).await;
+
+    // Every session from before the reset is revoked.
+    let response = request_with_token(Method::GET, "/device", &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    let (status, _) = refresh(&auth_body.refresh_token.unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The token can only be used once.
+    let response = request_with_body(Method::POST, "/auth/password/reset", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_forgot_password_unknown_user() {
+    let payload = ForgotPasswordDto {
+        username: format!("unknown-{}", uuid::Uuid::new_v4()),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/forgot", &payload);
+
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_forgot_password_mail_failure() {
+    // Nothing listens on the SMTP port, so the mail cannot be sent.
+    let config = Config {
+        mail_transport: "smtp".into(),
+        smtp_host: "127.0.0.1".into(),
+        smtp_port: 1,
+        ..test_config()
+    };
+    let (_, username, _) = create_user_with_credentials().await;
+
+    // Existing accounts respond like unknown usernames.
+    for username in [username, format!("unknown-{}", uuid::Uuid::new_v4())] {
+        let payload = ForgotPasswordDto { username };
+        let response = request_with_config_and_body(
+            config.clone(),
+            Method::POST,
+            "/auth/password/forgot",
+            &payload,
+        );
+        assert_eq!(response.await.status(), StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_reset_password_invalid_token() {
+    let payload = ResetPasswordDto {
+        token: "invalid".to_string(),
+        new_password: uuid::Uuid::new_v4().to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/reset", &payload);
+
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_verify_email() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let url = format!("/user/{}", user_id);
+
+    let response = request_with_token(Method::GET, url.as_str(), &auth_body.access_token);
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.data.unwrap().email_verified_at.is_none());
+
+    let payload = VerifyEmailDto {
+        token: read_mailed_token(&format!("{username}@test.com"), "Verification token"),
+    };
+    let response = request_with_body(Method::POST, "/auth/verify-email", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, url.as_str(), &auth_body.access_token);
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.data.unwrap().email_verified_at.is_some());
+
+    // The token can only be used once.
+    let response = request_with_body(Method::POST, "/auth/verify-email", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_verify_email_invalid_token() {
+    let payload = VerifyEmailDto {
+        token: "invalid".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/verify-email", &payload);
+
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_login_requires_verified_email() {
+    let config = Config {
+        require_verified_email: true,
+        ..test_config()
+    };
+    let (_, username, password) = create_user_with_credentials().await;
+    let payload = AuthPayload {
+        client_id: username.clone(),
+        client_secret: password,
+    };
+
+    let response =
+        request_with_config_and_body(config.clone(), Method::POST, "/auth/login", &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let verify_payload = VerifyEmailDto {
+        token: read_mailed_token(&format!("{username}@test.com"), "Verification token"),
+    };
+    let response = request_with_body(Method::POST, "/auth/verify-email", &verify_payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_config_and_body(config, Method::POST, "/auth/login", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+/// Enrolls and confirms TOTP for the user.
+/// Returns the secret, the code that confirmed the enrollment and the recovery codes.
+async fn enable_mfa(access_token: &str) -> (String, String, Vec<String>) {
+    let response = request_with_token(Method::POST, "/auth/mfa/totp/enroll", access_token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<TotpEnrollmentDto> =
+        deserialize_json_body(body).await.unwrap();
+    let enrollment = response_body.0.data.unwrap();
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+
+    let code = totp::generate_code(&enrollment.secret, chrono::Utc::now().timestamp()).unwrap();
+    let payload = TotpCodeDto { code: code.clone() };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/mfa/totp/confirm",
+        access_token,
+        &payload,
+    );
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<RecoveryCodesDto> =
+        deserialize_json_body(body).await.unwrap();
+
+    (
+        enrollment.secret,
+        code,
+        response_body.0.data.unwrap().recovery_codes,
+    )
+}
+
+/// Logs in with the password and returns the MFA challenge.
+async fn start_mfa_login(username: &str, password: &str) -> MfaChallengeDto {
+    let payload = AuthPayload {
+        client_id: username.to_string(),
+        client_secret: password.to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<MfaChallengeDto> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+async fn login_mfa(config: Config, mfa_token: &str, code: &str) -> (StatusCode, Option<AuthBody>) {
+    let payload = MfaLoginDto {
+        mfa_token: mfa_token.to_string(),
+        code: code.to_string(),
+    };
+    let response = request_with_config_and_body(config, Method::POST, "/auth/login/mfa", &payload);
+    let (parts, body) = response.await.into_parts();
+    if parts.status != StatusCode::OK {
+        return (parts.status, None);
+    }
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    (parts.status, response_body.0.data)
+}
+
+#[tokio::test]
+async fn test_mfa_login() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let (secret, used_code, recovery_codes) = enable_mfa(&auth_body.access_token).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    // MFA cannot be enrolled again once enabled.
+    let response = request_with_token(
+        Method::POST,
+        "/auth/mfa/totp/enroll",
+        &auth_body.access_token,
+    );
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    let challenge = start_mfa_login(&username, &password).await;
+    assert!(!challenge.mfa_token.is_empty());
+
+    // The code that confirmed the enrollment cannot be replayed.
+    let (status, _) = login_mfa(test_config(), &challenge.mfa_token, &used_code).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let code = totp::generate_code(&secret, chrono::Utc::now().timestamp() + 30).unwrap();
+    let (status, auth_body) = login_mfa(test_config(), &challenge.mfa_token, &code).await;
+    assert_eq!(status, StatusCode::OK);
+    let auth_body = auth_body.unwrap();
+    assert!(!auth_body.access_token.is_empty());
+    assert!(auth_body.refresh_token.is_some());
+
+    // The challenge is single-use.
+    let (status, _) = login_mfa(test_config(), &challenge.mfa_token, &code).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_mfa_login_with_recovery_code() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let (_, _, recovery_codes) = enable_mfa(&auth_body.access_token).await;
+
+    // Recovery codes are accepted regardless of case and dashes.
+    let code = recovery_codes[0].to_uppercase().replace('-', "");
+    let challenge = start_mfa_login(&username, &password).await;
+    let (status, _) = login_mfa(test_config(), &challenge.mfa_token, &code).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A recovery code can only be used once.
+    let challenge = start_mfa_login(&username, &password).await;
+    let (status, _) = login_mfa(test_config(), &challenge.mfa_token, &recovery_codes[0]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = login_mfa(test_config(), &challenge.mfa_token, &recovery_codes[1]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_mfa_challenge_max_attempts() {
+    let config = Config {
+        mfa_max_attempts: 2,
+        ..test_config()
+    };
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let (secret, _, _) = enable_mfa(&auth_body.access_token).await;
+
+    let challenge = start_mfa_login(&username, &password).await;
+    for _ in 0..2 {
+        let (status, _) = login_mfa(config.clone(), &challenge.mfa_token, "000000").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    // The challenge has been given up, so even a valid code is rejected.
+    let code = totp::generate_code(&secret, chrono::Utc::now().timestamp() + 30).unwrap();
+    let (status, _) = login_mfa(config, &challenge.mfa_token, &code).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_confirm_totp_invalid_code() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let payload = TotpCodeDto {
+        code: "123456".to_string(),
+    };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/mfa/totp/confirm",
+        &auth_body.access_token,
+        &payload,
+    );
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    // Without a confirmed enrollment, login does not require a second factor.
+    let response = request_with_token(
+        Method::POST,
+        "/auth/mfa/totp/enroll",
+        &auth_body.access_token,
+    );
+    assert_eq!(response.await.status(), StatusCode::OK);
+    login(&username, &password).await;
+}
+
+async fn create_api_key(
+    access_token: &str,
+    scopes: &[&str],
+) -> (StatusCode, Option<CreatedApiKeyDto>) {
+    let payload = CreateApiKeyDto {
+        name: "gateway".to_string(),
+        scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        expires_at: None,
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/auth/api-keys", access_token, &payload);
+    let (parts, body) = response.await.into_parts();
+    if parts.status != StatusCode::OK {
+        return (parts.status, None);
+    }
+
+    let response_body: RestApiResponse<CreatedApiKeyDto> =
+        deserialize_json_body(body).await.unwrap();
+    (parts.status, response_body.0.data)
+}
+
+#[tokio::test]
+async fn test_api_key() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let (status, created) = create_api_key(&auth_body.access_token, &["user:read"]).await;
+    assert_eq!(status, StatusCode::OK);
+    let created = created.unwrap();
+    assert!(created
+        .key
+        .starts_with(&format!("{}.", created.api_key.prefix)));
+
+    // The key acts on behalf of the user...
+    let url = format!("/user/{}", user_id);
+    let response = request_with_api_key(Method::GET, &url, &created.key);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // ...but only within its scopes.
+    let response = request_with_api_key(Method::GET, "/device", &created.key);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/auth/api-keys", &auth_body.access_token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Vec<ApiKeyDto>> = deserialize_json_body(body).await.unwrap();
+    let api_keys = response_body.0.data.unwrap();
+    assert_eq!(api_keys.len(), 1);
+    assert_eq!(api_keys[0].scopes, vec!["user:read".to_string()]);
+    assert!(api_keys[0].last_used_at.is_some());
+
+    let url = format!("/auth/api-keys/{}", created.api_key.id);
+    let response = request_with_token(Method::DELETE, &url, &auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let url = format!("/user/{}", user_id);
+    let response = request_with_api_key(Method::GET, &url, &created.key);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_key_cors() {
+    // Browser clients may send the key cross-origin.
+    let response = preflight(
+        test_config(),
+        "https://app.example.com",
+        "/device",
+        Method::GET,
+        "x-api-key",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let allowed = response.headers()["access-control-allow-headers"]
+        .to_str()
+        .unwrap();
+    assert!(allowed.contains("x-api-key"));
+}
+
+#[tokio::test]
+async fn test_create_api_key_scope_not_granted() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let (status, _) = create_api_key(&auth_body.access_token, &["session:revoke"]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = create_api_key(&auth_body.access_token, &[]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_key_invalid() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let (_, created) = create_api_key(&auth_body.access_token, &["user:read"]).await;
+    let created = created.unwrap();
+
+    let url = format!("/user/{}", TEST_USER_ID);
+    let wrong_secret = format!("{}.{}", created.api_key.prefix, uuid::Uuid::new_v4());
+    let response = request_with_api_key(Method::GET, &url, &wrong_secret);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let response = request_with_api_key(Method::GET, &url, "not-an-api-key");
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_delete_api_key_of_other_user() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    let (_, created) = create_api_key(&auth_body.access_token, &["user:read"]).await;
+
+    let (_, other_username, other_password) = create_user_with_credentials().await;
+    let other_auth_body = login(&other_username, &other_password).await;
+
+    let url = format!("/auth/api-keys/{}", created.unwrap().api_key.id);
+    let response = request_with_token(Method::DELETE, &url, &other_auth_body.access_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+/// Registers an OAuth2 client as the admin.
+async fn create_oauth_client(grant_types: &[&str], scopes: &[&str]) -> CreatedOAuthClientDto {
+    let payload = CreateOAuthClientDto {
+        name: "integration".to_string(),
+        scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        grant_types: grant_types.iter().map(|g| g.to_string()).collect(),
+    };
+    let response = request_with_auth_and_body(Method::POST, "/auth/oauth/clients", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<CreatedOAuthClientDto> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+/// Requests tokens at the OAuth2 token endpoint with HTTP Basic client authentication.
+/// Returns the token on success, or the OAuth2 error code.
+async fn oauth_token(
+    client: &CreatedOAuthClientDto,
+    form: &[(&str, &str)],
+) -> (StatusCode, Result<OAuthTokenDto, String>) {
+    let credentials = (
+        client.client.client_id.as_str(),
+        client.client_secret.as_str(),
+    );
+    let response = request_with_form("/oauth/token", Some(credentials), form);
+    let (parts, body) = response.await.into_parts();
+    if parts.status != StatusCode::OK {
+        let error: serde_json::Value = deserialize_json_body(body).await.unwrap();
+        return (
+            parts.status,
+            Err(error["error"].as_str().unwrap().to_string()),
+        );
+    }
+
+    (parts.status, Ok(deserialize_json_body(body).await.unwrap()))
+}
+
+async fn introspect(client: &CreatedOAuthClientDto, token: &str) -> IntrospectionDto {
+    let credentials = (
+        client.client.client_id.as_str(),
+        client.client_secret.as_str(),
+    );
+    let response =
+        request_with_form("/oauth/introspect", Some(credentials), &[("token", token)]).await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    deserialize_json_body(body).await.unwrap()
+}
+
+async fn revoke(client: &CreatedOAuthClientDto, token: &str) {
+    let credentials = (
+        client.client.client_id.as_str(),
+        client.client_secret.as_str(),
+    );
+    let response = request_with_form("/oauth/revoke", Some(credentials), &[("token", token)]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_oauth_client_credentials() {
+    let client = create_oauth_client(&["client_credentials"], &["user:read"]).await;
+
+    let (status, token) = oauth_token(&client, &[("grant_type", "client_credentials")]).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = token.unwrap();
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.scope, "user:read");
+    assert!(token.refresh_token.is_none());
+
+    // The token acts on behalf of the admin who registered the client, within the scope.
+    let url = format!("/user/{}", TEST_USER_ID);
+    let response = request_with_token(Method::GET, &url, &token.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_token(Method::GET, "/device", &token.access_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let introspection = introspect(&client, &token.access_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.scope.as_deref(), Some("user:read"));
+    assert_eq!(
+        introspection.client_id.as_deref(),
+        Some(client.client.client_id.as_str())
+    );
+    assert_eq!(introspection.username.as_deref(), Some(TEST_CLIENT_ID));
+
+    revoke(&client, &token.access_token).await;
+    let response = request_with_token(Method::GET, &url, &token.access_token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    assert!(!introspect(&client, &token.access_token).await.active);
+}
+
+#[tokio::test]
+async fn test_oauth_password_and_refresh_grant() {
+    let client = create_oauth_client(
+        &["password", "refresh_token"],
+        &["device:read", "user:read"],
+    )
+    .await;
+    let (_, username, password) = create_user_with_credentials().await;
+
+    // Client credentials may also be sent in the form body.
+    let response = request_with_form(
+        "/oauth/token",
+        None,
+        &[
+            ("grant_type", "password"),
+            ("username", &username),
+            ("password", &password),
+            ("scope", "device:read"),
+            ("client_id", &client.client.client_id),
+            ("client_secret", &client.client_secret),
+        ],
+    )
+    .await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let token: OAuthTokenDto = deserialize_json_body(body).await.unwrap();
+    assert_eq!(token.scope, "device:read");
+    let refresh_token = token.refresh_token.unwrap();
+
+    let response = request_with_token(Method::GET, "/device", &token.access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let introspection = introspect(&client, &refresh_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.token_type.as_deref(), Some("refresh_token"));
+
+    // The scope of a refreshed token cannot be widened.
+    let (status, error) = oauth_token(
+        &client,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &refresh_token),
+            ("scope", "user:read"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.unwrap_err(), "invalid_scope");
+
+    // Refresh tokens of a client are only accepted at the token endpoint.
+    let payload = RefreshTokenDto {
+        refresh_token: refresh_token.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let (status, refreshed) = oauth_token(
+        &client,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &refresh_token),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let refreshed = refreshed.unwrap();
+    assert_eq!(refreshed.scope, "device:read");
+    assert!(!introspect(&client, &refresh_token).await.active);
+
+    let new_refresh_token = refreshed.refresh_token.unwrap();
+    revoke(&client, &new_refresh_token).await;
+    assert!(!introspect(&client, &new_refresh_token).await.active);
+}
+
+#[tokio::test]
+async fn test_oauth_token_errors() {
+    let client = create_oauth_client(&["password"], &["user:read"]).await;
+    let (_, username, password) = create_user_with_credentials().await;
+
+    let response = request_with_form(
+        "/oauth/token",
+        Some((&client.client.client_id, "wrong-secret")),
+        &[("grant_type", "client_credentials")],
+    );
+    let response = response.await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+
+    let cases: [(&[(&str, &str)], &str); 5] = [
+        (&[("scope", "user:read")], "invalid_request"),
+        (
+            &[("grant_type", "authorization_code")],
+            "unsupported_grant_type",
+        ),
+        (
+            &[("grant_type", "client_credentials")],
+            "unauthorized_client",
+        ),
+        (
+            &[
+                ("grant_type", "password"),
+                ("username", &username),
+                ("password", &password),
+                ("scope", "device:read"),
+            ],
+            "invalid_scope",
+        ),
+        (
+            &[
+                ("grant_type", "password"),
+                ("username", &username),
+                ("password", "wrong_password"),
+            ],
+            "invalid_grant",
+        ),
+    ];
+    for (form, expected) in cases {
+        let (status, error) = oauth_token(&client, form).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.unwrap_err(), expected);
+    }
+}
+
+#[tokio::test]
+async fn test_create_oauth_client_forbidden() {
+    let (_, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let payload = CreateOAuthClientDto {
+        name: "integration".to_string(),
+        scopes: vec!["user:read".to_string()],
+        grant_types: vec!["client_credentials".to_string()],
+    };
+    let response = request_with_token_and_body(
+        Method::POST,
+        "/auth/oauth/clients",
+        &auth_body.access_token,
+        &payload,
+    );
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_revoke_all_sessions_user_not_found() {
+    let url = format!("/auth/users/{}/sessions", uuid::Uuid::new_v4());
+    let response = request_with_auth(Method::DELETE, url.as_str());
+
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_jwks() {
+    let response = request(Method::GET, "/.well-known/jwks.json");
+
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    // The key set is published as is, without the API response envelope.
+    let jwks: serde_json::Value = deserialize_json_body(body).await.unwrap();
+    assert!(jwks["keys"].is_array());
+}
+
+/// Signs an access token for the test user after applying `modify` to its claims.
+fn sign_token(modify: impl FnOnce(&mut Claims)) -> String {
+    let mut claims = Claims::new(TEST_USER_ID, vec!["user".to_string()], &test_config());
+    modify(&mut claims);
+    KEYS.encode(&claims).unwrap()
+}
+
+#[tokio::test]
+async fn test_token_claims_validation() {
+    let token = sign_token(|_| {});
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // minted for another environment
+    let token = sign_token(|claims| claims.aud = "another-service".to_string());
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let token = sign_token(|claims| claims.iss = "another-issuer".to_string());
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    // not valid yet
+    let token = sign_token(|claims| claims.nbf += 3600);
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    // expired beyond the leeway
+    let token = sign_token(|claims| {
+        claims.iat -= 7200;
+        claims.nbf -= 7200;
+        claims.exp = claims.iat + 60;
+    });
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+const OIDC_CLIENT_ID: &str = "test-client";
+const OIDC_CLIENT_SECRET: &str = "test-secret";
+
+/// Authorization code issued by the mock OpenID Connect provider: the PKCE code challenge
+/// of the login and the claims of the ID token it is exchanged for.
+type MockIdpCodes = Arc<Mutex<HashMap<String, (String, serde_json::Value)>>>;
+
+/// A local OpenID Connect provider serving discovery, JWKS and the token endpoint.
+/// Users "authenticate" by registering an authorization code with `authorize`.
+struct MockIdp {
+    issuer: String,
+    codes: MockIdpCodes,
+}
+
+impl MockIdp {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let codes = MockIdpCodes::default();
+        let keys = Arc::new(
+            KeyRing::from_dir(
+                std::path::Path::new("tests/asset/jwt"),
+                Some("2025-06"),
+                None,
+            )
+            .unwrap(),
+        );
+
+        let discovery = serde_json::json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+            "jwks_uri": format!("{issuer}/jwks"),
+        });
+        let jwks = serde_json::to_value(keys.jwks()).unwrap();
+        let token_codes = codes.clone();
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(discovery) }),
+            )
+            .route("/jwks", get(move || async move { Json(jwks) }))
+            .route(
+                "/token",
+                post(
+                    move |headers: HeaderMap, Form(form): Form<HashMap<String, String>>| async move {
+                        let credentials =
+                            STANDARD.encode(format!("{OIDC_CLIENT_ID}:{OIDC_CLIENT_SECRET}"));
+                        if headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok())
+                            != Some(format!("Basic {credentials}").as_str())
+                        {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+
+                        let (challenge, claims) = token_codes
+                            .lock()
+                            .unwrap()
+                            .remove(&form["code"])
+                            .ok_or(StatusCode::BAD_REQUEST)?;
+                        let verifier = form.get("code_verifier").ok_or(StatusCode::BAD_REQUEST)?;
+                        if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) != challenge {
+                            return Err(StatusCode::BAD_REQUEST);
+                        }
+
+                        let id_token = keys.encode(&claims).unwrap();
+                        Ok(Json(serde_json::json!({
+                            "access_token": "idp-access-token",
+                            "token_type": "Bearer",
+                            "id_token": id_token,
+                        })))
+                    },
+                ),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockIdp { issuer, codes }
+    }
+
+    fn config(&self, auto_provision: bool) -> Config {
+        Config {
+            oidc_discovery_url: Some(format!("{}/.well-known/openid-configuration", self.issuer)),
+            oidc_client_id: OIDC_CLIENT_ID.to_string(),
+            oidc_client_secret: Some(OIDC_CLIENT_SECRET.to_string()),
+            oidc_auto_provision: auto_provision,
+            ..test_config()
+        }
+    }
+
+    /// Follows the redirect of a login to the provider and authenticates the subject there.
+    /// `claims` are added to the ID token, overriding the defaults.
+    /// Returns the URI of the redirect back to the callback.
+    async fn authorize(&self, config: &Config, subject: &str, claims: serde_json::Value) -> String {
+        let response = request_with_config(config.clone(), Method::GET, "/auth/oidc/login").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()[LOCATION].to_str().unwrap().to_string();
+
+        let (endpoint, query) = location.split_once('?').unwrap();
+        assert_eq!(endpoint, format!("{}/authorize", self.issuer));
+        let params: HashMap<String, String> = serde_urlencoded::from_str(query).unwrap();
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], OIDC_CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let now = chrono::Utc::now().timestamp();
+        let mut id_token = serde_json::json!({
+            "iss": self.issuer,
+            "aud": OIDC_CLIENT_ID,
+            "sub": subject,
+            "iat": now,
+            "exp": now + 300,
+            "nonce": params["nonce"],
+        });
+        for (name, value) in claims.as_object().unwrap() {
+            id_token[name] = value.clone();
+        }
+
+        let code = uuid::Uuid::new_v4().to_string();
+        self.codes
+            .lock()
+            .unwrap()
+            .insert(code.clone(), (params["code_challenge"].clone(), id_token));
+        format!("/auth/oidc/callback?code={code}&state={}", params["state"])
+    }
+}
+
+/// Sends the redirect back from the provider and returns the issued tokens, if any.
+async fn oidc_callback(config: Config, callback_uri: &str) -> (StatusCode, Option<AuthBody>) {
+    let response = request_with_config(config, Method::GET, callback_uri).await;
+    let (parts, body) = response.into_parts();
+    if parts.status != StatusCode::OK {
+        return (parts.status, None);
+    }
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    (parts.status, response_body.0.data)
+}
+
+fn token_subject(auth_body: &AuthBody) -> String {
+    KEYS.decode::<Claims>(&auth_body.access_token, &make_validation(&test_config()))
+        .unwrap()
+        .claims
+        .sub
+}
+
+#[tokio::test]
+async fn test_oidc_login_auto_provision() {
+    let idp = MockIdp::start().await;
+    let config = idp.config(true);
+    let subject = uuid::Uuid::new_v4().to_string();
+    let username = format!("oidc-{subject}");
+    let claims = serde_json::json!({
+        "preferred_username": username,
+        "email": format!("{username}@test.com"),
+        "email_verified": true,
+    });
+
+    let callback_uri = idp.authorize(&config, &subject, claims.clone()).await;
+    let (status, auth_body) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    let user_id = token_subject(&auth_body.unwrap());
+
+    let pool = setup_test_db().await.unwrap();
+    let (stored_username, verified): (String, bool) =
+        sqlx::query_as("SELECT username, email_verified_at IS NOT NULL FROM users WHERE id = $1")
+            .bind(&user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored_username, username);
+    assert!(verified);
+
+    // The state of a login can only be used once.
+    let (status, _) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The next login maps the subject to the same user.
+    let callback_uri = idp.authorize(&config, &subject, claims).await;
+    let (status, auth_body) = oidc_callback(config, &callback_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(token_subject(&auth_body.unwrap()), user_id);
+}
+
+#[tokio::test]
+async fn test_oidc_login_requires_verified_email() {
+    let idp = MockIdp::start().await;
+    let config = Config {
+        require_verified_email: true,
+        ..idp.config(true)
+    };
+    let subject = uuid::Uuid::new_v4().to_string();
+    let username = format!("oidc-{subject}");
+    let claims = serde_json::json!({
+        "preferred_username": username,
+        "email": format!("{username}@test.com"),
+        "email_verified": false,
+    });
+
+    // The user is provisioned, but cannot log in with an unverified email address.
+    let callback_uri = idp.authorize(&config, &subject, claims.clone()).await;
+    let (status, auth_body) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(auth_body.is_none());
+
+    let pool = setup_test_db().await.unwrap();
+    let events: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT event_type, detail FROM auth_events WHERE username = $1")
+            .bind(&username)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        events,
+        [(
+            "login_failure".to_string(),
+            Some("email_not_verified".to_string())
+        )]
+    );
+
+    // Once the email address is verified, the login succeeds.
+    sqlx::query("UPDATE users SET email_verified_at = NOW() WHERE username = $1")
+        .bind(&username)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let callback_uri = idp.authorize(&config, &subject, claims).await;
+    let (status, auth_body) = oidc_callback(config, &callback_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(auth_body.is_some());
+}
+
+#[tokio::test]
+async fn test_oidc_provisioned_user_mfa() {
+    let idp = MockIdp::start().await;
+    let config = idp.config(true);
+    let subject = uuid::Uuid::new_v4().to_string();
+    let username = format!("oidc-{subject}");
+    let claims = serde_json::json!({
+        "preferred_username": username,
+        "email": format!("{username}@test.com"),
+        "email_verified": true,
+    });
+
+    // Provisioned users have no password, but can still enroll TOTP.
+    let callback_uri = idp.authorize(&config, &subject, claims.clone()).await;
+    let (status, auth_body) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    let first_login = auth_body.unwrap();
+    let (secret, _, _) = enable_mfa(&first_login.access_token).await;
+
+    // The next login is challenged for the second factor.
+    let callback_uri = idp.authorize(&config, &subject, claims).await;
+    let response = request_with_config(config.clone(), Method::GET, &callback_uri).await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<MfaChallengeDto> =
+        deserialize_json_body(body).await.unwrap();
+    let challenge = response_body.0.data.unwrap();
+
+    let code = totp::generate_code(&secret, chrono::Utc::now().timestamp() + 30).unwrap();
+    let (status, auth_body) = login_mfa(config, &challenge.mfa_token, &code).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        token_subject(&auth_body.unwrap()),
+        token_subject(&first_login)
+    );
+}
+
+#[tokio::test]
+async fn test_oidc_login_links_verified_email() {
+    let idp = MockIdp::start().await;
+    let config = idp.config(false);
+    let (user_id, username, _) = create_user_with_credentials().await;
+    let email = format!("{username}@test.com");
+    let subject = uuid::Uuid::new_v4().to_string();
+
+    // Without auto-provisioning, an identity that cannot be linked is rejected.
+    let claims = serde_json::json!({ "email": email, "email_verified": true });
+    let callback_uri = idp.authorize(&config, &subject, claims.clone()).await;
+    let (status, _) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let payload = VerifyEmailDto {
+        token: read_mailed_token(&email, "Verification token"),
+    };
+    let response = request_with_body(Method::POST, "/auth/verify-email", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // The provider must have verified the email address as well.
+    let unverified = serde_json::json!({ "email": email, "email_verified": false });
+    let callback_uri = idp.authorize(&config, &subject, unverified).await;
+    let (status, _) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let callback_uri = idp.authorize(&config, &subject, claims).await;
+    let (status, auth_body) = oidc_callback(config.clone(), &callback_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(token_subject(&auth_body.unwrap()), user_id);
+
+    // Once linked, the email address no longer matters.
+    let callback_uri = idp
+        .authorize(&config, &subject, serde_json::json!({}))
+        .await;
+    let (status, auth_body) = oidc_callback(config, &callback_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(token_subject(&auth_body.unwrap()), user_id);
+}
+
+#[tokio::test]
+async fn test_oidc_login_invalid_id_token() {
+    let idp = MockIdp::start().await;
+    let config = idp.config(true);
+    let subject = uuid::Uuid::new_v4().to_string();
+
+    for claims in [
+        serde_json::json!({ "aud": "another-client" }),
+        serde_json::json!({ "iss": "https://another-issuer" }),
+        serde_json::json!({ "nonce": "another-nonce" }),
+        serde_json::json!({ "exp": chrono::Utc::now().timestamp() - 3600 }),
+    ] {
+        let callback_uri = idp.authorize(&config, &subject, claims).await;
+        let (status, _) = oidc_callback(config.clone(), &callback_uri).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+}
+
+#[tokio::test]
+async fn test_oidc_callback_errors() {
+    let idp = MockIdp::start().await;
+    let config = idp.config(true);
+
+    let (status, _) = oidc_callback(
+        config.clone(),
+        "/auth/oidc/callback?code=code&state=unknown-state",
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = oidc_callback(
+        config.clone(),
+        "/auth/oidc/callback?error=access_denied&state=unknown-state",
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = oidc_callback(config, "/auth/oidc/callback").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_oidc_login_not_configured() {
+    let response = request(Method::GET, "/auth/oidc/login");
+
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
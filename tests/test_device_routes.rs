@@ -1,6 +1,6 @@
 use axum::http::{Method, StatusCode};
 
-use clean_axum_demo::common::dto::RestApiResponse;
+use clean_axum_demo::common::{config::Config, dto::RestApiResponse};
 use clean_axum_demo::domains::device::dto::device_dto::{
     CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateDeviceDtoWithIdDto, UpdateManyDevicesDto,
 };
@@ -9,7 +9,10 @@ use clean_axum_demo::domains::device::{DeviceOS, DeviceStatus};
 use uuid::Uuid;
 mod test_helpers;
 use test_helpers::{
-    deserialize_json_body, request_with_auth, request_with_auth_and_body, TEST_USER_ID,
+    create_user_with_credentials, deserialize_json_body, login, request_with_auth,
+    request_with_auth_and_body, request_with_auth_headers_and_body,
+    request_with_config_headers_and_body, request_with_token, request_with_token_and_body,
+    test_config, TEST_CLIENT_ID, TEST_CLIENT_SECRET, TEST_USER_ID,
 };
 
 use chrono::{Duration, Utc};
@@ -192,6 +195,105 @@ async fn test_delete_device() {
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
 
+#[tokio::test]
+async fn test_device_if_match() {
+    let device = create_test_device().await;
+    let url = format!("/device/{}", device.id);
+
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", device.version));
+
+    let payload = UpdateDeviceDto {
+        name: Some(format!("if-match-device-{}", Uuid::new_v4())),
+        user_id: None,
+        device_os: None,
+        status: None,
+        registered_at: None,
+        modified_by: TEST_USER_ID.to_string(),
+    };
+
+    // A change with the current version succeeds and returns the new version.
+    let response = request_with_auth_headers_and_body(
+        Method::PUT,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let new_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(new_etag, format!("\"{}\"", device.version + 1));
+
+    // Changes based on the previous version are rejected.
+    let response = request_with_auth_headers_and_body(
+        Method::PUT,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = request_with_auth_headers_and_body(
+        Method::DELETE,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = request_with_auth_headers_and_body(
+        Method::DELETE,
+        url.as_str(),
+        &[("if-match", new_etag.as_str())],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_device_if_match_required() {
+    let device = create_test_device().await;
+    let url = format!("/device/{}", device.id);
+    let config = Config {
+        require_if_match: true,
+        ..test_config()
+    };
+    let token = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await.access_token;
+    let authorization = format!("Bearer {token}");
+
+    let payload = UpdateDeviceDto {
+        name: Some(format!("if-match-device-{}", Uuid::new_v4())),
+        user_id: None,
+        device_os: None,
+        status: None,
+        registered_at: None,
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_config_headers_and_body(
+        config.clone(),
+        Method::PUT,
+        url.as_str(),
+        &[("authorization", authorization.as_str())],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = request_with_config_headers_and_body(
+        config,
+        Method::PUT,
+        url.as_str(),
+        &[("authorization", authorization.as_str()), ("if-match", "*")],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
 #[tokio::test]
 async fn test_update_many_devices() {
     let existent_device = create_test_device().await;
@@ -232,3 +334,153 @@ async fn test_update_many_devices() {
     // println!("response_body.0.status: {:?}", response_body.0.status);
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
+
+/// Logs in as a new regular user and returns its user id and access token.
+async fn login_regular_user() -> (String, String) {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+    (user_id, auth_body.access_token)
+}
+
+#[tokio::test]
+async fn test_device_owner_access() {
+    let (user_id, access_token) = login_regular_user().await;
+
+    let payload = CreateDeviceDto {
+        name: format!("own-device-{}", Uuid::new_v4()),
+        user_id: user_id.clone(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: user_id.clone(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/device", &access_token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+    let url = format!("/device/{}", device.id);
+
+    let response = request_with_token(Method::GET, url.as_str(), &access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // only the user's own devices are listed
+    let response = request_with_token(Method::GET, "/device", &access_token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Vec<DeviceDto>> = deserialize_json_body(body).await.unwrap();
+    let devices = response_body.0.data.unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].id, device.id);
+
+    let payload = UpdateDeviceDto {
+        name: Some(format!("own-device-{}", Uuid::new_v4())),
+        user_id: None,
+        device_os: None,
+        status: Some(DeviceStatus::Inactive),
+        registered_at: None,
+        modified_by: user_id.clone(),
+    };
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &access_token, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::DELETE, url.as_str(), &access_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_get_device_of_other_user_forbidden() {
+    let existent_device = create_test_device().await;
+    let (_, access_token) = login_regular_user().await;
+
+    let url = format!("/device/{}", existent_device.id);
+    let response = request_with_token(Method::GET, url.as_str(), &access_token);
+
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_create_device_for_other_user_forbidden() {
+    let (user_id, access_token) = login_regular_user().await;
+
+    let payload = CreateDeviceDto {
+        name: format!("test-device-{}", Uuid::new_v4()),
+        user_id: TEST_USER_ID.to_string(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: user_id,
+    };
+    let response = request_with_token_and_body(Method::POST, "/device", &access_token, &payload);
+
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_update_device_of_other_user_forbidden() {
+    let existent_device = create_test_device().await;
+    let (user_id, access_token) = login_regular_user().await;
+
+    let payload = UpdateDeviceDto {
+        name: Some(format!("stolen-device-{}", Uuid::new_v4())),
+        user_id: Some(user_id.clone()),
+        device_os: None,
+        status: None,
+        registered_at: None,
+        modified_by: user_id,
+    };
+    let url = format!("/device/{}", existent_device.id);
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &access_token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+    assert_eq!(device.name, existent_device.name);
+    assert_eq!(device.user_id, existent_device.user_id);
+}
+
+#[tokio::test]
+async fn test_delete_device_of_other_user_forbidden() {
+    let existent_device = create_test_device().await;
+    let (_, access_token) = login_regular_user().await;
+
+    let url = format!("/device/{}", existent_device.id);
+    let response = request_with_token(Method::DELETE, url.as_str(), &access_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_update_many_devices_of_other_user_forbidden() {
+    let existent_device = create_test_device().await;
+    let (user_id, access_token) = login_regular_user().await;
+
+    let payload = UpdateManyDevicesDto {
+        devices: vec![UpdateDeviceDtoWithIdDto {
+            id: Some(existent_device.id.clone()),
+            name: format!("stolen-device-{}", Uuid::new_v4()),
+            device_os: DeviceOS::IOS,
+            status: DeviceStatus::Blocked,
+        }],
+    };
+
+    // batch update of another user's devices
+    let url = format!("/device/batch/{}", TEST_USER_ID);
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &access_token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // another user's device smuggled into the user's own batch
+    let url = format!("/device/batch/{}", user_id);
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &access_token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
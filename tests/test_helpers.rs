@@ -9,6 +9,7 @@ use axum::{
     Router,
 };
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use dotenvy::from_filename;
 use http_body_util::BodyExt;
 
@@ -18,7 +19,7 @@ use clean_axum_demo::{
         bootstrap::build_app_state,
         config::Config,
         dto::RestApiResponse,
-        jwt::{AuthBody, AuthPayload},
+        jwt::{AuthBody, AuthPayload, API_KEY_HEADER},
     },
 };
 
@@ -62,14 +63,25 @@ pub async fn setup_test_db() -> Result<PgPool, Box<dyn std::error::Error>> {
     Ok(pool)
 }
 
+/// Helper function to load the test configuration
+#[allow(dead_code)]
+pub fn test_config() -> Config {
+    load_test_env();
+    Config::from_env().unwrap()
+}
+
 /// Helper function to create a test router
 pub async fn create_test_router() -> Router {
     let pool = setup_test_db().await.unwrap();
     let config = Config::from_env().unwrap();
-    let state = build_app_state(pool, config.clone());
-    let app = create_router(state);
+    create_test_router_with_config(pool, config)
+}
 
-    app
+/// Helper function to create a test router with a custom configuration
+fn create_test_router_with_config(pool: PgPool, config: Config) -> Router {
+    let state = build_app_state(pool, config);
+
+    create_router(state)
 }
 
 /// Helper function gets the authentication token
@@ -77,9 +89,17 @@ pub async fn create_test_router() -> Router {
 /// This function is used to authenticate the test client
 #[allow(dead_code)]
 async fn get_authentication_token() -> String {
+    let auth_body = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    format!("{} {}", auth_body.token_type, auth_body.access_token)
+}
+
+/// Helper function to log in with the given credentials
+/// and return the issued token pair
+#[allow(dead_code)]
+pub async fn login(client_id: &str, client_secret: &str) -> AuthBody {
     let payload = AuthPayload {
-        client_id: TEST_CLIENT_ID.to_string(),
-        client_secret: TEST_CLIENT_SECRET.to_string(),
+        client_id: client_id.to_string(),
+        client_secret: client_secret.to_string(),
     };
 
     let response = request_with_body(Method::POST, "/auth/login", &payload);
@@ -89,9 +109,57 @@ async fn get_authentication_token() -> String {
     assert_eq!(parts.status, StatusCode::OK);
 
     let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
-    let auth_body = response_body.0.data.unwrap();
-    let token = format!("{} {}", auth_body.token_type, auth_body.access_token);
-    token
+    response_body.0.data.unwrap()
+}
+
+/// Helper function to register a new user with login credentials.
+/// Returns the user ID, username and password.
+#[allow(dead_code)]
+pub async fn create_user_with_credentials() -> (String, String, String) {
+    let username = format!("testuser-{}", uuid::Uuid::new_v4());
+    let password = uuid::Uuid::new_v4().to_string();
+
+    let payload = serde_json::json!({
+        "username": username,
+        "email": format!("{}@test.com", username),
+        "password": password,
+    });
+    let response = request_with_body(Method::POST, "/auth/register", &payload).await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let user_id = response_body.0.data.unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    (user_id, username, password)
+}
+
+/// Helper function to read a token from the last mail written to the outbox for the recipient.
+/// The token is expected on a line starting with `<label>: `.
+#[allow(dead_code)]
+pub fn read_mailed_token(recipient: &str, label: &str) -> String {
+    let mut mails: Vec<_> = std::fs::read_dir(test_config().mail_outbox_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    mails.sort();
+
+    let prefix = format!("{label}: ");
+    mails
+        .iter()
+        .rev()
+        .map(|path| std::fs::read_to_string(path).unwrap())
+        .filter(|mail| mail.contains(&format!("To: {recipient}")))
+        .find_map(|mail| {
+            mail.lines()
+                .find_map(|line| line.strip_prefix(prefix.as_str()))
+                .map(|token| token.trim().to_string())
+        })
+        .unwrap_or_else(|| panic!("no mail with a {label} found for {recipient}"))
 }
 
 /// Helper function to deserialize the body of a request into a specific type
@@ -131,6 +199,16 @@ pub async fn request(method: Method, uri: &str) -> Response<Body> {
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request, served by a router with a custom configuration
+#[allow(dead_code)]
+pub async fn request_with_config(config: Config, method: Method, uri: &str) -> Response<Body> {
+    let request = get_request(method, uri);
+    let pool = setup_test_db().await.unwrap();
+    let app = create_test_router_with_config(pool, config);
+
+    app.oneshot(request.await).await.unwrap()
+}
+
 /// Helper function to create a request with a body
 #[allow(dead_code)]
 pub async fn request_with_body<T: serde::Serialize>(
@@ -145,6 +223,106 @@ pub async fn request_with_body<T: serde::Serialize>(
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with a body, served by a router with a custom configuration
+#[allow(dead_code)]
+pub async fn request_with_config_and_body<T: serde::Serialize>(
+    config: Config,
+    method: Method,
+    uri: &str,
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let request = get_request_with_body(method, uri, &json_payload);
+    let pool = setup_test_db().await.unwrap();
+    let app = create_test_router_with_config(pool, config);
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Helper function to create a request with a body sent through a proxy on behalf of `client_ip`,
+/// served by a router with a custom configuration
+#[allow(dead_code)]
+pub async fn request_from_ip_with_body<T: serde::Serialize>(
+    config: Config,
+    client_ip: &str,
+    method: Method,
+    uri: &str,
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let mut request = get_request_with_body(method, uri, &json_payload).await;
+    request
+        .headers_mut()
+        .insert("x-forwarded-for", client_ip.parse().unwrap());
+    let pool = setup_test_db().await.unwrap();
+    let app = create_test_router_with_config(pool, config);
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Helper function to create a request with a body and additional headers
+#[allow(dead_code)]
+pub async fn request_with_headers_and_body<T: serde::Serialize>(
+    method: Method,
+    uri: &str,
+    headers: &[(&'static str, &str)],
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let mut request = get_request_with_body(method, uri, &json_payload).await;
+    for (name, value) in headers {
+        request.headers_mut().insert(*name, value.parse().unwrap());
+    }
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Helper function to create a request with a body and additional headers,
+/// served by a router with a custom configuration
+#[allow(dead_code)]
+pub async fn request_with_config_headers_and_body<T: serde::Serialize>(
+    config: Config,
+    method: Method,
+    uri: &str,
+    headers: &[(&'static str, &str)],
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let mut request = get_request_with_body(method, uri, &json_payload).await;
+    for (name, value) in headers {
+        request.headers_mut().insert(*name, value.parse().unwrap());
+    }
+    let pool = setup_test_db().await.unwrap();
+    let app = create_test_router_with_config(pool, config);
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Helper function to send a CORS preflight request from a browser at `origin`,
+/// served by a router with a custom configuration
+#[allow(dead_code)]
+pub async fn preflight(
+    config: Config,
+    origin: &str,
+    uri: &str,
+    method: Method,
+    headers: &str,
+) -> Response<Body> {
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri.to_string())
+        .header("origin", origin)
+        .header("access-control-request-method", method.as_str())
+        .header("access-control-request-headers", headers)
+        .body(Body::empty())
+        .unwrap();
+    let pool = setup_test_db().await.unwrap();
+    let app = create_test_router_with_config(pool, config);
+
+    app.oneshot(request).await.unwrap()
+}
+
 /// Helper function to create a request with authentication
 #[allow(dead_code)]
 pub async fn request_with_auth(method: Method, uri: &str) -> Response<Body> {
@@ -170,6 +348,91 @@ pub async fn request_with_auth_and_body<T: serde::Serialize>(
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with authentication, additional headers and a body
+#[allow(dead_code)]
+pub async fn request_with_auth_headers_and_body<T: serde::Serialize>(
+    method: Method,
+    uri: &str,
+    headers: &[(&'static str, &str)],
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let token = get_authentication_token().await;
+    let mut request = get_request_with_auth_and_body(method, uri, &token, &json_payload).await;
+    for (name, value) in headers {
+        request.headers_mut().insert(*name, value.parse().unwrap());
+    }
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Helper function to create a request with the given bearer token
+#[allow(dead_code)]
+pub async fn request_with_token(method: Method, uri: &str, access_token: &str) -> Response<Body> {
+    let token = format!("Bearer {}", access_token);
+    let request = get_request_with_auth(method, uri, &token);
+    let app = create_test_router().await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Helper function to create a request with the given bearer token and a body
+#[allow(dead_code)]
+pub async fn request_with_token_and_body<T: serde::Serialize>(
+    method: Method,
+    uri: &str,
+    access_token: &str,
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let token = format!("Bearer {}", access_token);
+    let request = get_request_with_auth_and_body(method, uri, &token, &json_payload);
+    let app = create_test_router().await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Helper function to create a request authenticated with the given API key
+#[allow(dead_code)]
+pub async fn request_with_api_key(method: Method, uri: &str, api_key: &str) -> Response<Body> {
+    let mut request = get_request(method, uri).await;
+    request
+        .headers_mut()
+        .insert(API_KEY_HEADER, api_key.parse().unwrap());
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Helper function to create a form-encoded request, as sent by OAuth2 clients.
+/// `client` is sent as HTTP Basic credentials if given.
+#[allow(dead_code)]
+pub async fn request_with_form(
+    uri: &str,
+    client: Option<(&str, &str)>,
+    form: &[(&str, &str)],
+) -> Response<Body> {
+    let payload = form
+        .iter()
+        .map(|(name, value)| format!("{}={}", form_encode(name), form_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri(uri.to_string())
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(ACCEPT, "application/json");
+    if let Some((client_id, client_secret)) = client {
+        let credentials = STANDARD.encode(format!("{client_id}:{client_secret}"));
+        builder = builder.header(AUTHORIZATION, format!("Basic {credentials}"));
+    }
+    let request = builder.body(Body::from(payload)).unwrap();
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
 /// Helper function to create a request with authentication and multipart data
 #[allow(dead_code)]
 pub async fn request_with_auth_and_multipart(
@@ -184,6 +447,37 @@ pub async fn request_with_auth_and_multipart(
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with authentication, additional headers and a multipart body
+#[allow(dead_code)]
+pub async fn request_with_auth_headers_and_multipart(
+    method: Method,
+    uri: &str,
+    headers: &[(&'static str, &str)],
+    payload: Vec<u8>,
+) -> Response<Body> {
+    let token = get_authentication_token().await;
+    let mut request = get_request_with_auth_and_multipart(method, uri, &token, payload).await;
+    for (name, value) in headers {
+        request.headers_mut().insert(*name, value.parse().unwrap());
+    }
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// internal helper function to percent-encode a form value
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
 /// internal helper functions to create requests
 async fn get_request(method: Method, uri: &str) -> Request<Body> {
     Request::builder()
@@ -1,15 +1,24 @@
 use axum::http::{Method, StatusCode};
+use chrono::Utc;
 
 use clean_axum_demo::{
-    common::{dto::RestApiResponse, error::AppError},
-    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    common::{
+        dto::{PageDto, RestApiResponse},
+        error::AppError,
+    },
+    domains::{
+        device::{dto::device_dto::CreateDeviceDto, DeviceOS, DeviceStatus},
+        user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    },
 };
 
 mod test_helpers;
 
 use test_helpers::{
-    deserialize_json_body, request_with_auth, request_with_auth_and_body,
-    request_with_auth_and_multipart, TEST_USER_ID,
+    create_user_with_credentials, deserialize_json_body, login, preflight, read_mailed_token,
+    request_with_auth, request_with_auth_and_body, request_with_auth_and_multipart,
+    request_with_auth_headers_and_body, request_with_auth_headers_and_multipart, request_with_body,
+    request_with_token, setup_test_db, test_config, TEST_CLIENT_ID, TEST_USER_ID,
 };
 
 async fn create_user() -> Result<(CreateUserMultipartDto, UserDto), AppError> {
@@ -151,14 +160,16 @@ async fn test_get_users() {
 
     assert_eq!(parts.status, StatusCode::OK);
 
-    let response_body: RestApiResponse<Vec<UserDto>> = deserialize_json_body(body).await.unwrap();
+    let response_body: RestApiResponse<PageDto<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::OK);
 
-    let user_dtos = response_body.0.data.unwrap();
+    let page = response_body.0.data.unwrap();
 
-    // println!("user_dtos: {:?}", user_dtos);
-    assert!(!user_dtos.is_empty());
+    assert!(!page.items.is_empty());
+    assert_eq!(page.page, 1);
+    assert!(page.total >= page.items.len() as i64);
 }
 
 #[tokio::test]
@@ -167,8 +178,7 @@ async fn test_get_user_list() {
 
     let payload = SearchUserDto {
         username: Some(username),
-        id: None,
-        email: None,
+        ..Default::default()
     };
 
     let response = request_with_auth_and_body(Method::POST, "/user/list", &payload);
@@ -177,14 +187,145 @@ async fn test_get_user_list() {
 
     assert_eq!(parts.status, StatusCode::OK);
 
-    let response_body: RestApiResponse<Vec<UserDto>> = deserialize_json_body(body).await.unwrap();
+    let response_body: RestApiResponse<PageDto<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::OK);
 
-    let user_dtos = response_body.0.data.unwrap();
+    let page = response_body.0.data.unwrap();
 
-    // println!("user_dtos: {:?}", user_dtos);
-    assert!(!user_dtos.is_empty());
+    assert!(!page.items.is_empty());
+}
+
+/// Registers users named `<prefix>-<suffix>` and returns the prefix.
+async fn register_users(suffixes: &[&str]) -> String {
+    let prefix = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+    for suffix in suffixes {
+        let username = format!("{prefix}-{suffix}");
+        let payload = serde_json::json!({
+            "username": username,
+            "email": format!("{username}@test.com"),
+            "password": uuid::Uuid::new_v4().to_string(),
+        });
+        let response = request_with_body(Method::POST, "/auth/register", &payload).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    prefix
+}
+
+async fn list_users(query: &str) -> PageDto<UserDto> {
+    let url = format!("/user?{query}");
+    let response = request_with_auth(Method::GET, url.as_str());
+
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<PageDto<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+fn usernames(page: &PageDto<UserDto>) -> Vec<String> {
+    page.items
+        .iter()
+        .map(|user| user.username.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_get_users_paginated_and_sorted() {
+    let prefix = register_users(&["b", "c", "a"]).await;
+    // Search is case-insensitive.
+    let search = prefix.to_uppercase();
+
+    let page = list_users(&format!("search={search}&sort_by=username&page_size=2")).await;
+    assert_eq!(page.total, 3);
+    assert_eq!(page.page_size, 2);
+    assert_eq!(
+        usernames(&page),
+        [format!("{prefix}-a"), format!("{prefix}-b")]
+    );
+
+    let page = list_users(&format!(
+        "search={search}&sort_by=username&page_size=2&page=2"
+    ))
+    .await;
+    assert_eq!(page.total, 3);
+    assert_eq!(usernames(&page), [format!("{prefix}-c")]);
+
+    let page = list_users(&format!("search={search}&sort_by=username&order=desc")).await;
+    assert_eq!(
+        usernames(&page),
+        [
+            format!("{prefix}-c"),
+            format!("{prefix}-b"),
+            format!("{prefix}-a")
+        ]
+    );
+
+    // Without a sort order, the oldest users come first.
+    let page = list_users(&format!("search={search}")).await;
+    assert_eq!(
+        usernames(&page),
+        [
+            format!("{prefix}-b"),
+            format!("{prefix}-c"),
+            format!("{prefix}-a")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_get_users_filters() {
+    let prefix = register_users(&["a", "b"]).await;
+
+    let page = list_users(&format!("email={prefix}-B@TEST")).await;
+    assert_eq!(usernames(&page), [format!("{prefix}-b")]);
+
+    let page = list_users(&format!(
+        "username={prefix}&created_from=2000-01-01T00:00:00Z"
+    ))
+    .await;
+    assert_eq!(page.total, 2);
+
+    let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1))
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let page = list_users(&format!("username={prefix}&created_from={tomorrow}")).await;
+    assert_eq!(page.total, 0);
+    assert!(page.items.is_empty());
+
+    let page = list_users(&format!("username={prefix}&created_to={tomorrow}")).await;
+    assert_eq!(page.total, 2);
+
+    // The email criterion of the body is applied too.
+    let payload = SearchUserDto {
+        email: Some(format!("{prefix}-a@")),
+        ..Default::default()
+    };
+    let response = request_with_auth_and_body(Method::POST, "/user/list", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<PageDto<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
+    assert_eq!(
+        usernames(&response_body.0.data.unwrap()),
+        [format!("{prefix}-a")]
+    );
+}
+
+#[tokio::test]
+async fn test_get_users_invalid_query() {
+    for query in [
+        "sort_by=password_hash",
+        "order=sideways",
+        "page=0",
+        "page_size=1000",
+        "created_from=yesterday",
+    ] {
+        let url = format!("/user?{query}");
+        let response = request_with_auth(Method::GET, url.as_str());
+        assert_eq!(response.await.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
 }
 
 #[tokio::test]
@@ -251,6 +392,224 @@ async fn test_update_user() {
     assert_eq!(user_dto.email, Some(payload.email));
 }
 
+#[tokio::test]
+async fn test_update_user_email_resets_verification() {
+    let (payload, user) = create_user().await.expect("Failed to create user");
+
+    let verify_payload = serde_json::json!({
+        "token": read_mailed_token(&payload.email, "Verification token"),
+    });
+    let response = request_with_body(Method::POST, "/auth/verify-email", &verify_payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let username = format!("update-testuser-{}", uuid::Uuid::new_v4());
+    let payload = UpdateUserDto {
+        email: format!("{}@test.com", username),
+        username,
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth_and_body(Method::PUT, url.as_str(), &payload);
+
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.data.unwrap().email_verified_at.is_none());
+
+    // A verification token is mailed to the new address.
+    read_mailed_token(&payload.email, "Verification token");
+}
+
+/// Sends a JSON merge patch for the user as the test admin.
+async fn patch_user(user_id: &str, patch: &serde_json::Value) -> axum::response::Response {
+    let url = format!("/user/{}", user_id);
+    request_with_auth_headers_and_body(
+        Method::PATCH,
+        url.as_str(),
+        &[("content-type", "application/merge-patch+json")],
+        patch,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_patch_user() {
+    let (payload, user) = create_user().await.expect("Failed to create user");
+
+    // Only the email changes; the username is kept.
+    let email = format!("patched-{}@test.com", uuid::Uuid::new_v4());
+    let response = patch_user(&user.id, &serde_json::json!({ "email": email })).await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let patched = response_body.0.data.unwrap();
+    assert_eq!(patched.username, payload.username);
+    assert_eq!(patched.email, Some(email.clone()));
+    assert!(patched.modified_by.is_some());
+    read_mailed_token(&email, "Verification token");
+
+    // Only the username changes; the email is kept.
+    let username = format!("patched-testuser-{}", uuid::Uuid::new_v4());
+    let response = patch_user(&user.id, &serde_json::json!({ "username": username })).await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let patched = response_body.0.data.unwrap();
+    assert_eq!(patched.username, username);
+    assert_eq!(patched.email, Some(email));
+
+    // An empty patch changes nothing.
+    let response = patch_user(&user.id, &serde_json::json!({})).await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().username, username);
+}
+
+#[tokio::test]
+async fn test_patch_user_cors() {
+    let response = preflight(
+        test_config(),
+        "https://app.example.com",
+        &format!("/user/{}", TEST_USER_ID),
+        Method::PATCH,
+        "content-type",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["access-control-allow-methods"]
+        .to_str()
+        .unwrap()
+        .contains("PATCH"));
+}
+
+#[tokio::test]
+async fn test_patch_user_invalid() {
+    let (payload, user) = create_user().await.expect("Failed to create user");
+
+    // Supplied fields are validated.
+    let response = patch_user(&user.id, &serde_json::json!({ "email": "not-an-email" })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = patch_user(&user.id, &serde_json::json!({ "username": "x".repeat(65) })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Required fields cannot be cleared.
+    let response = patch_user(&user.id, &serde_json::json!({ "username": null })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Unknown members are rejected.
+    let response = patch_user(&user.id, &serde_json::json!({ "nickname": "x" })).await;
+    assert!(response.status().is_client_error());
+
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let unchanged = response_body.0.data.unwrap();
+    assert_eq!(unchanged.username, payload.username);
+    assert_eq!(unchanged.email, Some(payload.email));
+
+    let response = patch_user(
+        &uuid::Uuid::new_v4().to_string(),
+        &serde_json::json!({ "username": "nobody" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_user_if_match() {
+    let (_, user) = create_user().await.expect("Failed to create user");
+    let url = format!("/user/{}", user.id);
+
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", user.version));
+
+    // A change with the current version succeeds and returns the new version.
+    let email = format!("if-match-{}@test.com", uuid::Uuid::new_v4());
+    let response = request_with_auth_headers_and_body(
+        Method::PATCH,
+        url.as_str(),
+        &[
+            ("content-type", "application/merge-patch+json"),
+            ("if-match", etag.as_str()),
+        ],
+        &serde_json::json!({ "email": email }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let new_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(new_etag, format!("\"{}\"", user.version + 1));
+
+    // Changes based on the previous version are rejected.
+    let payload = UpdateUserDto {
+        username: user.username.clone(),
+        email: format!("stale-{}@test.com", uuid::Uuid::new_v4()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_auth_headers_and_body(
+        Method::PUT,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = request_with_auth_headers_and_body(
+        Method::DELETE,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    let (_, body) = response.into_parts();
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().email, Some(email));
+
+    let response = request_with_auth_headers_and_body(
+        Method::DELETE,
+        url.as_str(),
+        &[("if-match", new_etag.as_str())],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_user_if_match_cors() {
+    let url = format!("/user/{}", TEST_USER_ID);
+    let response = preflight(
+        test_config(),
+        "https://app.example.com",
+        &url,
+        Method::PUT,
+        "if-match",
+    )
+    .await;
+    assert!(response.headers()["access-control-allow-headers"]
+        .to_str()
+        .unwrap()
+        .contains("if-match"));
+
+    // Browser clients can read the version of the user.
+    let response = request_with_auth_headers_and_body(
+        Method::GET,
+        &url,
+        &[("origin", "https://app.example.com")],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["access-control-expose-headers"], "etag");
+}
+
 #[tokio::test]
 async fn test_delete_user_not_found() {
     let non_existent_id = uuid::Uuid::new_v4();
@@ -292,6 +651,25 @@ async fn test_delete_user() {
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
 
+#[tokio::test]
+async fn test_delete_user_forbidden() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    // A regular user may not delete users, not even themselves.
+    let url = format!("/user/{}", user_id);
+    let response = request_with_token(Method::DELETE, url.as_str(), &auth_body.access_token);
+
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.status, StatusCode::FORBIDDEN);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
 #[tokio::test]
 async fn test_delete_user_file() {
     let created = create_user_with_file()
@@ -314,3 +692,332 @@ async fn test_delete_user_file() {
     // println!("response_body.0.status: {:?}", response_body.0.status);
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
+
+#[tokio::test]
+async fn test_delete_user_soft_deletes() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+    let auth_body = login(&username, &password).await;
+
+    let url = format!("/user/{}", user_id);
+    let response = request_with_auth(Method::DELETE, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The user is hidden from every query.
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let page = list_users(&format!("username={username}")).await;
+    assert_eq!(page.total, 0);
+
+    // Deleting again finds no user.
+    let response = request_with_auth(Method::DELETE, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Nor can the user be updated.
+    let payload = UpdateUserDto {
+        username: username.clone(),
+        email: format!("{}@test.com", username),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_auth_and_body(Method::PUT, url.as_str(), &payload).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The user is signed out and can no longer log in.
+    let response = request_with_token(Method::GET, "/auth/sessions", &auth_body.access_token).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let payload = serde_json::json!({ "client_id": username, "client_secret": password });
+    let response = request_with_body(Method::POST, "/auth/login", &payload).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // The record is kept, marked with the admin who deleted it.
+    let pool = setup_test_db().await.unwrap();
+    let deleted_by_admin: bool = sqlx::query_scalar(
+        "SELECT deleted_at IS NOT NULL
+            AND deleted_by = (SELECT id FROM users WHERE username = $2)
+           FROM users WHERE id = $1",
+    )
+    .bind(&user_id)
+    .bind(TEST_CLIENT_ID)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(deleted_by_admin);
+}
+
+#[tokio::test]
+async fn test_restore_user() {
+    let (user_id, username, password) = create_user_with_credentials().await;
+
+    // Only deleted users can be restored.
+    let restore_url = format!("/user/{}/restore", user_id);
+    let response = request_with_auth(Method::POST, restore_url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let url = format!("/user/{}", user_id);
+    let response = request_with_auth(Method::DELETE, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A regular user may not restore users.
+    let (_, other_username, other_password) = create_user_with_credentials().await;
+    let other_auth_body = login(&other_username, &other_password).await;
+    let response = request_with_token(
+        Method::POST,
+        restore_url.as_str(),
+        &other_auth_body.access_token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let user = response_body.0.data.unwrap();
+    assert_eq!(user.id, user_id);
+    assert_eq!(user.username, username);
+
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    login(&username, &password).await;
+
+    let url = format!("/user/{}/restore", uuid::Uuid::new_v4());
+    let response = request_with_auth(Method::POST, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_purge_user() {
+    let (_, user, _) = create_user_with_file()
+        .await
+        .expect("Failed to create user with file for purging");
+
+    let device = CreateDeviceDto {
+        name: format!("test-device-{}", uuid::Uuid::new_v4()),
+        user_id: user.id.clone(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_auth_and_body(Method::POST, "/device", &device).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let pool = setup_test_db().await.unwrap();
+    let file_relative_path: String =
+        sqlx::query_scalar("SELECT file_relative_path FROM uploaded_files WHERE user_id = $1")
+            .bind(&user.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    let file_path =
+        std::path::Path::new(&test_config().assets_private_path).join(file_relative_path);
+    assert!(file_path.exists());
+
+    // Users must be soft-deleted before they can be purged.
+    let purge_url = format!("/user/{}/purge", user.id);
+    let response = request_with_auth(Method::DELETE, purge_url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::DELETE, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = request_with_auth(Method::DELETE, purge_url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.status, StatusCode::OK);
+
+    // The user, its device and its file are gone, including the file on disk.
+    let remaining: i64 = sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM users WHERE id = $1)
+              + (SELECT COUNT(*) FROM devices WHERE user_id = $1)
+              + (SELECT COUNT(*) FROM uploaded_files WHERE user_id = $1)",
+    )
+    .bind(&user.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining, 0);
+    assert!(!file_path.exists());
+
+    let response = request_with_auth(Method::DELETE, purge_url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Builds a multipart body holding only the profile picture read from tests/asset/.
+fn profile_picture_multipart(image_file: &str) -> Vec<u8> {
+    use std::io::Write;
+
+    let file_bytes = std::fs::read(format!("tests/asset/{}", image_file))
+        .unwrap_or_else(|_| panic!("Failed to read {} from tests/asset/", image_file));
+
+    let mut multipart_body = Vec::new();
+    write!(
+        &mut multipart_body,
+        "------XYZ\r\nContent-Disposition: form-data; name=\"profile_picture\"; filename=\"{}\"\r\nContent-Type: image/png\r\n\r\n",
+        image_file
+    )
+    .unwrap();
+    multipart_body.extend_from_slice(&file_bytes);
+    write!(&mut multipart_body, "\r\n------XYZ--\r\n").unwrap();
+    multipart_body
+}
+
+/// Returns the paths on disk of the profile pictures stored for the user.
+async fn profile_picture_paths(user_id: &str) -> Vec<std::path::PathBuf> {
+    let pool = setup_test_db().await.unwrap();
+    let file_relative_paths: Vec<String> = sqlx::query_scalar(
+        "SELECT file_relative_path FROM uploaded_files
+          WHERE user_id = $1 AND file_type = 'profile_picture'",
+    )
+    .bind(user_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+
+    let base_dir = test_config().assets_private_path;
+    file_relative_paths
+        .into_iter()
+        .map(|path| std::path::Path::new(&base_dir).join(path))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_update_profile_picture() {
+    let (_, user) = create_user().await.expect("Failed to create user");
+    let url = format!("/user/{}/profile-picture", user.id);
+
+    // A user without a picture gets one.
+    let response = request_with_auth_and_multipart(
+        Method::PUT,
+        url.as_str(),
+        profile_picture_multipart("cat.png"),
+    );
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let first = response_body.0.data.unwrap();
+    assert_eq!(first.origin_file_name.as_deref(), Some("cat.png"));
+    let first_paths = profile_picture_paths(&user.id).await;
+    assert_eq!(first_paths.len(), 1);
+    assert!(first_paths[0].exists());
+
+    // Replacing it swaps the record and removes the old file from disk.
+    let response = request_with_auth_and_multipart(
+        Method::PUT,
+        url.as_str(),
+        profile_picture_multipart("mario_PNG52.png"),
+    );
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let second = response_body.0.data.unwrap();
+    assert_eq!(second.origin_file_name.as_deref(), Some("mario_PNG52.png"));
+    assert_ne!(second.file_id, first.file_id);
+
+    let second_paths = profile_picture_paths(&user.id).await;
+    assert_eq!(second_paths.len(), 1);
+    assert!(second_paths[0].exists());
+    assert!(!first_paths[0].exists());
+}
+
+#[tokio::test]
+async fn test_update_profile_picture_invalid() {
+    let (_, user) = create_user().await.expect("Failed to create user");
+
+    // The picture is required.
+    let url = format!("/user/{}/profile-picture", user.id);
+    let response =
+        request_with_auth_and_multipart(Method::PUT, url.as_str(), b"------XYZ--\r\n".to_vec());
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    let url = format!("/user/{}/profile-picture", uuid::Uuid::new_v4());
+    let response = request_with_auth_and_multipart(
+        Method::PUT,
+        url.as_str(),
+        profile_picture_multipart("cat.png"),
+    );
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_profile_picture_if_match() {
+    let (_, user) = create_user().await.expect("Failed to create user");
+    let url = format!("/user/{}/profile-picture", user.id);
+    let etag = format!("\"{}\"", user.version);
+
+    let response = request_with_auth_headers_and_multipart(
+        Method::PUT,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        profile_picture_multipart("cat.png"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let new_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(new_etag, format!("\"{}\"", user.version + 1));
+
+    // Changes based on the previous version are rejected.
+    let response = request_with_auth_headers_and_multipart(
+        Method::PUT,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        profile_picture_multipart("mario_PNG52.png"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = request_with_auth_headers_and_body(
+        Method::DELETE,
+        url.as_str(),
+        &[("if-match", etag.as_str())],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = request_with_auth_headers_and_body(
+        Method::DELETE,
+        url.as_str(),
+        &[("if-match", new_etag.as_str())],
+        &(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["etag"],
+        format!("\"{}\"", user.version + 2)
+    );
+}
+
+#[tokio::test]
+async fn test_delete_profile_picture() {
+    let (_, user, _) = create_user_with_file()
+        .await
+        .expect("Failed to create user with file");
+    let paths = profile_picture_paths(&user.id).await;
+    assert_eq!(paths.len(), 1);
+
+    let url = format!("/user/{}/profile-picture", user.id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.status, StatusCode::OK);
+    let updated = response_body.0.data.unwrap();
+    assert!(updated.file_id.is_none());
+    assert_eq!(parts.headers["etag"], format!("\"{}\"", updated.version));
+
+    assert!(!paths[0].exists());
+    let user_url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::GET, user_url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let user = response_body.0.data.unwrap();
+    assert!(user.file_id.is_none());
+    assert!(user.origin_file_name.is_none());
+
+    // There is nothing left to delete.
+    let response = request_with_auth(Method::DELETE, url.as_str()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}